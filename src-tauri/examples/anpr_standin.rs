@@ -0,0 +1,78 @@
+// src-tauri/examples/anpr_standin.rs
+// Minimal ANPR camera stand-in for exercising the plate cross-check without a camera.
+// Any GET is answered with a plate read, as a camera's trigger endpoint would. With a
+// push address it also posts the plate as an event every few seconds.
+//
+//   cargo run --example anpr_standin -- [listen_addr] [plate] [push_addr] [interval_secs]
+//   cargo run --example anpr_standin -- 127.0.0.1:8092 "B 1234 XYZ" 127.0.0.1:8091 10
+//
+// Configure a camera with `ip = "127.0.0.1"` and
+// `trigger_url = "http://127.0.0.1:8092/plate/read"`, and set `anpr_enabled = true`.
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+fn plate_json(plate: &str) -> String {
+    format!(r#"{{"plate":"{}","confidence":94}}"#, plate)
+}
+
+fn answer_trigger(stream: TcpStream, plate: &str) -> std::io::Result<()> {
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 2 {
+        line.clear();
+    }
+    println!("Trigger: {}", request_line.trim_end());
+    let body = plate_json(plate);
+    write!(
+        &stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        body.len(),
+        body,
+    )
+}
+
+fn push_event(address: &str, plate: &str) -> std::io::Result<()> {
+    let mut stream = TcpStream::connect(address)?;
+    let body = plate_json(plate);
+    write!(
+        stream,
+        "POST /anpr HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        address,
+        body.len(),
+        body,
+    )?;
+    let mut status = String::new();
+    BufReader::new(&stream).read_line(&mut status)?;
+    println!("Pushed {} to {}: {}", plate, address, status.trim_end());
+    Ok(())
+}
+
+fn main() -> std::io::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let address = args.first().cloned().unwrap_or_else(|| "127.0.0.1:8092".to_string());
+    let plate = args.get(1).cloned().unwrap_or_else(|| "B 1234 XYZ".to_string());
+
+    if let Some(push_address) = args.get(2).cloned() {
+        let interval = Duration::from_secs(args.get(3).and_then(|v| v.parse().ok()).unwrap_or(10));
+        let plate = plate.clone();
+        thread::spawn(move || loop {
+            if let Err(e) = push_event(&push_address, &plate) {
+                println!("Push to {} failed: {}", push_address, e);
+            }
+            thread::sleep(interval);
+        });
+    }
+
+    let listener = TcpListener::bind(&address)?;
+    println!("ANPR stand-in listening on {}, reading plate {}", address, plate);
+    for stream in listener.incoming() {
+        if let Err(e) = answer_trigger(stream?, &plate) {
+            println!("Trigger request failed: {}", e);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,72 @@
+// src-tauri/examples/container_ocr_standin.rs
+// Minimal container OCR stand-in for exercising the gate-pass check without an OCR
+// system. It sends each container given on the command line to the lane, either as
+// plain TCP lines (`tcp`) or as one JSON POST (`http`). A container is the number,
+// optionally followed by its ISO code after a slash.
+//
+//   cargo run --example container_ocr_standin -- [lane_addr] [tcp|http] [container[/iso]...]
+//   cargo run --example container_ocr_standin -- 127.0.0.1:8093 http CSQU3054383/45G1 MSKU9070323/22G1
+//
+// Add `127.0.0.1` to `ocr_sources` and set `ocr_enabled = true`.
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+
+struct Recognition {
+    number: String,
+    iso_code: Option<String>,
+}
+
+fn send_lines(address: &str, recognitions: &[Recognition]) -> std::io::Result<()> {
+    let mut stream = TcpStream::connect(address)?;
+    for recognition in recognitions {
+        let line = match &recognition.iso_code {
+            Some(iso_code) => format!("{};{}", recognition.number, iso_code),
+            None => recognition.number.clone(),
+        };
+        writeln!(stream, "{}", line)?;
+        println!("Sent {} to {}", line, address);
+    }
+    Ok(())
+}
+
+fn send_http(address: &str, recognitions: &[Recognition]) -> std::io::Result<()> {
+    let containers: Vec<String> = recognitions.iter()
+        .map(|recognition| match &recognition.iso_code {
+            Some(iso_code) => format!(r#"{{"containerNumber":"{}","isoCode":"{}"}}"#, recognition.number, iso_code),
+            None => format!(r#"{{"containerNumber":"{}"}}"#, recognition.number),
+        })
+        .collect();
+    let body = format!(r#"{{"containers":[{}]}}"#, containers.join(","));
+    let mut stream = TcpStream::connect(address)?;
+    write!(
+        stream,
+        "POST /ocr HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        address,
+        body.len(),
+        body,
+    )?;
+    let mut status = String::new();
+    BufReader::new(&stream).read_line(&mut status)?;
+    println!("Posted {} to {}: {}", body, address, status.trim_end());
+    Ok(())
+}
+
+fn main() -> std::io::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let address = args.first().cloned().unwrap_or_else(|| "127.0.0.1:8093".to_string());
+    let mode = args.get(1).cloned().unwrap_or_else(|| "tcp".to_string());
+    let mut recognitions: Vec<Recognition> = args.iter().skip(2)
+        .map(|arg| match arg.split_once('/') {
+            Some((number, iso_code)) => Recognition { number: number.to_string(), iso_code: Some(iso_code.to_string()) },
+            None => Recognition { number: arg.clone(), iso_code: None },
+        })
+        .collect();
+    if recognitions.is_empty() {
+        recognitions.push(Recognition { number: "CSQU3054383".to_string(), iso_code: Some("45G1".to_string()) });
+    }
+
+    match mode.as_str() {
+        "http" => send_http(&address, &recognitions),
+        _ => send_lines(&address, &recognitions),
+    }
+}
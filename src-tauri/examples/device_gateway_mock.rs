@@ -0,0 +1,135 @@
+// src-tauri/examples/device_gateway_mock.rs
+// Minimal DeviceGatewayService mock for exercising the heartbeat client without the
+// real service. It prints every status report, hands out the device commands given
+// on the command line until the lane acknowledges them, and accepts everything.
+//
+//   cargo run --example device_gateway_mock -- [listen_addr] [command...]
+//   cargo run --example device_gateway_mock -- 127.0.0.1:8089 RESTART_READERS SYNC_BLACKLIST
+//
+// Point `device_gateway_url` at http://<listen_addr>/DeviceGatewayService.asmx and
+// set `device_gateway_enabled = true`.
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+struct PendingCommand {
+    id: String,
+    command: String,
+}
+
+struct Request {
+    soap_action: String,
+    body: String,
+}
+
+fn read_request(stream: &TcpStream) -> std::io::Result<Request> {
+    let mut reader = BufReader::new(stream);
+    let mut soap_action = String::new();
+    let mut content_length = 0;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            break;
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            match name.trim().to_ascii_lowercase().as_str() {
+                "content-length" => content_length = value.trim().parse().unwrap_or(0),
+                "soapaction" => soap_action = value.trim().trim_matches('"').to_string(),
+                _ => {}
+            }
+        }
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    Ok(Request { soap_action, body: String::from_utf8_lossy(&body).into_owned() })
+}
+
+fn element<'a>(xml: &'a str, tag: &str) -> &'a str {
+    xml.split(&format!("<{}>", tag))
+        .nth(1)
+        .and_then(|rest| rest.split(&format!("</{}>", tag)).next())
+        .unwrap_or("")
+}
+
+fn envelope(operation: &str, result: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="utf-8"?><soap:Envelope xmlns:soap="http://schemas.xmlsoap.org/soap/envelope/"><soap:Body><{op}Response xmlns="http://halotec-indonesia.com/"><{op}Result>{result}</{op}Result></{op}Response></soap:Body></soap:Envelope>"#,
+        op = operation,
+        result = result,
+    )
+}
+
+fn handle(request: &Request, pending: &mut Vec<PendingCommand>) -> String {
+    let operation = request.soap_action.rsplit('/').next().unwrap_or_default();
+    match operation {
+        "ReportDeviceStatus" => {
+            println!(
+                "Status from {} (version {}):",
+                element(&request.body, "gateId"),
+                element(&request.body, "softwareVersion"),
+            );
+            for device in request.body.split("<DeviceStatus>").skip(1) {
+                println!(
+                    "  {:<14} {:<8} {}",
+                    element(device, "device"),
+                    element(device, "status"),
+                    element(device, "detail"),
+                );
+            }
+            envelope(operation, "true")
+        }
+        "GetPendingCommands" => {
+            let commands: String = pending.iter()
+                .map(|c| format!("<DeviceCommand><commandId>{}</commandId><command>{}</command></DeviceCommand>", c.id, c.command))
+                .collect();
+            envelope(operation, &commands)
+        }
+        "AcknowledgeCommand" => {
+            let id = element(&request.body, "commandId");
+            println!(
+                "Command {} acknowledged: success={} {}",
+                id,
+                element(&request.body, "success"),
+                element(&request.body, "message"),
+            );
+            pending.retain(|c| c.id != id);
+            envelope(operation, "true")
+        }
+        other => {
+            println!("Unknown operation '{}'", other);
+            envelope(other, "false")
+        }
+    }
+}
+
+fn main() -> std::io::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let address = args.first().cloned().unwrap_or_else(|| "127.0.0.1:8089".to_string());
+    let mut pending: Vec<PendingCommand> = args.iter().skip(1).enumerate()
+        .map(|(i, command)| PendingCommand { id: format!("CMD{:04}", i + 1), command: command.to_uppercase() })
+        .collect();
+
+    let listener = TcpListener::bind(&address)?;
+    println!("DeviceGatewayService mock listening on {}, {} command(s) queued", address, pending.len());
+    for stream in listener.incoming() {
+        let mut stream = stream?;
+        let response = match read_request(&stream) {
+            Ok(request) => handle(&request, &mut pending),
+            Err(e) => {
+                println!("Bad request: {}", e);
+                continue;
+            }
+        };
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: text/xml; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            response.len(),
+            response,
+        )?;
+    }
+    Ok(())
+}
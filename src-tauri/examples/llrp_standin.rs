@@ -0,0 +1,172 @@
+// src-tauri/examples/llrp_standin.rs
+// Minimal LLRP reader stand-in for exercising the UHF integration without hardware.
+// It accepts one client at a time, acknowledges ROSpec setup with success and,
+// once a ROSpec is enabled, reports a tag every few seconds.
+//
+//   cargo run --example llrp_standin -- [listen_addr] [epc_hex] [interval_secs] [rssi]
+//   cargo run --example llrp_standin -- 127.0.0.1:5084 E2801160600002084D5E71A1 3 -55
+//
+// Point `uhf_reader_ip`/`uhf_reader_port` at it and set `uhf_enabled = true`.
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+const MSG_CLOSE_CONNECTION_RESPONSE: u16 = 4;
+const MSG_CLOSE_CONNECTION: u16 = 14;
+const MSG_RO_ACCESS_REPORT: u16 = 61;
+const MSG_READER_EVENT_NOTIFICATION: u16 = 63;
+const MSG_KEEPALIVE_ACK: u16 = 72;
+
+struct Settings {
+    epc: Vec<u8>,
+    interval: Duration,
+    rssi: i8,
+}
+
+fn frame(message_type: u16, id: u32, body: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(body.len() + 10);
+    out.extend_from_slice(&((1u16 << 10) | message_type).to_be_bytes());
+    out.extend_from_slice(&((body.len() + 10) as u32).to_be_bytes());
+    out.extend_from_slice(&id.to_be_bytes());
+    out.extend_from_slice(body);
+    out
+}
+
+fn tlv(param_type: u16, content: &[u8]) -> Vec<u8> {
+    let mut out = param_type.to_be_bytes().to_vec();
+    out.extend_from_slice(&((content.len() + 4) as u16).to_be_bytes());
+    out.extend_from_slice(content);
+    out
+}
+
+fn success_status() -> Vec<u8> {
+    tlv(287, &[0, 0, 0, 0]) // LLRPStatus: code 0, empty description
+}
+
+fn connection_event() -> Vec<u8> {
+    let timestamp = tlv(128, &0u64.to_be_bytes()); // UTCTimestamp
+    let attempt = tlv(256, &[0, 0]); // ConnectionAttemptEvent: success
+    tlv(246, &[timestamp, attempt].concat()) // ReaderEventNotificationData
+}
+
+fn tag_report(settings: &Settings) -> Vec<u8> {
+    let mut data = Vec::new();
+    if settings.epc.len() == 12 {
+        data.push(0x80 | 13); // EPC-96
+        data.extend_from_slice(&settings.epc);
+    } else {
+        let mut epc = ((settings.epc.len() * 8) as u16).to_be_bytes().to_vec();
+        epc.extend_from_slice(&settings.epc);
+        data.extend(tlv(241, &epc)); // EPCData
+    }
+    data.push(0x80 | 1); // AntennaID
+    data.extend_from_slice(&1u16.to_be_bytes());
+    data.push(0x80 | 6); // PeakRSSI
+    data.push(settings.rssi as u8);
+    tlv(240, &data) // TagReportData
+}
+
+fn parse_hex(value: &str) -> Option<Vec<u8>> {
+    if value.len() % 2 != 0 {
+        return None;
+    }
+    (0..value.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&value[i..i + 2], 16).ok())
+        .collect()
+}
+
+fn serve(stream: TcpStream, settings: Arc<Settings>) -> std::io::Result<()> {
+    let writer = Arc::new(Mutex::new(stream.try_clone()?));
+    let reporting = Arc::new(AtomicBool::new(false));
+    let connected = Arc::new(AtomicBool::new(true));
+    writer.lock().unwrap().write_all(&frame(MSG_READER_EVENT_NOTIFICATION, 0, &connection_event()))?;
+
+    let report_thread = {
+        let writer = Arc::clone(&writer);
+        let reporting = Arc::clone(&reporting);
+        let connected = Arc::clone(&connected);
+        let settings = Arc::clone(&settings);
+        thread::spawn(move || {
+            let mut id = 1000;
+            while connected.load(Ordering::Acquire) {
+                thread::sleep(settings.interval);
+                if reporting.load(Ordering::Acquire) {
+                    id += 1;
+                    let report = frame(MSG_RO_ACCESS_REPORT, id, &tag_report(&settings));
+                    if writer.lock().unwrap().write_all(&report).is_err() {
+                        break;
+                    }
+                    println!("-> RO_ACCESS_REPORT");
+                }
+            }
+        })
+    };
+
+    let mut reader = stream;
+    loop {
+        let mut header = [0u8; 10];
+        if reader.read_exact(&mut header).is_err() {
+            break;
+        }
+        let message_type = u16::from_be_bytes([header[0], header[1]]) & 0x03ff;
+        let length = u32::from_be_bytes([header[2], header[3], header[4], header[5]]) as usize;
+        let id = u32::from_be_bytes([header[6], header[7], header[8], header[9]]);
+        let mut body = vec![0u8; length.saturating_sub(10)];
+        reader.read_exact(&mut body)?;
+        println!("<- message {} (id {}, {} bytes)", message_type, id, length);
+
+        let response = match message_type {
+            MSG_KEEPALIVE_ACK => None,
+            MSG_CLOSE_CONNECTION => {
+                let close = frame(MSG_CLOSE_CONNECTION_RESPONSE, id, &success_status());
+                writer.lock().unwrap().write_all(&close)?;
+                break;
+            }
+            // ADD/DELETE/START/STOP/ENABLE/DISABLE_ROSPEC answer with type + 10.
+            20..=25 => {
+                if message_type == 24 || message_type == 22 {
+                    reporting.store(true, Ordering::Release);
+                }
+                if message_type == 21 || message_type == 25 || message_type == 23 {
+                    reporting.store(false, Ordering::Release);
+                }
+                Some(message_type + 10)
+            }
+            3 => Some(13), // SET_READER_CONFIG
+            _ => Some(100), // ERROR_MESSAGE
+        };
+        if let Some(response_type) = response {
+            let status = if response_type == 100 { tlv(287, &[0, 100, 0, 0]) } else { success_status() };
+            writer.lock().unwrap().write_all(&frame(response_type, id, &status))?;
+        }
+    }
+
+    connected.store(false, Ordering::Release);
+    let _ = report_thread.join();
+    Ok(())
+}
+
+fn main() -> std::io::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let address = args.first().cloned().unwrap_or_else(|| "127.0.0.1:5084".to_string());
+    let epc = args.get(1).and_then(|v| parse_hex(v)).unwrap_or_else(|| parse_hex("E2801160600002084D5E71A1").unwrap());
+    let interval = Duration::from_secs(args.get(2).and_then(|v| v.parse().ok()).unwrap_or(3));
+    let rssi = args.get(3).and_then(|v| v.parse().ok()).unwrap_or(-55);
+    let settings = Arc::new(Settings { epc, interval, rssi });
+
+    let listener = TcpListener::bind(&address)?;
+    println!("LLRP stand-in listening on {}", address);
+    for stream in listener.incoming() {
+        let stream = stream?;
+        println!("Client connected from {:?}", stream.peer_addr());
+        if let Err(e) = serve(stream, Arc::clone(&settings)) {
+            println!("Client session ended: {}", e);
+        }
+        println!("Client disconnected");
+    }
+    Ok(())
+}
@@ -1,11 +1,41 @@
 // src-tauri/src/adam_handler.rs
-use crate::config_handler::AppConfigState;
+use crate::audit_handler::{AuditKind, AuditLogState};
+use crate::auth_handler::{self, AuthState, Role};
+use crate::config_handler::{AppConfig, AppConfigState};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 use tauri::State;
 use tokio_modbus::client::Context;
 use tokio_modbus::prelude::*;
 
 const PORTAL_OPEN_COIL_ADDRESS: u16 = 0x0000;
 const PUSH_BUTTON_1_STATUS_REGISTER: u16 = 0x0000;
+const PORTAL_AUTHORIZATION_TTL: Duration = Duration::from_secs(120);
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Transactions that completed TruckIn and may open the portal once without a
+/// supervisor session. Everything else is a manual open.
+#[derive(Default)]
+pub struct PortalAuthorizationState(pub Mutex<HashMap<String, Instant>>);
+
+impl PortalAuthorizationState {
+    pub fn grant(&self, transaction_id: &str) {
+        if let Ok(mut pending) = self.0.lock() {
+            pending.retain(|_, granted| granted.elapsed() < PORTAL_AUTHORIZATION_TTL);
+            pending.insert(transaction_id.to_string(), Instant::now());
+        }
+    }
+
+    fn consume(&self, transaction_id: &str) -> bool {
+        match self.0.lock() {
+            Ok(mut pending) => pending
+                .remove(transaction_id)
+                .is_some_and(|granted| granted.elapsed() < PORTAL_AUTHORIZATION_TTL),
+            Err(_) => false,
+        }
+    }
+}
 
 async fn connect_adam_tcp(ip: &str, port: u16) -> Result<Context, String> {
     let socket_addr_str = format!("{}:{}", ip, port);
@@ -18,22 +48,63 @@ async fn connect_adam_tcp(ip: &str, port: u16) -> Result<Context, String> {
         .map_err(|e| format!("ADAM: Modbus TCP connect error to {}: {}", socket_addr_str, e))
 }
 
+/// Checks that the module at `ip:port` accepts a Modbus TCP connection.
+pub async fn probe(ip: &str, port: u16) -> Result<(), String> {
+    tokio::time::timeout(PROBE_TIMEOUT, connect_adam_tcp(ip, port))
+        .await
+        .map_err(|_| format!("ADAM: No answer from {}:{}", ip, port))?
+        .map(|_| ())
+}
+
+/// Pulses the portal OPEN coil. Callers are responsible for authorizing the open.
+pub async fn open_portal(config: &AppConfig) -> Result<(), String> {
+    let mut ctx = connect_adam_tcp(&config.adam_portal_ip, config.adam_portal_port).await?;
+    log::info!("ADAM Portal: Sending OPEN command to {}:{}", config.adam_portal_ip, config.adam_portal_port);
+    ctx.write_single_coil(PORTAL_OPEN_COIL_ADDRESS, true).await
+        .map_err(|e| format!("ADAM: Failed to write 'open' coil (ON): {}", e))?
+        .map_err(|e| format!("ADAM: Modbus exception writing 'open' coil (ON): {:?}", e))?;
+    tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+    ctx.write_single_coil(PORTAL_OPEN_COIL_ADDRESS, false).await
+        .map_err(|e| format!("ADAM: Failed to write 'open' coil (OFF): {}", e))?
+        .map_err(|e| format!("ADAM: Modbus exception writing 'open' coil (OFF): {:?}", e))?;
+    Ok(())
+}
+
 #[tauri::command]
 pub async fn control_adam_portal_command(
     action: String,
+    session_token: Option<String>,
+    transaction_id: Option<String>,
     config_state: State<'_, AppConfigState>,
+    auth_state: State<'_, AuthState>,
+    portal_auth_state: State<'_, PortalAuthorizationState>,
+    audit_state: State<'_, AuditLogState>,
 ) -> Result<String, String> {
     let config = config_state.0.lock().unwrap().clone();
-    let mut ctx = connect_adam_tcp(&config.adam_portal_ip, config.adam_portal_port).await?;
 
     match action.to_lowercase().as_str() {
         "open" => {
-            log::info!("ADAM Portal: Sending OPEN command to {}:{}", config.adam_portal_ip, config.adam_portal_port);
-            ctx.write_single_coil(PORTAL_OPEN_COIL_ADDRESS, true).await
-                .map_err(|e| format!("ADAM: Failed to write 'open' coil (ON): {}", e))?;
-            tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
-            ctx.write_single_coil(PORTAL_OPEN_COIL_ADDRESS, false).await
-                .map_err(|e| format!("ADAM: Failed to write 'open' coil (OFF): {}", e))?;
+            let (actor, manual) = match transaction_id.as_deref() {
+                Some(tx_id) if portal_auth_state.consume(tx_id) => {
+                    log::info!("ADAM Portal: Open authorized by completed transaction {}", tx_id);
+                    ("system".to_string(), false)
+                }
+                _ => {
+                    // Not tied to a completed transaction: this is a manual open.
+                    let session = auth_handler::require_role(&auth_state, &config_state, session_token.as_deref().unwrap_or_default(), Role::Supervisor)?;
+                    log::info!("ADAM Portal: Manual open authorized by supervisor {}", session.username);
+                    (session.username, true)
+                }
+            };
+            let result = open_portal(&config).await;
+            audit_state.record(AuditKind::PortalActuation, &actor, &config.gate_name, serde_json::json!({
+                "action": "open",
+                "manual": manual,
+                "transaction_id": transaction_id,
+                "success": result.is_ok(),
+                "error": result.as_ref().err(),
+            }));
+            result?;
             Ok(format!("ADAM Portal command '{}' sent.", action))
         }
         "close" => {
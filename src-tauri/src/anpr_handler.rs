@@ -0,0 +1,419 @@
+// src-tauri/src/anpr_handler.rs
+// ANPR plate cameras. Cameras push plate events to a small HTTP listener, or are
+// asked for a read over HTTP when a tag is validated; the plate is compared with
+// the police number registered to the tag and a mismatch alerts the operator.
+// `examples/anpr_standin.rs` is a camera stand-in for trying this without hardware.
+use serde::Serialize;
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{Emitter, Manager, State};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Notify;
+
+use crate::config_handler::{AnprCameraConfig, AppConfig, AppConfigState};
+
+const TRIGGER_TIMEOUT: Duration = Duration::from_secs(5);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+const RESTART_DELAY: Duration = Duration::from_secs(5);
+const DISABLED_RECHECK_INTERVAL: Duration = Duration::from_secs(10);
+/// A pushed plate is taken as the current truck's only if it was read this recently.
+const PLATE_VALIDITY: Duration = Duration::from_secs(60);
+const MAX_EVENT_SIZE: usize = 64 * 1024;
+
+/// JSON keys and XML elements cameras put the plate text in.
+const PLATE_FIELDS: &[&str] = &["plate", "plateNumber", "plate_number", "licensePlate", "PlateNumber", "LicensePlate"];
+const CONFIDENCE_FIELDS: &[&str] = &["confidence", "confidenceLevel", "Confidence"];
+
+/// A plate read by a camera, normalized to `B 1234 XYZ`.
+#[derive(Debug, Serialize, Clone)]
+pub struct PlateRead {
+    pub plate: String,
+    /// The text as the camera sent it.
+    pub raw: String,
+    pub camera_id: String,
+    pub confidence: Option<u8>,
+    pub timestamp: String,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PlateCheckOutcome {
+    Match,
+    Mismatch,
+    /// No camera produced a readable plate.
+    NoRead,
+    /// The tag carries no usable police number to compare with.
+    Unregistered,
+    Disabled,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct PlateCheck {
+    pub outcome: PlateCheckOutcome,
+    pub read: Option<PlateRead>,
+    /// The tag's police number, normalized when it could be.
+    pub registered: Option<String>,
+}
+
+#[derive(Default)]
+pub struct AnprState {
+    latest: Mutex<Option<(PlateRead, Instant)>>,
+    restart: Notify,
+}
+
+impl AnprState {
+    /// The plate of the truck in the lane, if one was read recently enough.
+    pub fn current_plate(&self) -> Option<String> {
+        self.current_read().map(|read| read.plate)
+    }
+
+    fn current_read(&self) -> Option<PlateRead> {
+        self.latest.lock().ok()?
+            .as_ref()
+            .filter(|(_, seen)| seen.elapsed() <= PLATE_VALIDITY)
+            .map(|(read, _)| read.clone())
+    }
+
+    /// Forgets the plate once the truck it belongs to has gone through.
+    pub fn clear(&self) {
+        if let Ok(mut latest) = self.latest.lock() {
+            *latest = None;
+        }
+    }
+
+    /// Closes the listener so it is opened again with the current settings.
+    pub fn request_restart(&self) {
+        self.restart.notify_one();
+    }
+
+    fn record(&self, app_handle: &tauri::AppHandle, read: PlateRead) {
+        log::info!("ANPR: Plate {} from camera {} (confidence {:?})", read.plate, read.camera_id, read.confidence);
+        if let Ok(mut latest) = self.latest.lock() {
+            *latest = Some((read.clone(), Instant::now()));
+        }
+        if let Err(e) = app_handle.emit("anpr_plate_read", &read) {
+            log::error!("Failed to emit anpr_plate_read event: {}", e);
+        }
+    }
+}
+
+/// Indonesian plates are a one or two letter region code, up to four digits and up
+/// to three suffix letters. Spacing, dashes and case vary between cameras and the
+/// registration data, so both sides are brought to `B 1234 XYZ` before comparing.
+pub fn normalize_plate(raw: &str) -> Option<String> {
+    let compact: String = raw.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    let region_len = compact.chars().take_while(char::is_ascii_alphabetic).count();
+    let digits_len = compact[region_len..].chars().take_while(char::is_ascii_digit).count();
+    let (region, rest) = compact.split_at(region_len);
+    let (digits, suffix) = rest.split_at(digits_len);
+    if !(1..=2).contains(&region.len())
+        || !(1..=4).contains(&digits.len())
+        || digits.starts_with('0')
+        || suffix.len() > 3
+        || !suffix.chars().all(|c| c.is_ascii_alphabetic())
+    {
+        return None;
+    }
+    Some([region, digits, suffix].iter().filter(|part| !part.is_empty()).copied().collect::<Vec<_>>().join(" "))
+}
+
+fn json_field(value: &serde_json::Value, names: &[&str]) -> Option<serde_json::Value> {
+    match value {
+        serde_json::Value::Object(map) => names.iter()
+            .find_map(|name| map.get(*name).filter(|v| !v.is_null()).cloned())
+            .or_else(|| map.values().find_map(|v| json_field(v, names))),
+        serde_json::Value::Array(items) => items.iter().find_map(|v| json_field(v, names)),
+        _ => None,
+    }
+}
+
+/// `<name>text</name>`, or an ONVIF `<tt:SimpleItem Name="name" Value="text"/>`.
+fn xml_field(body: &str, names: &[&str]) -> Option<String> {
+    names.iter().find_map(|name| {
+        let element = body.split(&format!("<{}>", name)).nth(1)
+            .and_then(|rest| rest.split(&format!("</{}>", name)).next());
+        let simple_item = || body.split(&format!("Name=\"{}\"", name)).nth(1)
+            .and_then(|rest| rest.split("Value=\"").nth(1))
+            .and_then(|rest| rest.split('"').next());
+        element.or_else(simple_item).map(|text| text.trim().to_string()).filter(|text| !text.is_empty())
+    })
+}
+
+/// Confidence as a percentage; some cameras send a 0..1 likelihood instead.
+fn percent(confidence: f64) -> u8 {
+    let confidence = if confidence <= 1.0 { confidence * 100.0 } else { confidence };
+    confidence.clamp(0.0, 100.0).round() as u8
+}
+
+/// Plate text and confidence from a camera's event or trigger response.
+fn parse_plate_event(body: &str) -> Option<(String, Option<u8>)> {
+    if let Ok(json) = serde_json::from_str::<serde_json::Value>(body) {
+        let plate = json_field(&json, PLATE_FIELDS)?.as_str()?.to_string();
+        let confidence = json_field(&json, CONFIDENCE_FIELDS)
+            .and_then(|v| v.as_f64().or_else(|| v.as_str()?.parse().ok()));
+        return Some((plate, confidence.map(percent)));
+    }
+    let plate = xml_field(body, PLATE_FIELDS)?;
+    let confidence = xml_field(body, CONFIDENCE_FIELDS).and_then(|c| c.parse::<f64>().ok());
+    Some((plate, confidence.map(percent)))
+}
+
+fn plate_read(camera: &AnprCameraConfig, body: &str) -> Result<PlateRead, String> {
+    let (raw, confidence) = parse_plate_event(body)
+        .ok_or_else(|| format!("Camera {} sent no plate", camera.id))?;
+    let plate = normalize_plate(&raw)
+        .ok_or_else(|| format!("Camera {} read '{}', which is not a plate", camera.id, raw))?;
+    Ok(PlateRead { plate, raw, camera_id: camera.id.clone(), confidence, timestamp: chrono::Local::now().to_rfc3339() })
+}
+
+/// Asks a camera to read the plate in front of it now.
+async fn trigger_read(camera: &AnprCameraConfig) -> Result<PlateRead, String> {
+    let client = reqwest::Client::builder()
+        .timeout(TRIGGER_TIMEOUT)
+        .build()
+        .map_err(|e| format!("Failed to build HTTP client: {}", e))?;
+    let response = client.get(&camera.trigger_url).send().await
+        .map_err(|e| format!("Camera {} trigger failed: {}", camera.id, e))?;
+    if !response.status().is_success() {
+        return Err(format!("Camera {} trigger returned {}", camera.id, response.status()));
+    }
+    let body = response.text().await
+        .map_err(|e| format!("Failed to read camera {} response: {}", camera.id, e))?;
+    plate_read(camera, &body)
+}
+
+/// Reads the request off `stream`; only the body is of interest.
+pub(crate) async fn read_http_body(stream: &mut TcpStream) -> Result<String, String> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let header_end = loop {
+        let read = stream.read(&mut chunk).await.map_err(|e| e.to_string())?;
+        if read == 0 {
+            return Err("Connection closed before the request was complete".to_string());
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+        if let Some(position) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+            break position + 4;
+        }
+        if buffer.len() > MAX_EVENT_SIZE {
+            return Err("Request headers too large".to_string());
+        }
+    };
+    let headers = String::from_utf8_lossy(&buffer[..header_end]).to_string();
+    let content_length = headers.lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+        .unwrap_or(0);
+    if content_length > MAX_EVENT_SIZE {
+        return Err(format!("Event of {} bytes is too large", content_length));
+    }
+    while buffer.len() < header_end + content_length {
+        let read = stream.read(&mut chunk).await.map_err(|e| e.to_string())?;
+        if read == 0 {
+            break;
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+    }
+    let end = buffer.len().min(header_end + content_length);
+    Ok(String::from_utf8_lossy(&buffer[header_end..end]).to_string())
+}
+
+async fn handle_event(app_handle: &tauri::AppHandle, camera: &AnprCameraConfig, mut stream: TcpStream) -> Result<(), String> {
+    let body = tokio::time::timeout(REQUEST_TIMEOUT, read_http_body(&mut stream)).await
+        .map_err(|_| format!("Camera {} did not send its event in time", camera.id))??;
+    let result = plate_read(camera, &body);
+    let status = if result.is_ok() { "200 OK" } else { "400 Bad Request" };
+    let _ = stream.write_all(format!("HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status).as_bytes()).await;
+    app_handle.state::<AnprState>().record(app_handle, result?);
+    Ok(())
+}
+
+/// Accepts connections on `port` until the listener fails or `restart` is notified.
+/// `accept` returns the handling of a connection, or `None` for a peer that is not
+/// a configured `device`; each connection is handled on its own task.
+pub(crate) async fn accept_connections<Fut>(
+    name: &str,
+    device: &str,
+    port: u16,
+    restart: &Notify,
+    mut accept: impl FnMut(TcpStream, SocketAddr) -> Option<Fut>,
+) -> Result<(), String>
+where
+    Fut: Future<Output = Result<(), String>> + Send + 'static,
+{
+    let listener = TcpListener::bind(("0.0.0.0", port)).await
+        .map_err(|e| format!("Failed to listen on port {}: {}", port, e))?;
+    log::info!("{}: Listening on port {}", name, port);
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (stream, peer) = accepted.map_err(|e| format!("Failed to accept connection: {}", e))?;
+                let Some(handling) = accept(stream, peer) else {
+                    log::warn!("{}: Ignoring connection from {}, not a configured {}", name, peer, device);
+                    continue;
+                };
+                let name = name.to_string();
+                tauri::async_runtime::spawn(async move {
+                    if let Err(e) = handling.await {
+                        log::warn!("{}: {}", name, e);
+                    }
+                });
+            }
+            _ = restart.notified() => return Ok(()),
+        }
+    }
+}
+
+/// Keeps a listener running while `enabled` says so, opening it again with the
+/// current settings after it fails or a restart is requested.
+pub(crate) fn spawn_listener<F, Fut>(app_handle: tauri::AppHandle, name: &'static str, enabled: fn(&AppConfig) -> bool, run: F)
+where
+    F: Fn(tauri::AppHandle, AppConfig) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<(), String>> + Send,
+{
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let config = app_handle.state::<AppConfigState>().0.lock().ok().map(|c| c.clone());
+            let Some(config) = config else {
+                log::error!("{}: Failed to acquire config lock", name);
+                tokio::time::sleep(RESTART_DELAY).await;
+                continue;
+            };
+            if !enabled(&config) {
+                tokio::time::sleep(DISABLED_RECHECK_INTERVAL).await;
+                continue;
+            }
+            match run(app_handle.clone(), config).await {
+                Ok(()) => continue,
+                Err(e) => log::error!("{}: {}", name, e),
+            }
+            tokio::time::sleep(RESTART_DELAY).await;
+        }
+    });
+}
+
+/// Accepts plate events until the listener fails or a restart is requested.
+async fn run_listener(app_handle: tauri::AppHandle, config: AppConfig) -> Result<(), String> {
+    let anpr_state = app_handle.state::<AnprState>();
+    accept_connections("ANPR", "camera", config.anpr_listen_port, &anpr_state.restart, |stream, peer| {
+        let camera = camera_at(&config, peer.ip())?;
+        let app_handle = app_handle.clone();
+        Some(async move { handle_event(&app_handle, &camera, stream).await })
+    }).await
+}
+
+fn camera_at(config: &AppConfig, ip: IpAddr) -> Option<AnprCameraConfig> {
+    config.anpr_cameras.iter()
+        .find(|camera| camera.ip.parse::<IpAddr>().is_ok_and(|camera_ip| camera_ip == ip))
+        .cloned()
+}
+
+pub fn spawn_anpr_listener(app_handle: tauri::AppHandle) {
+    spawn_listener(app_handle, "ANPR", |config| config.anpr_enabled, run_listener);
+}
+
+/// The plate of the truck in the lane. Cameras that can be triggered are asked for a
+/// fresh read, so a plate pushed for the previous truck is not used.
+async fn read_plate(app_handle: &tauri::AppHandle, config: &AppConfig) -> Option<PlateRead> {
+    let anpr_state = app_handle.state::<AnprState>();
+    for camera in config.anpr_cameras.iter().filter(|camera| !camera.trigger_url.is_empty()) {
+        match trigger_read(camera).await {
+            Ok(read) => {
+                anpr_state.record(app_handle, read.clone());
+                return Some(read);
+            }
+            Err(e) => log::warn!("ANPR: {}", e),
+        }
+    }
+    anpr_state.current_read()
+}
+
+/// Compares the plate in the lane with the police number registered to the tag and
+/// alerts the operator with `anpr_plate_mismatch` when they differ.
+pub async fn cross_check(app_handle: &tauri::AppHandle, config: &AppConfig, registered: Option<&str>) -> PlateCheck {
+    if !config.anpr_enabled {
+        return PlateCheck { outcome: PlateCheckOutcome::Disabled, read: None, registered: registered.map(str::to_string) };
+    }
+    let read = read_plate(app_handle, config).await;
+    let normalized = registered.and_then(normalize_plate);
+    let outcome = match (&read, &normalized) {
+        (None, _) => PlateCheckOutcome::NoRead,
+        (Some(_), None) => PlateCheckOutcome::Unregistered,
+        (Some(read), Some(registered)) if read.plate == *registered => PlateCheckOutcome::Match,
+        (Some(_), Some(_)) => PlateCheckOutcome::Mismatch,
+    };
+    let check = PlateCheck {
+        outcome,
+        read,
+        registered: normalized.or_else(|| registered.map(str::to_string)),
+    };
+    if outcome == PlateCheckOutcome::Mismatch {
+        log::warn!(
+            "ANPR: Plate {} does not match the tag's police number {}",
+            check.read.as_ref().map_or("", |r| r.plate.as_str()),
+            check.registered.as_deref().unwrap_or_default(),
+        );
+        if let Err(e) = app_handle.emit("anpr_plate_mismatch", &check) {
+            log::error!("Failed to emit anpr_plate_mismatch event: {}", e);
+        }
+    }
+    check
+}
+
+/// Reads the plate of the truck in the lane and checks it against `registered_plate`.
+#[tauri::command]
+pub async fn check_truck_plate_command(
+    app_handle: tauri::AppHandle,
+    config_state: State<'_, AppConfigState>,
+    registered_plate: String,
+) -> Result<PlateCheck, String> {
+    let config = config_state.0.lock().map_err(|_| "Failed to acquire config lock")?.clone();
+    let registered = Some(registered_plate.trim()).filter(|plate| !plate.is_empty());
+    Ok(cross_check(&app_handle, &config, registered).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spacing_dashes_and_case_normalize_alike() {
+        assert_eq!(normalize_plate("B1234XYZ").as_deref(), Some("B 1234 XYZ"));
+        assert_eq!(normalize_plate("b-1234-xyz").as_deref(), Some("B 1234 XYZ"));
+        assert_eq!(normalize_plate(" B 1234 XYZ ").as_deref(), Some("B 1234 XYZ"));
+    }
+
+    #[test]
+    fn suffix_and_two_letter_region_are_optional_parts() {
+        assert_eq!(normalize_plate("B 1").as_deref(), Some("B 1"));
+        assert_eq!(normalize_plate("DK 123 AB").as_deref(), Some("DK 123 AB"));
+    }
+
+    #[test]
+    fn malformed_plates_are_rejected() {
+        assert_eq!(normalize_plate("B 0123"), None);
+        assert_eq!(normalize_plate("1234 XYZ"), None);
+        assert_eq!(normalize_plate("ABC 1234"), None);
+        assert_eq!(normalize_plate("B 12345"), None);
+        assert_eq!(normalize_plate("B 1234 WXYZ"), None);
+        assert_eq!(normalize_plate("B 12 X3"), None);
+        assert_eq!(normalize_plate(""), None);
+    }
+
+    #[test]
+    fn a_cleared_plate_is_not_reused_for_the_next_truck() {
+        let state = AnprState::default();
+        let read = PlateRead { plate: "B 1234 XYZ".into(), raw: "B1234XYZ".into(), camera_id: "lane-1".into(), confidence: Some(90), timestamp: String::new() };
+        *state.latest.lock().unwrap() = Some((read, Instant::now()));
+        assert_eq!(state.current_plate().as_deref(), Some("B 1234 XYZ"));
+        state.clear();
+        assert_eq!(state.current_plate(), None);
+    }
+}
@@ -0,0 +1,381 @@
+// src-tauri/src/audit_handler.rs
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::State;
+
+use crate::auth_handler::{self, AuthState, Role};
+use crate::config_handler::AppConfigState;
+
+const AUDIT_LOG_FILE_NAME: &str = "audit_log.jsonl";
+const AUDIT_HEAD_FILE_NAME: &str = "audit_log.head";
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditKind {
+    PortalActuation,
+    Payment,
+    ManualOverride,
+    ConfigChange,
+    Settlement,
+    BlacklistChange,
+    DeviceCommand,
+}
+
+/// One line of the audit log. `hash` covers every other field, including
+/// `prev_hash`, so editing, removing or reordering any entry breaks the chain.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AuditEntry {
+    pub seq: u64,
+    pub timestamp: String,
+    pub kind: AuditKind,
+    pub actor: String,
+    pub gate_name: String,
+    pub details: serde_json::Value,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+impl AuditEntry {
+    fn compute_hash(&self) -> String {
+        let kind = serde_json::to_string(&self.kind).unwrap_or_default();
+        let details = serde_json::to_string(&self.details).unwrap_or_default();
+        let mut hasher = Sha256::new();
+        for part in [
+            self.seq.to_string().as_str(),
+            self.timestamp.as_str(),
+            kind.as_str(),
+            self.actor.as_str(),
+            self.gate_name.as_str(),
+            details.as_str(),
+            self.prev_hash.as_str(),
+        ] {
+            hasher.update(part.as_bytes());
+            hasher.update([0x1f]); // unit separator keeps field boundaries unambiguous
+        }
+        hex_encode(&hasher.finalize())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct AuditHead {
+    seq: u64,
+    hash: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct AuditIssue {
+    pub line: usize,
+    pub seq: Option<u64>,
+    pub problem: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct AuditVerificationReport {
+    pub valid: bool,
+    pub entries: usize,
+    pub head_seq: u64,
+    pub head_hash: String,
+    pub issues: Vec<AuditIssue>,
+}
+
+#[derive(Serialize)]
+struct AuditExport<'a> {
+    exported_at: String,
+    exported_by: String,
+    gate_name: String,
+    verification: &'a AuditVerificationReport,
+    entries: &'a [AuditEntry],
+}
+
+pub(crate) fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// An entry paired with its 1-based line number in the log file.
+type NumberedEntry = (usize, AuditEntry);
+
+/// Append-only, SHA-256 hash-chained log of gate actions.
+pub struct AuditLog {
+    path: PathBuf,
+    head_path: PathBuf,
+    last_seq: u64,
+    last_hash: String,
+}
+
+pub struct AuditLogState(pub Mutex<AuditLog>);
+
+impl AuditLog {
+    pub fn open(dir: &Path) -> Self {
+        let mut audit_log = AuditLog {
+            path: dir.join(AUDIT_LOG_FILE_NAME),
+            head_path: dir.join(AUDIT_HEAD_FILE_NAME),
+            last_seq: 0,
+            last_hash: GENESIS_HASH.to_string(),
+        };
+        match audit_log.read_entries() {
+            Ok((entries, _)) => {
+                if let Some((_, last)) = entries.last() {
+                    audit_log.last_seq = last.seq;
+                    audit_log.last_hash = last.hash.clone();
+                }
+                let report = audit_log.verify();
+                if !report.valid {
+                    log::error!("AUDIT: Audit log failed verification on startup: {:?}", report.issues);
+                }
+            }
+            Err(e) => log::error!("AUDIT: Failed to read audit log {:?}: {}", audit_log.path, e),
+        }
+        audit_log
+    }
+
+    /// Returns the parsed entries with their line numbers, plus issues for lines
+    /// that could not be parsed.
+    fn read_entries(&self) -> Result<(Vec<NumberedEntry>, Vec<AuditIssue>), String> {
+        if !self.path.exists() {
+            return Ok((Vec::new(), Vec::new()));
+        }
+        let file = File::open(&self.path).map_err(|e| format!("Failed to open audit log: {}", e))?;
+        let mut entries = Vec::new();
+        let mut issues = Vec::new();
+        for (index, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.map_err(|e| format!("Failed to read audit log: {}", e))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str::<AuditEntry>(&line) {
+                Ok(entry) => entries.push((index + 1, entry)),
+                Err(e) => issues.push(AuditIssue { line: index + 1, seq: None, problem: format!("Unparseable entry: {}", e) }),
+            }
+        }
+        Ok((entries, issues))
+    }
+
+    pub fn append(&mut self, kind: AuditKind, actor: &str, gate_name: &str, details: serde_json::Value) -> Result<AuditEntry, String> {
+        let mut entry = AuditEntry {
+            seq: self.last_seq + 1,
+            timestamp: chrono::Local::now().to_rfc3339(),
+            kind,
+            actor: actor.to_string(),
+            gate_name: gate_name.to_string(),
+            details,
+            prev_hash: self.last_hash.clone(),
+            hash: String::new(),
+        };
+        entry.hash = entry.compute_hash();
+
+        let line = serde_json::to_string(&entry).map_err(|e| format!("Failed to serialize audit entry: {}", e))?;
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)
+            .map_err(|e| format!("Failed to open audit log for append: {}", e))?;
+        writeln!(file, "{}", line).map_err(|e| format!("Failed to append audit entry: {}", e))?;
+        file.sync_data().map_err(|e| format!("Failed to flush audit log: {}", e))?;
+
+        self.last_seq = entry.seq;
+        self.last_hash = entry.hash.clone();
+        let head = serde_json::to_string(&AuditHead { seq: entry.seq, hash: entry.hash.clone() })
+            .map_err(|e| format!("Failed to serialize audit head: {}", e))?;
+        // Written aside and renamed over the old head, so a crash never leaves a half-written head.
+        let temp_path = self.head_path.with_extension("head.tmp");
+        let mut temp = File::create(&temp_path).map_err(|e| format!("Failed to write audit head: {}", e))?;
+        temp.write_all(head.as_bytes())
+            .and_then(|()| temp.sync_data())
+            .map_err(|e| format!("Failed to write audit head: {}", e))?;
+        fs::rename(&temp_path, &self.head_path).map_err(|e| format!("Failed to replace audit head: {}", e))?;
+        Ok(entry)
+    }
+
+    /// Walks the whole chain, reporting sequence gaps, broken links, edited
+    /// entries and a log that no longer reaches the recorded head.
+    pub fn verify(&self) -> AuditVerificationReport {
+        let (entries, mut issues) = match self.read_entries() {
+            Ok(result) => result,
+            Err(e) => (Vec::new(), vec![AuditIssue { line: 0, seq: None, problem: e }]),
+        };
+
+        let mut expected_seq = 1;
+        let mut expected_prev = GENESIS_HASH.to_string();
+        for (line, entry) in &entries {
+            let line = *line;
+            if entry.seq != expected_seq {
+                issues.push(AuditIssue { line, seq: Some(entry.seq), problem: format!("Sequence gap: expected {}, found {}", expected_seq, entry.seq) });
+            }
+            if entry.prev_hash != expected_prev {
+                issues.push(AuditIssue { line, seq: Some(entry.seq), problem: "Previous-hash link broken".to_string() });
+            }
+            if entry.compute_hash() != entry.hash {
+                issues.push(AuditIssue { line, seq: Some(entry.seq), problem: "Entry content does not match its hash (edited)".to_string() });
+            }
+            expected_seq = entry.seq + 1;
+            expected_prev = entry.hash.clone();
+        }
+
+        let head_seq = entries.last().map_or(0, |(_, e)| e.seq);
+        if let Ok(content) = fs::read_to_string(&self.head_path) {
+            match serde_json::from_str::<AuditHead>(&content) {
+                Ok(head) if head.seq != head_seq || head.hash != expected_prev => {
+                    issues.push(AuditIssue { line: entries.last().map_or(0, |(line, _)| *line), seq: Some(head.seq), problem: format!("Log ends at entry {} but the recorded head is entry {} (truncated or replaced)", head_seq, head.seq) });
+                }
+                Ok(_) => {}
+                Err(e) => issues.push(AuditIssue { line: 0, seq: None, problem: format!("Unreadable audit head: {}", e) }),
+            }
+        }
+
+        issues.sort_by_key(|i| i.line);
+        AuditVerificationReport {
+            valid: issues.is_empty(),
+            entries: entries.len(),
+            head_seq,
+            head_hash: expected_prev,
+            issues,
+        }
+    }
+}
+
+impl AuditLogState {
+    /// Appends an entry, logging instead of failing: the action being audited has already happened.
+    pub fn record(&self, kind: AuditKind, actor: &str, gate_name: &str, details: serde_json::Value) {
+        let result = self.0.lock()
+            .map_err(|_| "Failed to acquire audit log lock".to_string())
+            .and_then(|mut audit_log| audit_log.append(kind, actor, gate_name, details));
+        if let Err(e) = result {
+            log::error!("AUDIT: Failed to record {:?} by {}: {}", kind, actor, e);
+        }
+    }
+}
+
+#[tauri::command]
+pub fn verify_audit_log_command(
+    audit_state: State<'_, AuditLogState>,
+    auth_state: State<'_, AuthState>,
+    config_state: State<'_, AppConfigState>,
+    session_token: String,
+) -> Result<AuditVerificationReport, String> {
+    auth_handler::require_role(&auth_state, &config_state, &session_token, Role::Supervisor)?;
+    let audit_log = audit_state.0.lock().map_err(|_| "Failed to acquire audit log lock")?;
+    Ok(audit_log.verify())
+}
+
+/// Writes the full log with its verification report as one JSON document for auditors.
+#[tauri::command]
+pub fn export_audit_log_command(
+    audit_state: State<'_, AuditLogState>,
+    auth_state: State<'_, AuthState>,
+    config_state: State<'_, AppConfigState>,
+    session_token: String,
+    destination_path: String,
+) -> Result<AuditVerificationReport, String> {
+    let session = auth_handler::require_role(&auth_state, &config_state, &session_token, Role::Supervisor)?;
+    let gate_name = config_state.0.lock().map_err(|_| "Failed to acquire config lock")?.gate_name.clone();
+    let audit_log = audit_state.0.lock().map_err(|_| "Failed to acquire audit log lock")?;
+    let report = audit_log.verify();
+    let entries: Vec<AuditEntry> = audit_log.read_entries()?.0.into_iter().map(|(_, e)| e).collect();
+    let export = AuditExport {
+        exported_at: chrono::Local::now().to_rfc3339(),
+        exported_by: session.username.clone(),
+        gate_name,
+        verification: &report,
+        entries: &entries,
+    };
+    let content = serde_json::to_string_pretty(&export)
+        .map_err(|e| format!("Failed to serialize audit export: {}", e))?;
+    fs::write(&destination_path, content)
+        .map_err(|e| format!("Failed to write audit export to {}: {}", destination_path, e))?;
+    log::info!("AUDIT: {} entries exported to {} by {} (valid: {})", report.entries, destination_path, session.username, report.valid);
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            let dir = std::env::temp_dir().join(format!("audit-test-{}", uuid::Uuid::new_v4()));
+            fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn log_with_entries(dir: &TempDir, count: usize) -> AuditLog {
+        let mut audit_log = AuditLog::open(&dir.0);
+        for n in 0..count {
+            audit_log.append(AuditKind::PortalActuation, "op", "GATE1", serde_json::json!({ "n": n })).unwrap();
+        }
+        audit_log
+    }
+
+    fn log_lines(dir: &TempDir) -> Vec<String> {
+        fs::read_to_string(dir.0.join(AUDIT_LOG_FILE_NAME)).unwrap().lines().map(str::to_string).collect()
+    }
+
+    fn write_lines(dir: &TempDir, lines: &[String]) {
+        fs::write(dir.0.join(AUDIT_LOG_FILE_NAME), lines.join("\n") + "\n").unwrap();
+    }
+
+    #[test]
+    fn intact_chain_verifies_and_reopens_at_its_head() {
+        let dir = TempDir::new();
+        let report = log_with_entries(&dir, 3).verify();
+        assert!(report.valid, "{:?}", report.issues);
+        assert_eq!((report.entries, report.head_seq), (3, 3));
+        assert!(!dir.0.join("audit_log.head.tmp").exists());
+
+        let mut reopened = AuditLog::open(&dir.0);
+        let entry = reopened.append(AuditKind::Payment, "system", "GATE1", serde_json::json!({})).unwrap();
+        assert_eq!(entry.seq, 4);
+        assert!(reopened.verify().valid);
+    }
+
+    #[test]
+    fn edited_entry_is_detected() {
+        let dir = TempDir::new();
+        let audit_log = log_with_entries(&dir, 3);
+        let mut lines = log_lines(&dir);
+        lines[1] = lines[1].replace(r#""actor":"op""#, r#""actor":"someone else""#);
+        write_lines(&dir, &lines);
+
+        let report = audit_log.verify();
+        assert!(!report.valid);
+        assert!(report.issues.iter().any(|issue| issue.line == 2 && issue.problem.contains("edited")));
+    }
+
+    #[test]
+    fn removed_entry_breaks_the_chain() {
+        let dir = TempDir::new();
+        let audit_log = log_with_entries(&dir, 3);
+        let mut lines = log_lines(&dir);
+        lines.remove(1);
+        write_lines(&dir, &lines);
+
+        let report = audit_log.verify();
+        assert!(!report.valid);
+        assert!(report.issues.iter().any(|issue| issue.problem.contains("Sequence gap")));
+        assert!(report.issues.iter().any(|issue| issue.problem.contains("link broken")));
+    }
+
+    #[test]
+    fn truncated_log_no_longer_reaches_the_head() {
+        let dir = TempDir::new();
+        let audit_log = log_with_entries(&dir, 3);
+        let mut lines = log_lines(&dir);
+        lines.truncate(2);
+        write_lines(&dir, &lines);
+
+        let report = audit_log.verify();
+        assert!(!report.valid);
+        assert_eq!(report.head_seq, 2);
+        assert!(report.issues.iter().any(|issue| issue.problem.contains("truncated")));
+    }
+}
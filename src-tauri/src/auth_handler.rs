@@ -0,0 +1,413 @@
+// src-tauri/src/auth_handler.rs
+use argon2::password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{Emitter, Manager, State};
+
+use crate::config_handler::AppConfigState;
+
+const OPERATORS_FILE_NAME: &str = "operators.json";
+const MIN_PASSWORD_LEN: usize = 6;
+const SESSION_SWEEP_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Operator roles, ordered by privilege: a higher role may do everything a lower one can.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    LaneOperator,
+    Technician,
+    Supervisor,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct OperatorAccount {
+    username: String,
+    password_hash: String,
+    role: Role,
+    active: bool,
+    created_at: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct OperatorSummary {
+    pub username: String,
+    pub role: Role,
+    pub active: bool,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone)]
+struct Session {
+    username: String,
+    role: Role,
+    last_activity: Instant,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct SessionInfo {
+    pub token: String,
+    pub username: String,
+    pub role: Role,
+    pub idle_timeout_secs: u64,
+}
+
+#[derive(Serialize, Clone)]
+struct SessionLockedPayload {
+    username: String,
+    reason: String,
+}
+
+/// Local operator accounts (argon2-hashed, persisted) and in-memory login sessions.
+pub struct AuthManager {
+    dir: PathBuf,
+    operators: HashMap<String, OperatorAccount>,
+    sessions: HashMap<String, Session>,
+}
+
+pub struct AuthState(pub Mutex<AuthManager>);
+
+fn hash_password(password: &str) -> Result<String, String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| format!("Failed to hash password: {}", e))
+}
+
+fn verify_password(password: &str, password_hash: &str) -> bool {
+    match PasswordHash::new(password_hash) {
+        Ok(parsed) => Argon2::default().verify_password(password.as_bytes(), &parsed).is_ok(),
+        Err(e) => {
+            log::error!("AUTH: Stored password hash is malformed: {}", e);
+            false
+        }
+    }
+}
+
+fn validate_new_password(password: &str) -> Result<(), String> {
+    if password.chars().count() < MIN_PASSWORD_LEN {
+        return Err(format!("Password must be at least {} characters", MIN_PASSWORD_LEN));
+    }
+    Ok(())
+}
+
+impl AuthManager {
+    pub fn new(dir: &Path) -> Self {
+        let path = dir.join(OPERATORS_FILE_NAME);
+        let operators = if path.exists() {
+            match fs::read_to_string(&path).map(|c| serde_json::from_str::<Vec<OperatorAccount>>(&c)) {
+                Ok(Ok(list)) => list.into_iter().map(|op| (op.username.to_lowercase(), op)).collect(),
+                Ok(Err(e)) => {
+                    log::error!("AUTH: Failed to parse operator accounts {:?}: {}", path, e);
+                    HashMap::new()
+                }
+                Err(e) => {
+                    log::error!("AUTH: Failed to read operator accounts {:?}: {}", path, e);
+                    HashMap::new()
+                }
+            }
+        } else {
+            HashMap::new()
+        };
+        AuthManager { dir: dir.to_path_buf(), operators, sessions: HashMap::new() }
+    }
+
+    fn persist(&self) -> Result<(), String> {
+        let mut list: Vec<&OperatorAccount> = self.operators.values().collect();
+        list.sort_by(|a, b| a.username.cmp(&b.username));
+        let content = serde_json::to_string_pretty(&list)
+            .map_err(|e| format!("Failed to serialize operator accounts: {}", e))?;
+        fs::write(self.dir.join(OPERATORS_FILE_NAME), content)
+            .map_err(|e| format!("Failed to write operator accounts: {}", e))
+    }
+
+    fn active_supervisors(&self) -> usize {
+        self.operators.values().filter(|op| op.active && op.role == Role::Supervisor).count()
+    }
+
+    /// Drops sessions idle for longer than `idle_timeout` and returns their usernames.
+    pub fn expire_idle_sessions(&mut self, idle_timeout: Duration) -> Vec<String> {
+        let expired: Vec<String> = self.sessions.iter()
+            .filter(|(_, s)| s.last_activity.elapsed() > idle_timeout)
+            .map(|(token, _)| token.clone())
+            .collect();
+        expired.into_iter()
+            .filter_map(|token| self.sessions.remove(&token))
+            .map(|s| s.username)
+            .collect()
+    }
+
+    /// Checks `token` against `required` and refreshes the session's activity timestamp.
+    pub fn authorize(&mut self, token: &str, required: Role, idle_timeout: Duration) -> Result<SessionInfo, String> {
+        let session = self.sessions.get_mut(token)
+            .ok_or_else(|| "Not logged in or session expired. Please log in again.".to_string())?;
+        if session.last_activity.elapsed() > idle_timeout {
+            let username = session.username.clone();
+            self.sessions.remove(token);
+            log::info!("AUTH: Session for {} locked due to inactivity", username);
+            return Err("Session locked due to inactivity. Please log in again.".to_string());
+        }
+        if session.role < required {
+            log::warn!("AUTH: {} ({:?}) denied action requiring {:?}", session.username, session.role, required);
+            return Err(format!("This action requires {:?} rights", required));
+        }
+        session.last_activity = Instant::now();
+        Ok(SessionInfo {
+            token: token.to_string(),
+            username: session.username.clone(),
+            role: session.role,
+            idle_timeout_secs: idle_timeout.as_secs(),
+        })
+    }
+}
+
+fn idle_timeout(config_state: &State<'_, AppConfigState>) -> Result<Duration, String> {
+    let config = config_state.0.lock().map_err(|_| "Failed to acquire config lock")?;
+    Ok(Duration::from_secs(config.operator_idle_timeout_secs))
+}
+
+/// Gatekeeper for sensitive commands: fails unless `session_token` belongs to a live
+/// session with at least the `required` role.
+pub fn require_role(
+    auth_state: &State<'_, AuthState>,
+    config_state: &State<'_, AppConfigState>,
+    session_token: &str,
+    required: Role,
+) -> Result<SessionInfo, String> {
+    let timeout = idle_timeout(config_state)?;
+    auth_state.0.lock()
+        .map_err(|_| "Failed to acquire auth lock")?
+        .authorize(session_token, required, timeout)
+}
+
+/// Periodically locks idle sessions so the UI can return to the login screen
+/// even when nobody touches the lane PC.
+pub fn spawn_session_sweeper(app_handle: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(SESSION_SWEEP_INTERVAL).await;
+            let timeout = match app_handle.state::<AppConfigState>().0.lock() {
+                Ok(config) => Duration::from_secs(config.operator_idle_timeout_secs),
+                Err(_) => continue,
+            };
+            let expired = match app_handle.state::<AuthState>().0.lock() {
+                Ok(mut auth) => auth.expire_idle_sessions(timeout),
+                Err(_) => continue,
+            };
+            for username in expired {
+                log::info!("AUTH: Session for {} locked due to inactivity", username);
+                let payload = SessionLockedPayload { username, reason: "inactivity".to_string() };
+                if let Err(e) = app_handle.emit("operator_session_locked", &payload) {
+                    log::error!("Failed to emit operator_session_locked event: {}", e);
+                }
+            }
+        }
+    });
+}
+
+#[tauri::command]
+pub fn auth_setup_required_command(auth_state: State<'_, AuthState>) -> Result<bool, String> {
+    let auth = auth_state.0.lock().map_err(|_| "Failed to acquire auth lock")?;
+    Ok(auth.operators.is_empty())
+}
+
+#[tauri::command]
+pub async fn operator_login_command(
+    auth_state: State<'_, AuthState>,
+    config_state: State<'_, AppConfigState>,
+    username: String,
+    password: String,
+) -> Result<SessionInfo, String> {
+    let key = username.trim().to_lowercase();
+    let account = {
+        let auth = auth_state.0.lock().map_err(|_| "Failed to acquire auth lock")?;
+        auth.operators.get(&key).cloned()
+    };
+    let Some(account) = account.filter(|a| a.active) else {
+        log::warn!("AUTH: Login failed for unknown or inactive operator '{}'", username.trim());
+        return Err("Invalid username or password".to_string());
+    };
+
+    // Argon2 is deliberately slow; keep it off the async workers.
+    let password_hash = account.password_hash.clone();
+    let valid = tauri::async_runtime::spawn_blocking(move || verify_password(&password, &password_hash))
+        .await
+        .map_err(|e| format!("Password verification task failed: {}", e))?;
+    if !valid {
+        log::warn!("AUTH: Login failed for operator '{}'", account.username);
+        return Err("Invalid username or password".to_string());
+    }
+
+    let timeout = idle_timeout(&config_state)?;
+    let token = uuid::Uuid::new_v4().to_string();
+    let mut auth = auth_state.0.lock().map_err(|_| "Failed to acquire auth lock")?;
+    auth.sessions.insert(token.clone(), Session {
+        username: account.username.clone(),
+        role: account.role,
+        last_activity: Instant::now(),
+    });
+    log::info!("AUTH: Operator {} logged in as {:?}", account.username, account.role);
+    Ok(SessionInfo {
+        token,
+        username: account.username,
+        role: account.role,
+        idle_timeout_secs: timeout.as_secs(),
+    })
+}
+
+#[tauri::command]
+pub fn operator_logout_command(auth_state: State<'_, AuthState>, session_token: String) -> Result<(), String> {
+    let mut auth = auth_state.0.lock().map_err(|_| "Failed to acquire auth lock")?;
+    if let Some(session) = auth.sessions.remove(&session_token) {
+        log::info!("AUTH: Operator {} logged out", session.username);
+    }
+    Ok(())
+}
+
+/// Validates the session and counts as activity, so the UI can call it on user input.
+#[tauri::command]
+pub fn operator_session_status_command(
+    auth_state: State<'_, AuthState>,
+    config_state: State<'_, AppConfigState>,
+    session_token: String,
+) -> Result<SessionInfo, String> {
+    require_role(&auth_state, &config_state, &session_token, Role::LaneOperator)
+}
+
+/// Creates an operator account. Requires supervisor rights, except for the very first
+/// account on a fresh install, which must be a supervisor.
+#[tauri::command]
+pub async fn create_operator_command(
+    auth_state: State<'_, AuthState>,
+    config_state: State<'_, AppConfigState>,
+    session_token: Option<String>,
+    username: String,
+    password: String,
+    role: Role,
+) -> Result<OperatorSummary, String> {
+    let username = username.trim().to_string();
+    if username.is_empty() {
+        return Err("Username must not be empty".to_string());
+    }
+    validate_new_password(&password)?;
+
+    let bootstrap = auth_state.0.lock().map_err(|_| "Failed to acquire auth lock")?.operators.is_empty();
+    if bootstrap {
+        if role != Role::Supervisor {
+            return Err("The first operator account must be a supervisor".to_string());
+        }
+    } else {
+        require_role(&auth_state, &config_state, session_token.as_deref().unwrap_or_default(), Role::Supervisor)?;
+    }
+
+    let password_hash = tauri::async_runtime::spawn_blocking(move || hash_password(&password))
+        .await
+        .map_err(|e| format!("Password hashing task failed: {}", e))??;
+
+    let mut auth = auth_state.0.lock().map_err(|_| "Failed to acquire auth lock")?;
+    // Another first-run request may have created the bootstrap supervisor while this one hashed.
+    if bootstrap && !auth.operators.is_empty() {
+        return Err("An operator account already exists; log in as a supervisor to add more".to_string());
+    }
+    let key = username.to_lowercase();
+    if auth.operators.contains_key(&key) {
+        return Err(format!("Operator '{}' already exists", username));
+    }
+    let account = OperatorAccount {
+        username: username.clone(),
+        password_hash,
+        role,
+        active: true,
+        created_at: chrono::Local::now().to_rfc3339(),
+    };
+    let summary = OperatorSummary {
+        username: account.username.clone(),
+        role: account.role,
+        active: account.active,
+        created_at: account.created_at.clone(),
+    };
+    auth.operators.insert(key, account);
+    auth.persist()?;
+    log::info!("AUTH: Operator {} created with role {:?}", username, role);
+    Ok(summary)
+}
+
+/// Changes a password. Operators may change their own; supervisors may change anyone's.
+#[tauri::command]
+pub async fn set_operator_password_command(
+    auth_state: State<'_, AuthState>,
+    config_state: State<'_, AppConfigState>,
+    session_token: String,
+    username: String,
+    new_password: String,
+) -> Result<(), String> {
+    let session = require_role(&auth_state, &config_state, &session_token, Role::LaneOperator)?;
+    if !session.username.eq_ignore_ascii_case(username.trim()) && session.role < Role::Supervisor {
+        return Err("Only a supervisor can change another operator's password".to_string());
+    }
+    validate_new_password(&new_password)?;
+
+    let password_hash = tauri::async_runtime::spawn_blocking(move || hash_password(&new_password))
+        .await
+        .map_err(|e| format!("Password hashing task failed: {}", e))??;
+
+    let mut auth = auth_state.0.lock().map_err(|_| "Failed to acquire auth lock")?;
+    let account = auth.operators.get_mut(&username.trim().to_lowercase())
+        .ok_or_else(|| format!("Operator '{}' not found", username.trim()))?;
+    account.password_hash = password_hash;
+    auth.persist()?;
+    log::info!("AUTH: Password for {} changed by {}", username.trim(), session.username);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn set_operator_active_command(
+    auth_state: State<'_, AuthState>,
+    config_state: State<'_, AppConfigState>,
+    session_token: String,
+    username: String,
+    active: bool,
+) -> Result<(), String> {
+    let session = require_role(&auth_state, &config_state, &session_token, Role::Supervisor)?;
+    let mut auth = auth_state.0.lock().map_err(|_| "Failed to acquire auth lock")?;
+    let key = username.trim().to_lowercase();
+    let account = auth.operators.get(&key)
+        .ok_or_else(|| format!("Operator '{}' not found", username.trim()))?;
+    if !active && account.active && account.role == Role::Supervisor && auth.active_supervisors() <= 1 {
+        return Err("Cannot deactivate the last active supervisor".to_string());
+    }
+    if let Some(account) = auth.operators.get_mut(&key) {
+        account.active = active;
+    }
+    if !active {
+        auth.sessions.retain(|_, s| s.username.to_lowercase() != key);
+    }
+    auth.persist()?;
+    log::info!("AUTH: Operator {} set active={} by {}", username.trim(), active, session.username);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn list_operators_command(
+    auth_state: State<'_, AuthState>,
+    config_state: State<'_, AppConfigState>,
+    session_token: String,
+) -> Result<Vec<OperatorSummary>, String> {
+    require_role(&auth_state, &config_state, &session_token, Role::Supervisor)?;
+    let auth = auth_state.0.lock().map_err(|_| "Failed to acquire auth lock")?;
+    let mut list: Vec<OperatorSummary> = auth.operators.values()
+        .map(|op| OperatorSummary {
+            username: op.username.clone(),
+            role: op.role,
+            active: op.active,
+            created_at: op.created_at.clone(),
+        })
+        .collect();
+    list.sort_by(|a, b| a.username.cmp(&b.username));
+    Ok(list)
+}
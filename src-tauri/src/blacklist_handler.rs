@@ -0,0 +1,503 @@
+// src-tauri/src/blacklist_handler.rs
+// Banned truck tags and stolen e-money cards. The list is kept in SQLite, synced
+// from CaCMTool as signed deltas since the last version seen, and can be amended by a
+// supervisor. It is checked before a tag is validated and before a card is charged.
+use reqwest::Client as ReqwestClient;
+use rusqlite::{params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tauri::{Manager, State};
+
+use crate::audit_handler::{AuditKind, AuditLogState};
+use crate::auth_handler::{self, AuthState, Role};
+use crate::config_handler::AppConfigState;
+use crate::db_handler::DatabaseState;
+use crate::remote_config_handler;
+
+const BLACKLIST_DELTA_ENDPOINT: &str = "/api/Blacklist/Delta";
+/// HMAC-SHA256 of the body under the gate's remote config secret, as for lane configs.
+const SIGNATURE_HEADER: &str = "X-Blacklist-Signature";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+const DISABLED_RECHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BlacklistKind {
+    /// Truck RFID tag, as sent to CGS.
+    Tag,
+    /// E-money card number.
+    Card,
+}
+
+impl BlacklistKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            BlacklistKind::Tag => "tag",
+            BlacklistKind::Card => "card",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            BlacklistKind::Tag => "Tag",
+            BlacklistKind::Card => "Card",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "tag" => Some(BlacklistKind::Tag),
+            "card" => Some(BlacklistKind::Card),
+            _ => None,
+        }
+    }
+}
+
+/// Where an entry came from. Syncing never touches manual entries.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BlacklistSource {
+    Sync,
+    Manual,
+}
+
+impl BlacklistSource {
+    fn as_str(self) -> &'static str {
+        match self {
+            BlacklistSource::Sync => "sync",
+            BlacklistSource::Manual => "manual",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct BlacklistEntry {
+    pub kind: BlacklistKind,
+    pub value: String,
+    pub reason: String,
+    pub source: BlacklistSource,
+    pub added_by: Option<String>,
+    pub added_at: String,
+}
+
+/// Tags and card numbers are compared without surrounding spaces and case.
+fn normalize(value: &str) -> String {
+    value.trim().to_ascii_uppercase()
+}
+
+fn row_to_entry(row: &rusqlite::Row<'_>) -> rusqlite::Result<BlacklistEntry> {
+    let kind: String = row.get(0)?;
+    let source: String = row.get(3)?;
+    Ok(BlacklistEntry {
+        kind: BlacklistKind::parse(&kind).unwrap_or(BlacklistKind::Tag),
+        value: row.get(1)?,
+        reason: row.get(2)?,
+        source: if source == "manual" { BlacklistSource::Manual } else { BlacklistSource::Sync },
+        added_by: row.get(4)?,
+        added_at: row.get(5)?,
+    })
+}
+
+pub fn find_entry(db_state: &DatabaseState, kind: BlacklistKind, value: &str) -> Result<Option<BlacklistEntry>, String> {
+    let conn = db_state.0.lock().map_err(|_| "Failed to acquire database lock")?;
+    conn.query_row(
+        "SELECT kind, value, reason, source, added_by, added_at FROM blacklist WHERE kind = ?1 AND value = ?2",
+        params![kind.as_str(), normalize(value)],
+        row_to_entry,
+    ).optional().map_err(|e| e.to_string())
+}
+
+/// Refuses a blacklisted tag or card with the reason it was listed. A failed lookup
+/// is logged and lets the value through; the online checks still apply.
+pub fn ensure_not_blacklisted(db_state: &DatabaseState, kind: BlacklistKind, value: &str) -> Result<(), String> {
+    if value.trim().is_empty() {
+        return Ok(());
+    }
+    match find_entry(db_state, kind, value) {
+        Ok(None) => Ok(()),
+        Ok(Some(entry)) => {
+            log::warn!("BLACKLIST: {} {} refused ({}): {}", kind.label(), entry.value, entry.source.as_str(), entry.reason);
+            Err(format!("{} {} is blacklisted: {}", kind.label(), entry.value, entry.reason))
+        }
+        Err(e) => {
+            log::error!("BLACKLIST: Failed to check {} {}: {}", kind.as_str(), value, e);
+            Ok(())
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct DeltaEntry {
+    kind: BlacklistKind,
+    value: String,
+    #[serde(default)]
+    reason: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct DeltaKey {
+    kind: BlacklistKind,
+    value: String,
+}
+
+/// CaCMTool's answer to `?since=<version>`: what changed after that version, or
+/// the whole list when `full` is set (first sync, or history no longer kept).
+#[derive(Debug, Deserialize)]
+pub struct BlacklistDelta {
+    version: i64,
+    #[serde(default)]
+    full: bool,
+    #[serde(default)]
+    added: Vec<DeltaEntry>,
+    #[serde(default)]
+    removed: Vec<DeltaKey>,
+}
+
+fn synced_version(db_state: &DatabaseState) -> Result<Option<i64>, String> {
+    let conn = db_state.0.lock().map_err(|_| "Failed to acquire database lock")?;
+    conn.query_row("SELECT version FROM blacklist_sync WHERE id = 1", [], |row| row.get(0))
+        .optional()
+        .map(Option::flatten)
+        .map_err(|e| e.to_string())
+}
+
+/// Applies a delta in one transaction and moves the sync cursor to its version.
+/// Returns the number of entries added or removed.
+pub fn apply_delta(db_state: &DatabaseState, delta: &BlacklistDelta) -> Result<usize, String> {
+    let mut conn = db_state.0.lock().map_err(|_| "Failed to acquire database lock")?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let now = chrono::Local::now().to_rfc3339();
+    let mut changed = 0;
+    if delta.full {
+        changed += tx.execute("DELETE FROM blacklist WHERE source = 'sync'", []).map_err(|e| e.to_string())?;
+    }
+    for key in &delta.removed {
+        changed += tx.execute(
+            "DELETE FROM blacklist WHERE kind = ?1 AND value = ?2 AND source = 'sync'",
+            params![key.kind.as_str(), normalize(&key.value)],
+        ).map_err(|e| e.to_string())?;
+    }
+    for entry in &delta.added {
+        changed += tx.execute(
+            "INSERT INTO blacklist (kind, value, reason, source, added_by, added_at) VALUES (?1, ?2, ?3, 'sync', NULL, ?4)
+             ON CONFLICT (kind, value) DO UPDATE SET reason = excluded.reason, added_at = excluded.added_at
+             WHERE blacklist.source = 'sync'",
+            params![entry.kind.as_str(), normalize(&entry.value), entry.reason, now],
+        ).map_err(|e| e.to_string())?;
+    }
+    tx.execute(
+        "INSERT INTO blacklist_sync (id, version, synced_at, last_error) VALUES (1, ?1, ?2, NULL)
+         ON CONFLICT (id) DO UPDATE SET version = excluded.version, synced_at = excluded.synced_at, last_error = NULL",
+        params![delta.version, now],
+    ).map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(changed)
+}
+
+/// Whether a delta moves the list forward from `since`. An older version is refused
+/// outright, so a replayed signed delta cannot bring back a list CaCMTool has moved on
+/// from; the same version again is only applied when it is the full list.
+fn is_newer_delta(since: Option<i64>, delta: &BlacklistDelta) -> Result<bool, String> {
+    match since {
+        Some(version) if delta.version < version => Err(format!(
+            "Blacklist delta version {} is older than the synced version {}",
+            delta.version, version
+        )),
+        Some(version) if delta.version == version => Ok(delta.full),
+        _ => Ok(true),
+    }
+}
+
+fn record_sync_error(db_state: &DatabaseState, error: &str) -> Result<(), String> {
+    let conn = db_state.0.lock().map_err(|_| "Failed to acquire database lock")?;
+    conn.execute(
+        "INSERT INTO blacklist_sync (id, version, synced_at, last_error) VALUES (1, NULL, NULL, ?1)
+         ON CONFLICT (id) DO UPDATE SET last_error = excluded.last_error",
+        params![error],
+    ).map(|_| ()).map_err(|e| e.to_string())
+}
+
+/// Fetches and applies the changes since the last synced version. Returns `None`
+/// when syncing is turned off for this lane.
+pub async fn sync_blacklist(app_handle: &tauri::AppHandle) -> Result<Option<usize>, String> {
+    let config = app_handle.state::<AppConfigState>().0.lock()
+        .map_err(|_| "Failed to acquire config lock")?
+        .clone();
+    if !config.blacklist_sync_enabled {
+        return Ok(None);
+    }
+    let db_state = app_handle.state::<DatabaseState>();
+    let since = synced_version(&db_state)?;
+
+    let url = format!("{}{}", config.cacm_tool_url.trim_end_matches('/'), BLACKLIST_DELTA_ENDPOINT);
+    let client = ReqwestClient::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .map_err(|e| format!("Failed to build HTTP client: {}", e))?;
+    let mut query = vec![("gateId", config.gate_name.clone())];
+    if let Some(version) = since {
+        query.push(("since", version.to_string()));
+    }
+    let result = async {
+        let secret = remote_config_handler::signing_secret(app_handle, &config.gate_name)?;
+        let response = client.get(&url).query(&query).send().await
+            .map_err(|e| format!("Blacklist sync request failed: {}", e))?;
+        if !response.status().is_success() {
+            return Err(format!("Blacklist sync request returned {}", response.status()));
+        }
+        let signature = response.headers().get(SIGNATURE_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string)
+            .ok_or_else(|| format!("Blacklist delta has no {} header", SIGNATURE_HEADER))?;
+        let body = response.bytes().await
+            .map_err(|e| format!("Failed to read blacklist delta: {}", e))?;
+        remote_config_handler::verify_signature(&secret, &body, &signature)
+            .map_err(|e| format!("Blacklist delta refused: {}", e))?;
+        let delta: BlacklistDelta = serde_json::from_slice(&body)
+            .map_err(|e| format!("Failed to parse blacklist delta: {}", e))?;
+        if !is_newer_delta(since, &delta)? {
+            return Ok(None);
+        }
+        Ok(Some(delta))
+    }.await;
+    let delta = match result {
+        Ok(Some(delta)) => delta,
+        Ok(None) => return Ok(Some(0)),
+        Err(e) => {
+            record_sync_error(&db_state, &e)?;
+            return Err(e);
+        }
+    };
+
+    let changed = apply_delta(&db_state, &delta)?;
+    if changed > 0 {
+        log::info!("BLACKLIST: Synced to version {} ({} change(s))", delta.version, changed);
+    }
+    Ok(Some(changed))
+}
+
+pub fn spawn_blacklist_sync(app_handle: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let outcome = sync_blacklist(&app_handle).await;
+            if let Err(e) = &outcome {
+                log::error!("BLACKLIST: {}", e);
+            }
+            let interval = match outcome {
+                Ok(None) => DISABLED_RECHECK_INTERVAL,
+                _ => app_handle.state::<AppConfigState>().0.lock()
+                    .map(|c| Duration::from_secs(c.blacklist_sync_interval_secs))
+                    .unwrap_or(DISABLED_RECHECK_INTERVAL),
+            };
+            tokio::time::sleep(interval).await;
+        }
+    });
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BlacklistEntryRequest {
+    pub kind: BlacklistKind,
+    pub value: String,
+    pub reason: String,
+}
+
+#[tauri::command]
+pub fn add_blacklist_entry_command(
+    config_state: State<'_, AppConfigState>,
+    auth_state: State<'_, AuthState>,
+    db_state: State<'_, DatabaseState>,
+    audit_state: State<'_, AuditLogState>,
+    session_token: String,
+    entry: BlacklistEntryRequest,
+) -> Result<BlacklistEntry, String> {
+    let session = auth_handler::require_role(&auth_state, &config_state, &session_token, Role::Supervisor)?;
+    let value = normalize(&entry.value);
+    let reason = entry.reason.trim().to_string();
+    if value.is_empty() {
+        return Err(format!("{} must not be empty", entry.kind.label()));
+    }
+    if reason.is_empty() {
+        return Err("A reason is required; it is shown to the operator on a match".to_string());
+    }
+    let gate_name = config_state.0.lock().map_err(|_| "Failed to acquire config lock")?.gate_name.clone();
+    {
+        let conn = db_state.0.lock().map_err(|_| "Failed to acquire database lock")?;
+        conn.execute(
+            "INSERT INTO blacklist (kind, value, reason, source, added_by, added_at) VALUES (?1, ?2, ?3, 'manual', ?4, ?5)
+             ON CONFLICT (kind, value) DO UPDATE SET reason = excluded.reason, source = 'manual',
+                 added_by = excluded.added_by, added_at = excluded.added_at",
+            params![entry.kind.as_str(), value, reason, session.username, chrono::Local::now().to_rfc3339()],
+        ).map_err(|e| format!("Failed to blacklist {} {}: {}", entry.kind.as_str(), value, e))?;
+    }
+    log::warn!("BLACKLIST: {} {} added by supervisor {}: {}", entry.kind.label(), value, session.username, reason);
+    audit_state.record(AuditKind::BlacklistChange, &session.username, &gate_name, serde_json::json!({
+        "action": "add",
+        "kind": entry.kind,
+        "value": value,
+        "reason": reason,
+    }));
+    find_entry(&db_state, entry.kind, &value)?
+        .ok_or_else(|| format!("{} {} was not stored", entry.kind.label(), value))
+}
+
+/// Removes an entry. A synced entry comes back with the next full sync unless
+/// CaCMTool drops it too.
+#[tauri::command]
+pub fn remove_blacklist_entry_command(
+    config_state: State<'_, AppConfigState>,
+    auth_state: State<'_, AuthState>,
+    db_state: State<'_, DatabaseState>,
+    audit_state: State<'_, AuditLogState>,
+    session_token: String,
+    kind: BlacklistKind,
+    value: String,
+) -> Result<bool, String> {
+    let session = auth_handler::require_role(&auth_state, &config_state, &session_token, Role::Supervisor)?;
+    let gate_name = config_state.0.lock().map_err(|_| "Failed to acquire config lock")?.gate_name.clone();
+    let previous = find_entry(&db_state, kind, &value)?;
+    let Some(previous) = previous else {
+        return Ok(false);
+    };
+    {
+        let conn = db_state.0.lock().map_err(|_| "Failed to acquire database lock")?;
+        conn.execute("DELETE FROM blacklist WHERE kind = ?1 AND value = ?2", params![kind.as_str(), previous.value])
+            .map_err(|e| e.to_string())?;
+    }
+    log::warn!("BLACKLIST: {} {} removed by supervisor {}", kind.label(), previous.value, session.username);
+    audit_state.record(AuditKind::BlacklistChange, &session.username, &gate_name, serde_json::json!({
+        "action": "remove",
+        "kind": kind,
+        "value": previous.value,
+        "previous_reason": previous.reason,
+        "previous_source": previous.source,
+    }));
+    Ok(true)
+}
+
+#[tauri::command]
+pub fn list_blacklist_command(
+    config_state: State<'_, AppConfigState>,
+    auth_state: State<'_, AuthState>,
+    db_state: State<'_, DatabaseState>,
+    session_token: String,
+) -> Result<Vec<BlacklistEntry>, String> {
+    auth_handler::require_role(&auth_state, &config_state, &session_token, Role::Supervisor)?;
+    let conn = db_state.0.lock().map_err(|_| "Failed to acquire database lock")?;
+    let mut stmt = conn.prepare(
+        "SELECT kind, value, reason, source, added_by, added_at FROM blacklist ORDER BY kind, value",
+    ).map_err(|e| e.to_string())?;
+    let rows = stmt.query_map([], row_to_entry).map_err(|e| e.to_string())?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+}
+
+#[derive(Debug, Serialize)]
+pub struct BlacklistStatus {
+    pub sync_enabled: bool,
+    pub version: Option<i64>,
+    pub synced_at: Option<String>,
+    pub last_error: Option<String>,
+    pub tags: i64,
+    pub cards: i64,
+}
+
+#[tauri::command]
+pub fn get_blacklist_status_command(
+    config_state: State<'_, AppConfigState>,
+    db_state: State<'_, DatabaseState>,
+) -> Result<BlacklistStatus, String> {
+    let sync_enabled = config_state.0.lock().map_err(|_| "Failed to acquire config lock")?.blacklist_sync_enabled;
+    let conn = db_state.0.lock().map_err(|_| "Failed to acquire database lock")?;
+    let (version, synced_at, last_error) = conn.query_row(
+        "SELECT version, synced_at, last_error FROM blacklist_sync WHERE id = 1",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    ).optional().map_err(|e| e.to_string())?.unwrap_or((None, None, None));
+    let (tags, cards) = conn.query_row(
+        "SELECT COALESCE(SUM(kind = 'tag'), 0), COALESCE(SUM(kind = 'card'), 0) FROM blacklist",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    ).map_err(|e| e.to_string())?;
+    Ok(BlacklistStatus { sync_enabled, version, synced_at, last_error, tags, cards })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db_handler;
+
+    fn delta(value: serde_json::Value) -> BlacklistDelta {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn listed(db_state: &DatabaseState) -> Vec<(String, String)> {
+        let conn = db_state.0.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT value, source FROM blacklist ORDER BY value").unwrap();
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?))).unwrap();
+        rows.collect::<Result<Vec<_>, _>>().unwrap()
+    }
+
+    fn add_manual(db_state: &DatabaseState, value: &str) {
+        db_state.0.lock().unwrap().execute(
+            "INSERT INTO blacklist (kind, value, reason, source, added_by, added_at) VALUES ('tag', ?1, 'held', 'manual', 'sup', '')",
+            params![value],
+        ).unwrap();
+    }
+
+    fn pair(value: &str, source: &str) -> (String, String) {
+        (value.to_string(), source.to_string())
+    }
+
+    #[test]
+    fn delta_adds_and_removes_synced_entries() {
+        let db = db_handler::open_in_memory();
+        let first = delta(serde_json::json!({
+            "version": 1,
+            "added": [{ "kind": "tag", "value": " t1 ", "reason": "banned" }, { "kind": "card", "value": "6032", "reason": "stolen" }],
+        }));
+        assert_eq!(apply_delta(&db, &first).unwrap(), 2);
+        assert!(find_entry(&db, BlacklistKind::Tag, "T1").unwrap().is_some());
+        assert!(find_entry(&db, BlacklistKind::Card, "6032").unwrap().is_some());
+
+        let second = delta(serde_json::json!({ "version": 2, "removed": [{ "kind": "tag", "value": "T1" }] }));
+        assert_eq!(apply_delta(&db, &second).unwrap(), 1);
+        assert_eq!(listed(&db), vec![pair("6032", "sync")]);
+        assert_eq!(synced_version(&db).unwrap(), Some(2));
+    }
+
+    #[test]
+    fn full_list_replaces_synced_entries_and_keeps_manual_ones() {
+        let db = db_handler::open_in_memory();
+        apply_delta(&db, &delta(serde_json::json!({
+            "version": 1,
+            "added": [{ "kind": "tag", "value": "T1" }, { "kind": "tag", "value": "T2" }],
+        }))).unwrap();
+        add_manual(&db, "M1");
+
+        apply_delta(&db, &delta(serde_json::json!({ "version": 5, "full": true, "added": [{ "kind": "tag", "value": "T3" }] }))).unwrap();
+        assert_eq!(listed(&db), vec![pair("M1", "manual"), pair("T3", "sync")]);
+    }
+
+    #[test]
+    fn sync_never_overwrites_or_removes_manual_entries() {
+        let db = db_handler::open_in_memory();
+        add_manual(&db, "M1");
+        apply_delta(&db, &delta(serde_json::json!({
+            "version": 1,
+            "added": [{ "kind": "tag", "value": "M1", "reason": "from CaCMTool" }],
+        }))).unwrap();
+        apply_delta(&db, &delta(serde_json::json!({ "version": 2, "removed": [{ "kind": "tag", "value": "M1" }] }))).unwrap();
+        let entry = find_entry(&db, BlacklistKind::Tag, "M1").unwrap().unwrap();
+        assert_eq!((entry.source, entry.reason.as_str()), (BlacklistSource::Manual, "held"));
+    }
+
+    #[test]
+    fn older_deltas_are_refused() {
+        let at = |version: i64, full: bool| delta(serde_json::json!({ "version": version, "full": full }));
+        assert!(is_newer_delta(None, &at(1, false)).unwrap());
+        assert!(is_newer_delta(Some(4), &at(5, false)).unwrap());
+        assert!(!is_newer_delta(Some(5), &at(5, false)).unwrap());
+        assert!(is_newer_delta(Some(5), &at(5, true)).unwrap());
+        assert!(is_newer_delta(Some(5), &at(4, false)).is_err());
+        assert!(is_newer_delta(Some(5), &at(4, true)).is_err(), "a replayed full list is a downgrade");
+    }
+}
@@ -0,0 +1,366 @@
+// src-tauri/src/bundle_handler.rs
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use argon2::Argon2;
+use base64::{engine::general_purpose, Engine as _};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::fs;
+use tauri::State;
+
+use crate::audit_handler::{self, AuditKind, AuditLogState};
+use crate::auth_handler::{self, AuthState, Role};
+use crate::config_handler::{self, AppConfig, AppConfigState, ConfigFieldChange, FieldError, SECRET_CONFIG_FIELDS};
+use crate::config_reload_handler;
+use crate::credential_handler::{CredentialStoreState, GateCredential};
+use crate::remote_config_handler;
+
+const BUNDLE_FORMAT: &str = "cacm-lane-bundle";
+const BUNDLE_VERSION: u32 = 1;
+const MIN_PASSPHRASE_LEN: usize = 12;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// How secrets travel in an exported bundle. They are never written in clear text.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BundleSecretsMode {
+    /// Left out; the importing lane keeps its own.
+    Exclude,
+    /// Encrypted with a key derived from the bundle passphrase.
+    Encrypt,
+}
+
+/// The file on disk. `content` is kept as the exact JSON text that was signed so
+/// verification never depends on re-serializing it.
+#[derive(Debug, Serialize, Deserialize)]
+struct BundleFile {
+    format: String,
+    version: u32,
+    salt: String,
+    content: String,
+    signature: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BundleContent {
+    created_at: String,
+    created_by: String,
+    source_gate: String,
+    /// `AppConfig` without the fields listed in `SECRET_CONFIG_FIELDS`.
+    config: serde_json::Map<String, serde_json::Value>,
+    secrets_mode: BundleSecretsMode,
+    encrypted_secrets: Option<EncryptedSecrets>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct EncryptedSecrets {
+    nonce: String,
+    ciphertext: String,
+}
+
+/// Everything secret that belongs to a lane, keyed by role rather than gate name
+/// so it can be stored under the importing lane's gate.
+#[derive(Serialize, Deserialize, Default)]
+struct BundleSecrets {
+    config_fields: serde_json::Map<String, serde_json::Value>,
+    soap_credential: Option<GateCredential>,
+    remote_config_secret: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct BundlePreview {
+    pub source_gate: String,
+    pub created_at: String,
+    pub created_by: String,
+    pub secrets_included: bool,
+    pub changes: Vec<ConfigFieldChange>,
+    pub validation: Vec<FieldError>,
+    /// Secrets that would be stored for the target gate, by name only.
+    pub secrets: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BundleExportRequest {
+    pub destination_path: String,
+    pub passphrase: String,
+    pub secrets_mode: BundleSecretsMode,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BundleImportRequest {
+    pub bundle_path: String,
+    pub passphrase: String,
+    /// Stores the settings and secrets under this gate instead of the bundle's.
+    pub gate_name: Option<String>,
+}
+
+struct BundleKeys {
+    mac: [u8; 32],
+    cipher: [u8; 32],
+}
+
+/// Derives the signing and encryption keys from the passphrase. Argon2 is
+/// deliberately slow, so this runs on the blocking pool.
+async fn derive_keys(passphrase: String, salt: Vec<u8>) -> Result<BundleKeys, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut okm = [0u8; 64];
+        Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), &salt, &mut okm)
+            .map_err(|e| format!("Failed to derive bundle keys: {}", e))?;
+        let mut keys = BundleKeys { mac: [0u8; 32], cipher: [0u8; 32] };
+        keys.mac.copy_from_slice(&okm[..32]);
+        keys.cipher.copy_from_slice(&okm[32..]);
+        Ok(keys)
+    })
+    .await
+    .map_err(|e| format!("Key derivation task failed: {}", e))?
+}
+
+fn sign(keys: &BundleKeys, content: &str) -> Result<HmacSha256, String> {
+    let mut mac = <HmacSha256 as Mac>::new_from_slice(&keys.mac)
+        .map_err(|e| format!("Invalid bundle signing key: {}", e))?;
+    mac.update(content.as_bytes());
+    Ok(mac)
+}
+
+fn check_passphrase(passphrase: &str) -> Result<(), String> {
+    if passphrase.chars().count() < MIN_PASSPHRASE_LEN {
+        return Err(format!("The bundle passphrase must be at least {} characters", MIN_PASSPHRASE_LEN));
+    }
+    Ok(())
+}
+
+/// Reads a bundle and checks its signature. A wrong passphrase and a modified
+/// file are indistinguishable here, and both are rejected.
+async fn open_bundle(path: &str, passphrase: String) -> Result<(BundleContent, BundleKeys), String> {
+    let raw = fs::read_to_string(path).map_err(|e| format!("Failed to read bundle {}: {}", path, e))?;
+    let file: BundleFile = serde_json::from_str(&raw).map_err(|e| format!("Not a lane bundle: {}", e))?;
+    if file.format != BUNDLE_FORMAT {
+        return Err(format!("Not a lane bundle (format '{}')", file.format));
+    }
+    if file.version > BUNDLE_VERSION {
+        return Err(format!("Bundle version {} is newer than this build supports ({})", file.version, BUNDLE_VERSION));
+    }
+    let salt = general_purpose::STANDARD.decode(&file.salt).map_err(|e| format!("Corrupt bundle salt: {}", e))?;
+    let signature = general_purpose::STANDARD.decode(&file.signature).map_err(|e| format!("Corrupt bundle signature: {}", e))?;
+
+    let keys = derive_keys(passphrase, salt).await?;
+    sign(&keys, &file.content)?
+        .verify_slice(&signature)
+        .map_err(|_| "Bundle signature does not match (wrong passphrase or modified file)".to_string())?;
+    let content = serde_json::from_str(&file.content).map_err(|e| format!("Corrupt bundle content: {}", e))?;
+    Ok((content, keys))
+}
+
+fn decrypt_secrets(content: &BundleContent, keys: &BundleKeys) -> Result<Option<BundleSecrets>, String> {
+    let Some(encrypted) = &content.encrypted_secrets else {
+        return Ok(None);
+    };
+    let nonce = general_purpose::STANDARD.decode(&encrypted.nonce).map_err(|e| format!("Corrupt secrets nonce: {}", e))?;
+    if nonce.len() != 12 {
+        return Err("Corrupt secrets nonce".to_string());
+    }
+    let ciphertext = general_purpose::STANDARD.decode(&encrypted.ciphertext).map_err(|e| format!("Corrupt secrets: {}", e))?;
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&keys.cipher));
+    let plaintext = cipher.decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+        .map_err(|_| "Failed to decrypt bundle secrets".to_string())?;
+    serde_json::from_slice(&plaintext).map(Some).map_err(|e| format!("Corrupt bundle secrets: {}", e))
+}
+
+/// Builds the config the bundle would produce on this lane. Secret fields come from
+/// the bundle when it carries them and from the current config otherwise.
+fn resolve_config(
+    content: &BundleContent,
+    secrets: Option<&BundleSecrets>,
+    current: &AppConfig,
+    gate_name: Option<&str>,
+) -> Result<AppConfig, String> {
+    let current_value = serde_json::to_value(current).map_err(|e| format!("Failed to serialize current settings: {}", e))?;
+    let mut merged = content.config.clone();
+    for field in SECRET_CONFIG_FIELDS {
+        let value = secrets
+            .and_then(|s| s.config_fields.get(*field))
+            .or_else(|| current_value.get(*field));
+        if let Some(value) = value {
+            merged.insert(field.to_string(), value.clone());
+        }
+    }
+    if let Some(gate_name) = gate_name.map(str::trim).filter(|g| !g.is_empty()) {
+        merged.insert("gate_name".to_string(), serde_json::Value::String(gate_name.to_string()));
+    }
+    let mut value = serde_json::Value::Object(merged);
+    config_handler::migrate_config_value(&mut value)?;
+    serde_json::from_value(value).map_err(|e| format!("Bundle settings do not match the settings format: {}", e))
+}
+
+fn secret_names(secrets: Option<&BundleSecrets>) -> Vec<String> {
+    let Some(secrets) = secrets else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = secrets.config_fields.keys().cloned().collect();
+    if secrets.soap_credential.is_some() {
+        names.push("soap_credentials".to_string());
+    }
+    if secrets.remote_config_secret.is_some() {
+        names.push("remote_config_secret".to_string());
+    }
+    names
+}
+
+/// Writes the lane's settings and, optionally, its encrypted secrets to one signed file.
+#[tauri::command]
+pub async fn export_config_bundle_command(
+    config_state: State<'_, AppConfigState>,
+    credential_state: State<'_, CredentialStoreState>,
+    auth_state: State<'_, AuthState>,
+    audit_state: State<'_, AuditLogState>,
+    session_token: String,
+    request: BundleExportRequest,
+) -> Result<String, String> {
+    let BundleExportRequest { destination_path, passphrase, secrets_mode } = request;
+    let session = auth_handler::require_role(&auth_state, &config_state, &session_token, Role::Supervisor)?;
+    check_passphrase(&passphrase)?;
+    let config = config_state.0.lock().map_err(|_| "Failed to acquire config lock")?.clone();
+
+    let mut config_fields = match serde_json::to_value(&config) {
+        Ok(serde_json::Value::Object(map)) => map,
+        _ => return Err("Failed to serialize settings".to_string()),
+    };
+    let mut secrets = BundleSecrets::default();
+    for field in SECRET_CONFIG_FIELDS {
+        if let Some(value) = config_fields.remove(*field) {
+            secrets.config_fields.insert(field.to_string(), value);
+        }
+    }
+
+    let salt = uuid::Uuid::new_v4().as_bytes().to_vec();
+    let keys = derive_keys(passphrase, salt.clone()).await?;
+    let encrypted_secrets = match secrets_mode {
+        BundleSecretsMode::Exclude => None,
+        BundleSecretsMode::Encrypt => {
+            {
+                let store = credential_state.0.lock().map_err(|_| "Failed to acquire credential store lock")?;
+                secrets.soap_credential = store.soap_credential(&config.gate_name)?;
+                secrets.remote_config_secret = store.get(&remote_config_handler::signing_secret_id(&config.gate_name))?;
+            }
+            let plaintext = serde_json::to_vec(&secrets).map_err(|e| format!("Failed to serialize secrets: {}", e))?;
+            let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&keys.cipher));
+            let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+            let ciphertext = cipher.encrypt(&nonce, plaintext.as_slice())
+                .map_err(|_| "Failed to encrypt bundle secrets".to_string())?;
+            Some(EncryptedSecrets {
+                nonce: general_purpose::STANDARD.encode(nonce),
+                ciphertext: general_purpose::STANDARD.encode(ciphertext),
+            })
+        }
+    };
+
+    let content = BundleContent {
+        created_at: chrono::Local::now().to_rfc3339(),
+        created_by: session.username.clone(),
+        source_gate: config.gate_name.clone(),
+        config: config_fields,
+        secrets_mode,
+        encrypted_secrets,
+    };
+    let content = serde_json::to_string_pretty(&content).map_err(|e| format!("Failed to serialize bundle: {}", e))?;
+    let signature = sign(&keys, &content)?.finalize().into_bytes();
+    let file = BundleFile {
+        format: BUNDLE_FORMAT.to_string(),
+        version: BUNDLE_VERSION,
+        salt: general_purpose::STANDARD.encode(&salt),
+        signature: general_purpose::STANDARD.encode(signature),
+        content,
+    };
+    let output = serde_json::to_string_pretty(&file).map_err(|e| format!("Failed to serialize bundle: {}", e))?;
+    fs::write(&destination_path, output)
+        .map_err(|e| format!("Failed to write bundle to {}: {}", destination_path, e))?;
+
+    log::info!("BUNDLE: Settings of {} exported to {} by {} (secrets: {:?})", config.gate_name, destination_path, session.username, secrets_mode);
+    audit_state.record(
+        AuditKind::ConfigChange,
+        &session.username,
+        &config.gate_name,
+        serde_json::json!({ "bundle_export": destination_path, "secrets_mode": secrets_mode, "signature": audit_handler::hex_encode(&signature) }),
+    );
+    Ok(format!("Lane bundle written to {}", destination_path))
+}
+
+/// Verifies a bundle and shows what importing it would change, without applying anything.
+#[tauri::command]
+pub async fn preview_config_bundle_command(
+    config_state: State<'_, AppConfigState>,
+    auth_state: State<'_, AuthState>,
+    session_token: String,
+    request: BundleImportRequest,
+) -> Result<BundlePreview, String> {
+    let BundleImportRequest { bundle_path, passphrase, gate_name } = request;
+    auth_handler::require_role(&auth_state, &config_state, &session_token, Role::Supervisor)?;
+    let (content, keys) = open_bundle(&bundle_path, passphrase).await?;
+    let secrets = decrypt_secrets(&content, &keys)?;
+    let current = config_state.0.lock().map_err(|_| "Failed to acquire config lock")?.clone();
+    let imported = resolve_config(&content, secrets.as_ref(), &current, gate_name.as_deref())?;
+    Ok(BundlePreview {
+        changes: config_handler::diff_configs(&current, &imported),
+        validation: imported.validate(),
+        secrets: secret_names(secrets.as_ref()),
+        secrets_included: secrets.is_some(),
+        source_gate: content.source_gate,
+        created_at: content.created_at,
+        created_by: content.created_by,
+    })
+}
+
+/// Applies a bundle after the same validation as a settings save. A `gate_name`
+/// in the request renames the lane, the usual case when commissioning from a neighbour.
+#[tauri::command]
+pub async fn import_config_bundle_command(
+    app_handle: tauri::AppHandle,
+    config_state: State<'_, AppConfigState>,
+    credential_state: State<'_, CredentialStoreState>,
+    auth_state: State<'_, AuthState>,
+    audit_state: State<'_, AuditLogState>,
+    session_token: String,
+    request: BundleImportRequest,
+) -> Result<Vec<ConfigFieldChange>, String> {
+    let BundleImportRequest { bundle_path, passphrase, gate_name } = request;
+    let session = auth_handler::require_role(&auth_state, &config_state, &session_token, Role::Supervisor)?;
+    let (content, keys) = open_bundle(&bundle_path, passphrase).await?;
+    let secrets = decrypt_secrets(&content, &keys)?;
+    let previous = config_state.0.lock().map_err(|_| "Failed to acquire config lock")?.clone();
+    let imported = resolve_config(&content, secrets.as_ref(), &previous, gate_name.as_deref())?;
+    if let Some(message) = config_handler::blocking_errors(&imported.validate()) {
+        log::warn!("BUNDLE: Rejected bundle {} from {}: {}", bundle_path, session.username, message);
+        return Err(message);
+    }
+
+    let changes = config_handler::diff_configs(&previous, &imported);
+    config_handler::write_app_settings(&app_handle, imported.clone(), &config_state)?;
+    config_reload_handler::reconfigure(&app_handle, &previous, &imported, "bundle").await?;
+
+    if let Some(secrets) = &secrets {
+        let mut store = credential_state.0.lock().map_err(|_| "Failed to acquire credential store lock")?;
+        if let Some(credential) = &secrets.soap_credential {
+            store.set_soap_credential(&imported.gate_name, credential)?;
+        }
+        if let Some(secret) = &secrets.remote_config_secret {
+            store.put(&remote_config_handler::signing_secret_id(&imported.gate_name), secret)?;
+        }
+    }
+
+    log::info!("BUNDLE: Bundle from {} imported as {} by {}", content.source_gate, imported.gate_name, session.username);
+    audit_state.record(
+        AuditKind::ConfigChange,
+        &session.username,
+        &imported.gate_name,
+        serde_json::json!({
+            "bundle_import": bundle_path,
+            "source_gate": content.source_gate,
+            "secrets": secret_names(secrets.as_ref()),
+            "changes": changes,
+        }),
+    );
+    Ok(changes)
+}
@@ -1,64 +1,827 @@
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::PathBuf;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
 use tauri::Manager;
 
+use crate::audit_handler::{AuditKind, AuditLogState};
+use crate::auth_handler::{self, AuthState, Role};
+use crate::config_reload_handler;
+use crate::issuer_handler::{CardType, SUPPORTED_CARD_TYPES};
+use crate::money::Money;
+use crate::tariff_handler::{self, TariffConfig};
+
+/// Fields whose values are masked wherever configs are diffed or shown.
+pub const SECRET_CONFIG_FIELDS: &[&str] = &["emoney_init_key"];
+
+/// Schema version written by this build. Files without `config_version` are version 1.
+pub const CURRENT_CONFIG_VERSION: u32 = 3;
+
+const STANDARD_BAUD_RATES: &[u32] = &[1200, 2400, 4800, 9600, 19200, 38400, 57600, 115200, 230400];
+
+/// How the SOAP requests to CGS authenticate themselves.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SoapAuthMode {
+    /// AuthHeader derived from the gate name (base64 of gate name / gate name + "PWD").
+    #[default]
+    Legacy,
+    /// AuthHeader carrying the stored per-gate credentials.
+    AuthHeader,
+    /// WS-Security UsernameToken with the password in clear text.
+    WsSecurityText,
+    /// WS-Security UsernameToken with PasswordDigest, nonce and created timestamp.
+    WsSecurityDigest,
+}
+
+/// What a lane does with a truck when CGS cannot be reached.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DegradedModePolicy {
+    /// Stop the lane until CGS answers again.
+    #[default]
+    Block,
+    /// Let through tags and gate passes CGS accepted within `validation_cache_ttl_secs`.
+    AllowKnown,
+    /// Let every truck through and reconcile with CGS once it is back.
+    AllowAll,
+}
+
+impl DegradedModePolicy {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DegradedModePolicy::Block => "block",
+            DegradedModePolicy::AllowKnown => "allow_known",
+            DegradedModePolicy::AllowAll => "allow_all",
+        }
+    }
+}
+
+/// One e-money reader on the lane. `id` names it in card events and payment routing.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ReaderConfig {
+    pub id: String,
+    /// Fixed device path. With a USB match set it is only used when no adapter matches.
+    #[serde(default)]
+    pub port: String,
+    pub baud_rate: u32,
+    /// Find the adapter by USB identity instead, so it survives being re-plugged
+    /// under another path. Every field that is set must match.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usb_vid: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usb_pid: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usb_serial: Option<String>,
+}
+
+impl ReaderConfig {
+    pub fn has_usb_match(&self) -> bool {
+        self.usb_vid.is_some() || self.usb_pid.is_some() || self.usb_serial.is_some()
+    }
+
+    /// True when `port` is a USB adapter with the configured VID/PID/serial number.
+    pub fn matches_usb(&self, port: &serialport::SerialPortInfo) -> bool {
+        match &port.port_type {
+            serialport::SerialPortType::UsbPort(usb) => {
+                self.has_usb_match()
+                    && self.usb_vid.map_or(true, |vid| vid == usb.vid)
+                    && self.usb_pid.map_or(true, |pid| pid == usb.pid)
+                    && self.usb_serial.as_ref().map_or(true, |serial| usb.serial_number.as_deref() == Some(serial.as_str()))
+            }
+            _ => false,
+        }
+    }
+}
+
+/// True when `port` is among the enumerated serial ports. Paths are compared after
+/// resolving symlinks, so a /dev/serial/by-id link matches the tty it points to.
+pub fn is_port_listed(ports: &[serialport::SerialPortInfo], port: &str) -> bool {
+    if ports.iter().any(|p| p.port_name == port) {
+        return true;
+    }
+    let canonical = |path: &str| fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path));
+    let wanted = canonical(port);
+    ports.iter().any(|p| canonical(&p.port_name) == wanted)
+}
+
+/// An ANPR camera over the truck lane. Plate events it pushes are only taken from `ip`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct AnprCameraConfig {
+    pub id: String,
+    pub ip: String,
+    /// HTTP endpoint that makes the camera read a plate now and answers with it.
+    /// Left empty, the camera only pushes plate events.
+    #[serde(default)]
+    pub trigger_url: String,
+}
+
+fn default_reader_port() -> String {
+    if cfg!(windows) {
+        "COM1".to_string()
+    } else {
+        "/dev/ttyUSB0".to_string()
+    }
+}
+
+/// Lane configuration. Fields missing from a saved file take their default value,
+/// so adding a field never invalidates existing lane configs.
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
 pub struct AppConfig {
+    pub config_version: u32,
     pub cgs_gateway_url: String,
     pub device_gateway_url: String,
     pub cacm_tool_url: String,
     pub gate_name: String,
     pub gate_type: i32, // 0 for IN, 1 for OUT
-    pub emoney_reader_port: String,
-    pub emoney_baud_rate: u32,
+    pub emoney_readers: Vec<ReaderConfig>,
     pub emoney_init_key: String,
-    pub emoney_deduct_price: f64,
+    pub emoney_deduct_price: Money,
+    /// The same card read again within this many milliseconds is not reported as a new tap.
+    pub card_debounce_ms: u64,
+    /// E-money issuers whose cards this site takes payment from.
+    pub enabled_issuers: Vec<CardType>,
+    /// Charge rules; with none, every truck pays `emoney_deduct_price`.
+    pub tariff: TariffConfig,
     pub adam_portal_ip: String,
     pub adam_portal_port: u16,
     pub adam_button_ip: String,
     pub adam_button_port: u16,
+    pub soap_auth_mode: SoapAuthMode,
+    pub degraded_mode: DegradedModePolicy,
+    /// How long a tag or gate pass CGS accepted still counts as known in degraded mode.
+    pub validation_cache_ttl_secs: u64,
+    pub operator_idle_timeout_secs: u64,
+    /// Pull this lane's settings from CaCMTool, keyed by `gate_name`.
+    pub remote_config_enabled: bool,
+    pub remote_config_interval_secs: u64,
+    /// Pull banned tags and stolen cards from CaCMTool as deltas.
+    pub blacklist_sync_enabled: bool,
+    pub blacklist_sync_interval_secs: u64,
+    /// Report lane health to DeviceGatewayService and run the commands it queues.
+    pub device_gateway_enabled: bool,
+    pub device_gateway_interval_secs: u64,
+    /// Long-range UHF reader for windshield truck tags, spoken to over LLRP.
+    pub uhf_enabled: bool,
+    pub uhf_reader_ip: String,
+    pub uhf_reader_port: u16,
+    /// Antennas to read from; empty means all of them.
+    pub uhf_antennas: Vec<u16>,
+    /// Reads weaker than this (dBm) are ignored, e.g. trucks in the next lane.
+    pub uhf_min_rssi: i32,
+    /// A tag seen again within this many seconds of its last read is not reported again.
+    pub uhf_duplicate_window_secs: u64,
+    /// Plate cameras, cross-checked against the police number registered to the tag.
+    pub anpr_enabled: bool,
+    /// Port the lane listens on for plate events pushed by the cameras.
+    pub anpr_listen_port: u16,
+    pub anpr_cameras: Vec<AnprCameraConfig>,
+    /// Gate OCR systems reporting container numbers and ISO codes, checked against the gate passes.
+    pub ocr_enabled: bool,
+    /// Port the lane listens on for recognitions, over HTTP or as plain TCP lines.
+    pub ocr_listen_port: u16,
+    /// Addresses of the OCR systems; recognitions from anywhere else are ignored.
+    pub ocr_sources: Vec<String>,
+    /// Refuse gate-in when the recognized containers do not match, instead of only alerting.
+    pub ocr_block_on_mismatch: bool,
 }
 
 impl Default for AppConfig {
     fn default() -> Self {
         Self {
+            config_version: CURRENT_CONFIG_VERSION,
             cgs_gateway_url: "https://cusmod-ca.multiterminal.co.id/cgsin02/services/services.asmx".to_string(),
             device_gateway_url: "https://cusmod-ca.multiterminal.co.id/DeviceGateway/DeviceGatewayService.asmx".to_string(),
             cacm_tool_url: "http://cacmtool.halotec.my.id".to_string(),
             gate_name: "GATE_A01".to_string(),
             gate_type: 0, // IN
-            emoney_reader_port: if cfg!(windows) { 
-                "COM1".to_string() 
-            } else { 
-                "/dev/ttyUSB0".to_string() 
-            },
-            emoney_baud_rate: 38400,
+            emoney_readers: vec![ReaderConfig {
+                id: "main".to_string(),
+                port: default_reader_port(),
+                baud_rate: 38400,
+                usb_vid: None,
+                usb_pid: None,
+                usb_serial: None,
+            }],
             emoney_init_key: "FE45DF39F44A4866AD7153136E051B0A".to_string(),
-            emoney_deduct_price: 17000.0,
+            emoney_deduct_price: Money::idr(17_000),
+            card_debounce_ms: 2000,
+            enabled_issuers: SUPPORTED_CARD_TYPES.to_vec(),
+            tariff: TariffConfig::default(),
             adam_portal_ip: "10.0.0.10".to_string(),
             adam_portal_port: 502,
             adam_button_ip: "10.0.0.11".to_string(),
             adam_button_port: 502,
+            soap_auth_mode: SoapAuthMode::Legacy,
+            degraded_mode: DegradedModePolicy::Block,
+            validation_cache_ttl_secs: 43_200,
+            operator_idle_timeout_secs: 300,
+            remote_config_enabled: false,
+            remote_config_interval_secs: 300,
+            blacklist_sync_enabled: false,
+            blacklist_sync_interval_secs: 300,
+            device_gateway_enabled: false,
+            device_gateway_interval_secs: 60,
+            uhf_enabled: false,
+            uhf_reader_ip: "10.0.0.20".to_string(),
+            uhf_reader_port: 5084,
+            uhf_antennas: Vec::new(),
+            uhf_min_rssi: -70,
+            uhf_duplicate_window_secs: 10,
+            anpr_enabled: false,
+            anpr_listen_port: 8091,
+            anpr_cameras: Vec::new(),
+            ocr_enabled: false,
+            ocr_listen_port: 8093,
+            ocr_sources: Vec::new(),
+            ocr_block_on_mismatch: false,
         }
     }
 }
 
 pub struct AppConfigState(pub Mutex<AppConfig>);
 
-fn get_config_path(app_handle: &tauri::AppHandle) -> Result<PathBuf, Box<dyn std::error::Error>> {
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldSeverity {
+    /// Blocks saving.
+    Error,
+    /// Shown to the technician but accepted, e.g. a reader that is currently unplugged.
+    Warning,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+    pub severity: FieldSeverity,
+}
+
+impl FieldError {
+    fn error(field: &str, message: impl Into<String>) -> Self {
+        FieldError { field: field.to_string(), message: message.into(), severity: FieldSeverity::Error }
+    }
+
+    fn warning(field: &str, message: impl Into<String>) -> Self {
+        FieldError { field: field.to_string(), message: message.into(), severity: FieldSeverity::Warning }
+    }
+}
+
+fn validate_url(field: &str, value: &str, errors: &mut Vec<FieldError>) {
+    match url::Url::parse(value) {
+        Ok(parsed) if !matches!(parsed.scheme(), "http" | "https") => {
+            errors.push(FieldError::error(field, format!("Unsupported URL scheme '{}', expected http or https", parsed.scheme())));
+        }
+        Ok(parsed) if parsed.host_str().map_or(true, str::is_empty) => {
+            errors.push(FieldError::error(field, "URL has no host"));
+        }
+        Ok(_) => {}
+        Err(e) => errors.push(FieldError::error(field, format!("Invalid URL: {}", e))),
+    }
+}
+
+fn validate_endpoint(ip_field: &str, ip: &str, port_field: &str, port: u16, errors: &mut Vec<FieldError>) {
+    if ip.parse::<IpAddr>().is_err() {
+        errors.push(FieldError::error(ip_field, format!("'{}' is not a valid IP address", ip)));
+    }
+    if port == 0 {
+        errors.push(FieldError::error(port_field, "Port must be between 1 and 65535"));
+    }
+}
+
+fn validate_readers(readers: &[ReaderConfig], errors: &mut Vec<FieldError>) {
+    if readers.is_empty() {
+        errors.push(FieldError::error("emoney_readers", "At least one e-money reader must be configured"));
+        return;
+    }
+    let available_ports = serialport::available_ports()
+        .map_err(|e| log::warn!("Could not enumerate serial ports for validation: {}", e))
+        .ok();
+    for (index, reader) in readers.iter().enumerate() {
+        let field = |name: &str| format!("emoney_readers[{}].{}", index, name);
+        if reader.id.trim().is_empty() {
+            errors.push(FieldError::error(&field("id"), "Reader id must not be empty"));
+        } else if readers[..index].iter().any(|r| r.id == reader.id) {
+            errors.push(FieldError::error(&field("id"), format!("Reader id '{}' is used more than once", reader.id)));
+        }
+        if reader.has_usb_match() {
+            if let Some(ports) = &available_ports {
+                if !ports.iter().any(|p| reader.matches_usb(p)) {
+                    errors.push(FieldError::warning(&field("usb_vid"), "No connected USB serial adapter matches this reader"));
+                }
+            }
+        } else if reader.port.trim().is_empty() {
+            errors.push(FieldError::error(&field("port"), "Reader port must not be empty"));
+        } else if let Some(ports) = &available_ports {
+            if !is_port_listed(ports, &reader.port) {
+                errors.push(FieldError::warning(&field("port"), format!("Serial port {} is not currently present", reader.port)));
+            }
+        }
+        if !reader.port.trim().is_empty() && readers[..index].iter().any(|r| r.port == reader.port) {
+            errors.push(FieldError::error(&field("port"), format!("Port {} is already used by another reader", reader.port)));
+        }
+        if reader.usb_serial.as_deref().is_some_and(|serial| serial.trim().is_empty()) {
+            errors.push(FieldError::error(&field("usb_serial"), "USB serial number must not be empty when set"));
+        }
+        if !STANDARD_BAUD_RATES.contains(&reader.baud_rate) {
+            errors.push(FieldError::error(&field("baud_rate"), format!("Baud rate must be one of {:?}", STANDARD_BAUD_RATES)));
+        }
+    }
+}
+
+fn validate_tariff(tariff: &TariffConfig, errors: &mut Vec<FieldError>) {
+    for (index, rule) in tariff.rules.iter().enumerate() {
+        let field = |name: &str| format!("tariff.rules[{}].{}", index, name);
+        if rule.code.trim().is_empty() {
+            errors.push(FieldError::error(&field("code"), "Tariff code must not be empty"));
+        } else if tariff.rules[..index].iter().any(|r| r.code == rule.code) {
+            errors.push(FieldError::error(&field("code"), format!("Tariff code '{}' is used more than once", rule.code)));
+        }
+        if rule.description.trim().is_empty() {
+            errors.push(FieldError::error(&field("description"), "Description must not be empty; it is printed on the slip"));
+        }
+        if !rule.amount.is_positive() {
+            errors.push(FieldError::error(&field("amount"), "Amount must be greater than zero"));
+        }
+        if let Some(size) = rule.sizes.iter().find(|size| !tariff_handler::is_known_length(size.trim())) {
+            errors.push(FieldError::error(&field("sizes"), format!("'{}' is not an ISO container length in feet", size)));
+        }
+        if rule.iso_codes.iter().any(|code| code.trim().is_empty()) {
+            errors.push(FieldError::error(&field("iso_codes"), "ISO codes must not be empty"));
+        }
+        if let (Some(min), Some(max)) = (rule.min_containers, rule.max_containers) {
+            if min > max {
+                errors.push(FieldError::error(&field("min_containers"), "Minimum containers is above the maximum"));
+            }
+        }
+        if let Some(Err(e)) = rule.hours.as_deref().map(tariff_handler::parse_hours) {
+            errors.push(FieldError::error(&field("hours"), e));
+        }
+        if rule.min_dwell_mins.is_some_and(|min| min < 0) {
+            errors.push(FieldError::error(&field("min_dwell_mins"), "Dwell time must not be negative"));
+        }
+        if let (Some(min), Some(max)) = (rule.min_dwell_mins, rule.max_dwell_mins) {
+            if min > max {
+                errors.push(FieldError::error(&field("min_dwell_mins"), "Minimum dwell time is above the maximum"));
+            }
+        }
+    }
+
+    let overstay = &tariff.overstay;
+    if overstay.grace_mins < 0 {
+        errors.push(FieldError::error("tariff.overstay.grace_mins", "Grace period must not be negative"));
+    }
+    for (index, band) in overstay.bands.iter().enumerate() {
+        let field = |name: &str| format!("tariff.overstay.bands[{}].{}", index, name);
+        if !band.amount.is_positive() {
+            errors.push(FieldError::error(&field("amount"), "Amount must be greater than zero"));
+        }
+        match (band.up_to_mins, index > 0) {
+            (Some(limit), _) if limit <= 0 => {
+                errors.push(FieldError::error(&field("up_to_mins"), "Band limit must be greater than zero"));
+            }
+            (Some(limit), true) if overstay.bands[index - 1].up_to_mins.map_or(true, |previous| limit <= previous) => {
+                errors.push(FieldError::error(&field("up_to_mins"), "Bands must be in ascending order of up_to_mins"));
+            }
+            (None, _) if index + 1 < overstay.bands.len() => {
+                errors.push(FieldError::error(&field("up_to_mins"), "Only the last band may be open-ended"));
+            }
+            _ => {}
+        }
+    }
+}
+
+fn validate_anpr_cameras(cameras: &[AnprCameraConfig], errors: &mut Vec<FieldError>) {
+    if cameras.is_empty() {
+        errors.push(FieldError::error("anpr_cameras", "At least one ANPR camera must be configured"));
+    }
+    for (index, camera) in cameras.iter().enumerate() {
+        let field = |name: &str| format!("anpr_cameras[{}].{}", index, name);
+        if camera.id.trim().is_empty() {
+            errors.push(FieldError::error(&field("id"), "Camera id must not be empty"));
+        } else if cameras[..index].iter().any(|c| c.id == camera.id) {
+            errors.push(FieldError::error(&field("id"), format!("Camera id '{}' is used more than once", camera.id)));
+        }
+        if camera.ip.parse::<IpAddr>().is_err() {
+            errors.push(FieldError::error(&field("ip"), format!("'{}' is not a valid IP address", camera.ip)));
+        }
+        if !camera.trigger_url.is_empty() {
+            validate_url(&field("trigger_url"), &camera.trigger_url, errors);
+        }
+    }
+}
+
+impl AppConfig {
+    /// Checks every field, returning all problems at once so the settings form can
+    /// mark each offending input. An empty list means the config is valid.
+    pub fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+
+        validate_url("cgs_gateway_url", &self.cgs_gateway_url, &mut errors);
+        validate_url("device_gateway_url", &self.device_gateway_url, &mut errors);
+        validate_url("cacm_tool_url", &self.cacm_tool_url, &mut errors);
+
+        if self.gate_name.trim().is_empty() {
+            errors.push(FieldError::error("gate_name", "Gate name must not be empty"));
+        } else if !self.gate_name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            errors.push(FieldError::error("gate_name", "Gate name may only contain letters, digits, '_' and '-'"));
+        }
+        if !matches!(self.gate_type, 0 | 1) {
+            errors.push(FieldError::error("gate_type", format!("Gate type must be 0 (IN) or 1 (OUT), got {}", self.gate_type)));
+        }
+
+        validate_readers(&self.emoney_readers, &mut errors);
+        if self.emoney_init_key.len() != 32 || !self.emoney_init_key.chars().all(|c| c.is_ascii_hexdigit()) {
+            errors.push(FieldError::error("emoney_init_key", "Init key must be 32 hexadecimal characters"));
+        }
+        if !self.emoney_deduct_price.is_positive() {
+            errors.push(FieldError::error("emoney_deduct_price", "Deduct price must be greater than zero"));
+        }
+        validate_tariff(&self.tariff, &mut errors);
+
+        validate_endpoint("adam_portal_ip", &self.adam_portal_ip, "adam_portal_port", self.adam_portal_port, &mut errors);
+        validate_endpoint("adam_button_ip", &self.adam_button_ip, "adam_button_port", self.adam_button_port, &mut errors);
+
+        if self.enabled_issuers.is_empty() {
+            errors.push(FieldError::error("enabled_issuers", "At least one e-money issuer must be enabled"));
+        }
+        if let Some(unsupported) = self.enabled_issuers.iter().find(|issuer| !SUPPORTED_CARD_TYPES.contains(issuer)) {
+            errors.push(FieldError::error("enabled_issuers", format!("{:?} is not a supported issuer", unsupported)));
+        }
+        if self.card_debounce_ms > 60_000 {
+            errors.push(FieldError::error("card_debounce_ms", "Card debounce must be at most 60 seconds"));
+        }
+        if !(60..=604_800).contains(&self.validation_cache_ttl_secs) {
+            errors.push(FieldError::error("validation_cache_ttl_secs", "Validation cache period must be between 1 minute and 7 days"));
+        }
+        if !(30..=86_400).contains(&self.operator_idle_timeout_secs) {
+            errors.push(FieldError::error("operator_idle_timeout_secs", "Idle timeout must be between 30 seconds and 24 hours"));
+        }
+        if !(30..=86_400).contains(&self.remote_config_interval_secs) {
+            errors.push(FieldError::error("remote_config_interval_secs", "Remote config interval must be between 30 seconds and 24 hours"));
+        }
+        if !(30..=86_400).contains(&self.blacklist_sync_interval_secs) {
+            errors.push(FieldError::error("blacklist_sync_interval_secs", "Blacklist sync interval must be between 30 seconds and 24 hours"));
+        }
+        if !(15..=3_600).contains(&self.device_gateway_interval_secs) {
+            errors.push(FieldError::error("device_gateway_interval_secs", "Device gateway interval must be between 15 seconds and 1 hour"));
+        }
+
+        if self.uhf_enabled {
+            validate_endpoint("uhf_reader_ip", &self.uhf_reader_ip, "uhf_reader_port", self.uhf_reader_port, &mut errors);
+        }
+        if self.uhf_antennas.contains(&0) {
+            errors.push(FieldError::error("uhf_antennas", "Antenna numbers start at 1; leave the list empty to use all antennas"));
+        }
+        if !(-128..=0).contains(&self.uhf_min_rssi) {
+            errors.push(FieldError::error("uhf_min_rssi", "Minimum RSSI must be between -128 and 0 dBm"));
+        }
+        if !(1..=3_600).contains(&self.uhf_duplicate_window_secs) {
+            errors.push(FieldError::error("uhf_duplicate_window_secs", "Duplicate window must be between 1 second and 1 hour"));
+        }
+        if self.anpr_enabled {
+            validate_anpr_cameras(&self.anpr_cameras, &mut errors);
+            if self.anpr_listen_port == 0 {
+                errors.push(FieldError::error("anpr_listen_port", "Port must be between 1 and 65535"));
+            }
+        }
+        if self.ocr_enabled {
+            if self.ocr_sources.is_empty() {
+                errors.push(FieldError::error("ocr_sources", "At least one OCR system address must be configured"));
+            }
+            for (index, source) in self.ocr_sources.iter().enumerate() {
+                if source.parse::<IpAddr>().is_err() {
+                    errors.push(FieldError::error(&format!("ocr_sources[{}]", index), format!("'{}' is not a valid IP address", source)));
+                }
+            }
+            if self.ocr_listen_port == 0 {
+                errors.push(FieldError::error("ocr_listen_port", "Port must be between 1 and 65535"));
+            } else if self.anpr_enabled && self.ocr_listen_port == self.anpr_listen_port {
+                errors.push(FieldError::error("ocr_listen_port", "The OCR and ANPR listeners need different ports"));
+            }
+        }
+
+        errors
+    }
+}
+
+/// Formats the blocking problems of a validation result, or `None` if there are none.
+pub fn blocking_errors(errors: &[FieldError]) -> Option<String> {
+    let blocking: Vec<String> = errors.iter()
+        .filter(|e| e.severity == FieldSeverity::Error)
+        .map(|e| format!("{}: {}", e.field, e.message))
+        .collect();
+    if blocking.is_empty() {
+        None
+    } else {
+        Some(format!("Invalid settings: {}", blocking.join("; ")))
+    }
+}
+
+type Migration = fn(&mut serde_json::Map<String, serde_json::Value>);
+
+/// `MIGRATIONS[n]` upgrades a version `n + 1` document to version `n + 2`.
+const MIGRATIONS: &[Migration] = &[migrate_v1_to_v2, migrate_v2_to_v3];
+
+/// v2 added SOAP auth modes and operator sessions. Lanes upgrading keep the
+/// gate-name-derived AuthHeader they were using.
+fn migrate_v1_to_v2(map: &mut serde_json::Map<String, serde_json::Value>) {
+    map.entry("soap_auth_mode").or_insert_with(|| serde_json::json!("legacy"));
+}
+
+/// v3 replaced the single reader port and baud rate with a list of named readers.
+fn migrate_v2_to_v3(map: &mut serde_json::Map<String, serde_json::Value>) {
+    let port = map.remove("emoney_reader_port");
+    let baud_rate = map.remove("emoney_baud_rate");
+    if port.is_none() && baud_rate.is_none() {
+        return;
+    }
+    map.insert("emoney_readers".to_string(), serde_json::json!([{
+        "id": "main",
+        "port": port.unwrap_or_else(|| serde_json::json!(default_reader_port())),
+        "baud_rate": baud_rate.unwrap_or_else(|| serde_json::json!(38400)),
+    }]));
+}
+
+/// Upgrades a raw settings document in place. Returns the version it started at.
+pub fn migrate_config_value(value: &mut serde_json::Value) -> Result<u32, String> {
+    let map = value.as_object_mut()
+        .ok_or_else(|| "Settings file does not contain a JSON object".to_string())?;
+    let from_version = match map.get("config_version") {
+        None => 1,
+        Some(v) => v.as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .ok_or_else(|| format!("Invalid config_version: {}", v))?,
+    };
+    if from_version == 0 || from_version > CURRENT_CONFIG_VERSION {
+        return Err(format!(
+            "Settings file has config_version {}, this build supports up to {}",
+            from_version, CURRENT_CONFIG_VERSION
+        ));
+    }
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(from_version as usize - 1) {
+        log::info!("Migrating settings from version {} to {}", index + 1, index + 2);
+        migration(map);
+    }
+    map.insert("config_version".to_string(), serde_json::json!(CURRENT_CONFIG_VERSION));
+    Ok(from_version)
+}
+
+/// Reads and migrates the per-lane settings file, returning only the keys it sets.
+/// A migrated file is rewritten in the current format, keeping the original next to
+/// it as a backup.
+fn read_lane_layer(path: &Path) -> Result<serde_json::Map<String, serde_json::Value>, String> {
+    if !path.exists() {
+        return Ok(serde_json::Map::new());
+    }
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read settings file: {}", e))?;
+    let mut value: serde_json::Value = serde_json::from_str(&content)
+        .map_err(|e| format!("Settings file {:?} is not valid JSON: {}", path, e))?;
+    let from_version = migrate_config_value(&mut value)?;
+
+    if from_version < CURRENT_CONFIG_VERSION {
+        let backup = path.with_extension(format!("v{}.bak", from_version));
+        fs::copy(path, &backup)
+            .map_err(|e| format!("Failed to back up settings before migration: {}", e))?;
+        let migrated = serde_json::to_string_pretty(&value)
+            .map_err(|e| format!("Failed to serialize migrated settings: {}", e))?;
+        fs::write(path, migrated)
+            .map_err(|e| format!("Failed to write migrated settings: {}", e))?;
+        log::info!("Settings migrated from version {} to {} (backup at {:?})", from_version, CURRENT_CONFIG_VERSION, backup);
+    }
+    match value {
+        serde_json::Value::Object(map) => Ok(map),
+        _ => Err("Settings file does not contain a JSON object".to_string()),
+    }
+}
+
+/// Where a setting's effective value came from, lowest precedence first.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigLayer {
+    Default,
+    Site,
+    Lane,
+    Environment,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct EffectiveField {
+    pub field: String,
+    pub value: serde_json::Value,
+    pub source: ConfigLayer,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct EffectiveConfigReport {
+    pub site_config_path: String,
+    pub site_config_present: bool,
+    pub lane_config_path: String,
+    pub fields: Vec<EffectiveField>,
+}
+
+const ENV_PREFIX: &str = "CACM";
+const SITE_CONFIG_ENV: &str = "CACM_SITE_CONFIG";
+const SITE_CONFIG_FILE_NAME: &str = "site.toml";
+const LANE_CONFIG_FILE_NAME: &str = "app_settings.json";
+
+/// The site TOML is shared by every lane of a terminal; `CACM_SITE_CONFIG` may
+/// point at a network share instead of the local app data directory.
+pub fn site_config_path(data_dir: &Path) -> PathBuf {
+    std::env::var_os(SITE_CONFIG_ENV)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| data_dir.join(SITE_CONFIG_FILE_NAME))
+}
+
+fn env_var_name(field: &str) -> String {
+    format!("{}_{}", ENV_PREFIX, field.to_uppercase())
+}
+
+fn read_site_layer(path: &Path) -> Result<toml::Table, String> {
+    if !path.exists() {
+        return Ok(toml::Table::new());
+    }
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read site config {:?}: {}", path, e))?;
+    let mut site = content.parse::<toml::Table>()
+        .map_err(|e| format!("Site config {:?} is not valid TOML: {}", path, e))?;
+    migrate_site_layer(&mut site, path)?;
+    Ok(site)
+}
+
+/// The site TOML carries no version and may be shared by lanes on older builds, so
+/// it is not rewritten; the single-reader keys v3 replaced are migrated in memory.
+fn migrate_site_layer(site: &mut toml::Table, path: &Path) -> Result<(), String> {
+    let legacy = ["emoney_reader_port", "emoney_baud_rate"];
+    if !legacy.iter().any(|key| site.contains_key(*key)) {
+        return Ok(());
+    }
+    if site.contains_key("emoney_readers") {
+        return Err(format!(
+            "Site config {:?} sets both emoney_readers and the legacy emoney_reader_port/emoney_baud_rate; remove the legacy keys",
+            path
+        ));
+    }
+    let mut map = match serde_json::to_value(&*site) {
+        Ok(serde_json::Value::Object(map)) => map,
+        _ => return Err(format!("Failed to migrate site config {:?}", path)),
+    };
+    migrate_v2_to_v3(&mut map);
+    *site = serde_json::from_value(serde_json::Value::Object(map))
+        .map_err(|e| format!("Failed to migrate site config {:?}: {}", path, e))?;
+    log::warn!("Site config {:?} uses emoney_reader_port/emoney_baud_rate; read as emoney_readers, please update the file", path);
+    Ok(())
+}
+
+/// Stacks defaults, site TOML, lane JSON and (optionally) `CACM_*` variables.
+fn merge_layers(
+    site: &toml::Table,
+    lane: Option<&serde_json::Map<String, serde_json::Value>>,
+    with_env: bool,
+) -> Result<AppConfig, String> {
+    let defaults = config::Config::try_from(&AppConfig::default())
+        .map_err(|e| format!("Failed to build default config layer: {}", e))?;
+    let site = toml::to_string(site)
+        .map_err(|e| format!("Failed to re-serialize site config: {}", e))?;
+    let mut builder = config::Config::builder()
+        .add_source(defaults)
+        .add_source(config::File::from_str(&site, config::FileFormat::Toml));
+    if let Some(lane) = lane {
+        let lane = serde_json::to_string(lane)
+            .map_err(|e| format!("Failed to re-serialize lane config: {}", e))?;
+        builder = builder.add_source(config::File::from_str(&lane, config::FileFormat::Json));
+    }
+    if with_env {
+        // Values stay strings; `config` converts them per field, so "0012" stays "0012".
+        builder = builder.add_source(config::Environment::with_prefix(ENV_PREFIX));
+    }
+    builder.build()
+        .and_then(|merged| merged.try_deserialize::<AppConfig>())
+        .map_err(|e| format!("Failed to merge configuration layers: {}", e))
+}
+
+/// The effective configuration plus the raw layers it was built from.
+pub struct LayeredConfig {
+    pub config: AppConfig,
+    pub site_path: PathBuf,
+    pub lane_path: PathBuf,
+    site: toml::Table,
+    lane: serde_json::Map<String, serde_json::Value>,
+}
+
+impl LayeredConfig {
+    pub fn load(data_dir: &Path) -> Result<Self, String> {
+        let site_path = site_config_path(data_dir);
+        let lane_path = data_dir.join(LANE_CONFIG_FILE_NAME);
+        let site = read_site_layer(&site_path)?;
+        let lane = read_lane_layer(&lane_path)?;
+        let config = merge_layers(&site, Some(&lane), true)?;
+        Ok(LayeredConfig { config, site_path, lane_path, site, lane })
+    }
+
+    pub fn source_of(&self, field: &str) -> ConfigLayer {
+        if std::env::var_os(env_var_name(field)).is_some() {
+            ConfigLayer::Environment
+        } else if self.lane.contains_key(field) {
+            ConfigLayer::Lane
+        } else if self.site.contains_key(field) {
+            ConfigLayer::Site
+        } else {
+            ConfigLayer::Default
+        }
+    }
+
+    pub fn report(&self) -> EffectiveConfigReport {
+        let value = serde_json::to_value(&self.config).unwrap_or_default();
+        let fields = value.as_object()
+            .map(|map| map.iter()
+                .map(|(field, value)| EffectiveField {
+                    field: field.clone(),
+                    value: if SECRET_CONFIG_FIELDS.contains(&field.as_str()) {
+                        serde_json::Value::String("********".to_string())
+                    } else {
+                        value.clone()
+                    },
+                    source: self.source_of(field),
+                })
+                .collect())
+            .unwrap_or_default();
+        EffectiveConfigReport {
+            site_config_path: self.site_path.display().to_string(),
+            site_config_present: self.site_path.exists(),
+            lane_config_path: self.lane_path.display().to_string(),
+            fields,
+        }
+    }
+
+    /// The keys of `settings` the lane file has to store: everything that differs
+    /// from defaults + site, except values still supplied unchanged by the environment.
+    fn lane_overrides(&self, settings: &AppConfig) -> Result<serde_json::Map<String, serde_json::Value>, String> {
+        let base = serde_json::to_value(merge_layers(&self.site, None, false)?).unwrap_or_default();
+        let with_env = serde_json::to_value(merge_layers(&self.site, None, true)?).unwrap_or_default();
+        let wanted = serde_json::to_value(settings)
+            .map_err(|e| format!("Failed to serialize settings: {}", e))?;
+        let mut overrides = serde_json::Map::new();
+        if let Some(wanted) = wanted.as_object() {
+            for (field, value) in wanted {
+                let from_env = std::env::var_os(env_var_name(field)).is_some() && with_env.get(field) == Some(value);
+                if field == "config_version" || (base.get(field) != Some(value) && !from_env) {
+                    overrides.insert(field.clone(), value.clone());
+                }
+            }
+        }
+        Ok(overrides)
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ConfigFieldChange {
+    pub field: String,
+    pub old: serde_json::Value,
+    pub new: serde_json::Value,
+}
+
+/// Lists top-level fields that differ between two configs, masking secret values.
+pub fn diff_configs(old: &AppConfig, new: &AppConfig) -> Vec<ConfigFieldChange> {
+    let old = serde_json::to_value(old).unwrap_or_default();
+    let new = serde_json::to_value(new).unwrap_or_default();
+    let (Some(old), Some(new)) = (old.as_object(), new.as_object()) else {
+        return Vec::new();
+    };
+    let mask = |field: &str, value: &serde_json::Value| {
+        if SECRET_CONFIG_FIELDS.contains(&field) && !value.is_null() {
+            serde_json::Value::String("********".to_string())
+        } else {
+            value.clone()
+        }
+    };
+    new.iter()
+        .filter(|(field, value)| old.get(field.as_str()) != Some(value))
+        .map(|(field, value)| ConfigFieldChange {
+            field: field.clone(),
+            old: mask(field, old.get(field.as_str()).unwrap_or(&serde_json::Value::Null)),
+            new: mask(field, value),
+        })
+        .collect()
+}
+
+/// Returns the application data directory, creating it if needed.
+pub fn get_app_data_dir(app_handle: &tauri::AppHandle) -> Result<PathBuf, String> {
     // Use the new Tauri 2.0 API
-    let config_dir = app_handle
+    let data_dir = app_handle
         .path()
         .app_data_dir()
         .map_err(|e| format!("Failed to get app data directory: {}", e))?;
-    
-    if !config_dir.exists() {
-        fs::create_dir_all(&config_dir)?;
+
+    if !data_dir.exists() {
+        fs::create_dir_all(&data_dir)
+            .map_err(|e| format!("Failed to create app data directory: {}", e))?;
     }
-    
-    Ok(config_dir.join("app_settings.json"))
+
+    Ok(data_dir)
 }
 
 #[tauri::command]
@@ -66,62 +829,91 @@ pub fn get_app_settings(
     app_handle: tauri::AppHandle, 
     state: tauri::State<'_, AppConfigState>
 ) -> Result<AppConfig, String> {
-    let path = get_config_path(&app_handle)
-        .map_err(|e| format!("Failed to get config path: {}", e))?;
-    
-    if path.exists() {
-        log::info!("Loading settings from: {:?}", path);
-        let content = fs::read_to_string(&path)
-            .map_err(|e| format!("Failed to read settings file: {}", e))?;
-        
-        match serde_json::from_str(&content) {
-            Ok(loaded_config) => {
-                let mut app_config_state = state.0.lock()
-                    .map_err(|e| format!("Failed to lock config state: {}", e))?;
-                *app_config_state = loaded_config;
-                Ok(app_config_state.clone())
-            }
-            Err(e) => {
-                log::error!("Failed to parse settings JSON, using default from state: {}", e);
-                let app_config_state = state.0.lock()
-                    .map_err(|e| format!("Failed to lock config state: {}", e))?;
-                Ok(app_config_state.clone())
-            }
-        }
-    } else {
-        log::info!("Settings file not found at {:?}, returning default from state.", path);
-        let app_config_state = state.0.lock()
+    let data_dir = get_app_data_dir(&app_handle)?;
+    // A layer that cannot be read is an error, not a reason to run the lane on defaults.
+    let layered = LayeredConfig::load(&data_dir)?;
+    log::info!("Loaded settings (site: {:?}, lane: {:?})", layered.site_path, layered.lane_path);
+    for problem in layered.config.validate() {
+        log::warn!("Loaded settings: {} ({:?}): {}", problem.field, problem.severity, problem.message);
+    }
+
+    let lane_file_missing = !layered.lane_path.exists();
+    let loaded_config = layered.config;
+    {
+        let mut app_config_state = state.0.lock()
             .map_err(|e| format!("Failed to lock config state: {}", e))?;
-        
-        // Optionally save the default config here if it doesn't exist
-        let default_config = app_config_state.clone();
-        drop(app_config_state); // Release the lock before calling save
-        
-        // Save default config for next time
-        match save_app_settings(app_handle, default_config.clone(), state) {
+        *app_config_state = loaded_config.clone();
+    }
+
+    if lane_file_missing {
+        log::info!("Lane settings file not found, creating it.");
+        match write_app_settings(&app_handle, loaded_config.clone(), &state) {
             Ok(_) => log::info!("Default config saved successfully"),
             Err(e) => log::warn!("Failed to save default config: {}", e),
         }
-        
-        Ok(default_config)
     }
+
+    Ok(loaded_config)
+}
+
+/// Shows every setting's effective value and the layer it came from.
+#[tauri::command]
+pub fn get_effective_config_command(app_handle: tauri::AppHandle) -> Result<EffectiveConfigReport, String> {
+    let data_dir = get_app_data_dir(&app_handle)?;
+    Ok(LayeredConfig::load(&data_dir)?.report())
 }
 
+/// Saves settings from the settings screen. Requires a supervisor session.
 #[tauri::command]
-pub fn save_app_settings(
+pub async fn save_app_settings(
     app_handle: tauri::AppHandle, 
     settings: AppConfig, 
-    state: tauri::State<'_, AppConfigState>
+    state: tauri::State<'_, AppConfigState>,
+    auth_state: tauri::State<'_, AuthState>,
+    audit_state: tauri::State<'_, AuditLogState>,
+    session_token: String,
 ) -> Result<(), String> {
-    let path = get_config_path(&app_handle)
-        .map_err(|e| format!("Failed to get config path: {}", e))?;
-    
-    log::info!("Saving settings to: {:?}", path);
+    let session = auth_handler::require_role(&auth_state, &state, &session_token, Role::Supervisor)?;
+    log::info!("Settings change requested by {}", session.username);
+    if let Some(message) = blocking_errors(&settings.validate()) {
+        log::warn!("Rejected settings from {}: {}", session.username, message);
+        return Err(message);
+    }
+    let previous = state.0.lock()
+        .map_err(|e| format!("Failed to lock config state: {}", e))?
+        .clone();
+    let changes = diff_configs(&previous, &settings);
+    let gate_name = settings.gate_name.clone();
+    write_app_settings(&app_handle, settings.clone(), &state)?;
+    // Running subsystems pick up the new values here; a failure restores `previous`.
+    config_reload_handler::reconfigure(&app_handle, &previous, &settings, "settings").await?;
+    audit_state.record(AuditKind::ConfigChange, &session.username, &gate_name, serde_json::json!({ "changes": changes }));
+    Ok(())
+}
+
+/// Returns per-field problems so the settings form can highlight them before saving.
+#[tauri::command]
+pub fn validate_app_settings_command(settings: AppConfig) -> Vec<FieldError> {
+    settings.validate()
+}
+
+/// Persists `settings` as lane overrides and makes them the active configuration.
+pub fn write_app_settings(
+    app_handle: &tauri::AppHandle,
+    mut settings: AppConfig,
+    state: &tauri::State<'_, AppConfigState>,
+) -> Result<(), String> {
+    settings.config_version = CURRENT_CONFIG_VERSION;
+    let data_dir = get_app_data_dir(app_handle)?;
+    let layered = LayeredConfig::load(&data_dir)?;
+    let overrides = layered.lane_overrides(&settings)?;
+
+    log::info!("Saving {} lane setting(s) to: {:?}", overrides.len(), layered.lane_path);
     
-    let content = serde_json::to_string_pretty(&settings)
+    let content = serde_json::to_string_pretty(&overrides)
         .map_err(|e| format!("Failed to serialize settings: {}", e))?;
     
-    fs::write(&path, content)
+    fs::write(&layered.lane_path, content)
         .map_err(|e| format!("Failed to write settings file: {}", e))?;
     
     // Update the state
@@ -135,36 +927,165 @@ pub fn save_app_settings(
 
 // Helper function to initialize the config state
 pub fn initialize_config_state(app_handle: &tauri::AppHandle) -> AppConfigState {
-    let default_config = AppConfig::default();
-    
-    // Try to load existing config
-    let config_path = match get_config_path(app_handle) {
-        Ok(path) => path,
+    let loaded = get_app_data_dir(app_handle).and_then(|dir| LayeredConfig::load(&dir));
+    match loaded {
+        Ok(layered) => {
+            log::info!("Loaded existing config from: {:?}", layered.lane_path);
+            AppConfigState(Mutex::new(layered.config))
+        }
         Err(e) => {
-            log::error!("Failed to get config path during initialization: {}", e);
-            return AppConfigState(Mutex::new(default_config));
+            log::error!("Failed to load existing config: {}", e);
+            log::info!("Using default config");
+            AppConfigState(Mutex::new(AppConfig::default()))
         }
-    };
-    
-    if config_path.exists() {
-        match fs::read_to_string(&config_path) {
-            Ok(content) => {
-                match serde_json::from_str::<AppConfig>(&content) {
-                    Ok(loaded_config) => {
-                        log::info!("Loaded existing config from: {:?}", config_path);
-                        return AppConfigState(Mutex::new(loaded_config));
-                    }
-                    Err(e) => {
-                        log::error!("Failed to parse existing config: {}", e);
-                    }
-                }
-            }
-            Err(e) => {
-                log::error!("Failed to read existing config: {}", e);
-            }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn migrated(mut value: serde_json::Value) -> (u32, serde_json::Value) {
+        let from_version = migrate_config_value(&mut value).unwrap();
+        (from_version, value)
+    }
+
+    fn site(toml: &str) -> toml::Table {
+        toml.parse().unwrap()
+    }
+
+    fn lane(value: serde_json::Value) -> serde_json::Map<String, serde_json::Value> {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn documents_migrate_to_the_current_version() {
+        let readers = |port: &str, baud_rate: u32| json!([{ "id": "main", "port": port, "baud_rate": baud_rate }]);
+        let cases = [
+            (
+                json!({ "gate_name": "G1", "emoney_reader_port": "COM3", "emoney_baud_rate": 9600 }),
+                1,
+                json!({ "gate_name": "G1", "soap_auth_mode": "legacy", "emoney_readers": readers("COM3", 9600), "config_version": 3 }),
+            ),
+            (
+                json!({ "config_version": 1, "soap_auth_mode": "ws_security_text" }),
+                1,
+                json!({ "soap_auth_mode": "ws_security_text", "config_version": 3 }),
+            ),
+            (
+                json!({ "config_version": 2, "emoney_reader_port": "COM4" }),
+                2,
+                json!({ "emoney_readers": readers("COM4", 38400), "config_version": 3 }),
+            ),
+            (
+                json!({ "config_version": 3, "emoney_readers": readers("COM5", 115200) }),
+                3,
+                json!({ "emoney_readers": readers("COM5", 115200), "config_version": 3 }),
+            ),
+        ];
+        for (input, from_version, expected) in cases {
+            assert_eq!(migrated(input.clone()), (from_version, expected), "migrating {}", input);
         }
     }
-    
-    log::info!("Using default config");
-    AppConfigState(Mutex::new(default_config))
-}
\ No newline at end of file
+
+    #[test]
+    fn unsupported_versions_are_refused() {
+        for input in [json!({ "config_version": 0 }), json!({ "config_version": 4 }), json!({ "config_version": "3" }), json!([])] {
+            assert!(migrate_config_value(&mut input.clone()).is_err(), "accepted {}", input);
+        }
+    }
+
+    #[test]
+    fn migrated_v1_document_loads_as_a_lane_layer() {
+        let (_, value) = migrated(json!({ "gate_name": "G1", "emoney_reader_port": "COM3", "emoney_baud_rate": 9600 }));
+        let config = merge_layers(&toml::Table::new(), Some(&lane(value)), false).unwrap();
+        assert_eq!(config.soap_auth_mode, SoapAuthMode::Legacy);
+        assert_eq!(config.emoney_readers.len(), 1);
+        assert_eq!((config.emoney_readers[0].port.as_str(), config.emoney_readers[0].baud_rate), ("COM3", 9600));
+    }
+
+    #[test]
+    fn legacy_site_keys_are_read_as_readers() {
+        let mut legacy = site("emoney_reader_port = \"COM7\"\ngate_name = \"SITE\"");
+        migrate_site_layer(&mut legacy, Path::new("site.toml")).unwrap();
+        assert!(!legacy.contains_key("emoney_reader_port"));
+        let config = merge_layers(&legacy, None, false).unwrap();
+        assert_eq!(config.emoney_readers[0].port, "COM7");
+        assert_eq!(config.emoney_readers[0].baud_rate, 38400);
+
+        let mut both = site("emoney_reader_port = \"COM7\"\n[[emoney_readers]]\nid = \"a\"\nport = \"COM8\"\nbaud_rate = 9600");
+        assert!(migrate_site_layer(&mut both, Path::new("site.toml")).is_err());
+    }
+
+    #[test]
+    fn each_layer_overrides_the_one_below() {
+        let site = site("gate_name = \"SITE\"\ngate_type = 1\ncard_debounce_ms = 500");
+        let lane = lane(json!({ "gate_name": "LANE", "card_debounce_ms": 750 }));
+        let defaults = AppConfig::default();
+
+        let site_only = merge_layers(&site, None, false).unwrap();
+        assert_eq!((site_only.gate_name.as_str(), site_only.gate_type, site_only.card_debounce_ms), ("SITE", 1, 500));
+
+        let merged = merge_layers(&site, Some(&lane), false).unwrap();
+        assert_eq!((merged.gate_name.as_str(), merged.gate_type, merged.card_debounce_ms), ("LANE", 1, 750));
+        assert_eq!(merged.cacm_tool_url, defaults.cacm_tool_url);
+
+        let layered = LayeredConfig {
+            config: merged,
+            site_path: PathBuf::from("site.toml"),
+            lane_path: PathBuf::from("app_settings.json"),
+            site,
+            lane,
+        };
+        assert_eq!(layered.source_of("gate_name"), ConfigLayer::Lane);
+        assert_eq!(layered.source_of("gate_type"), ConfigLayer::Site);
+        assert_eq!(layered.source_of("device_gateway_url"), ConfigLayer::Default);
+    }
+
+    #[test]
+    fn environment_overrides_every_file_layer() {
+        // The only test that sets a CACM_* variable, so parallel tests are unaffected.
+        let site = site("cgs_gateway_url = \"http://site.example/cgs\"");
+        let lane = lane(json!({ "cgs_gateway_url": "http://lane.example/cgs" }));
+        std::env::set_var(env_var_name("cgs_gateway_url"), "http://env.example/cgs");
+        let merged = merge_layers(&site, Some(&lane), true);
+        std::env::remove_var(env_var_name("cgs_gateway_url"));
+        assert_eq!(merged.unwrap().cgs_gateway_url, "http://env.example/cgs");
+    }
+
+    #[test]
+    fn lane_file_stores_only_what_differs_from_defaults_and_site() {
+        let site = site("gate_name = \"SITE\"\ngate_type = 1");
+        let config = merge_layers(&site, None, false).unwrap();
+        let layered = LayeredConfig {
+            config: config.clone(),
+            site_path: PathBuf::from("site.toml"),
+            lane_path: PathBuf::from("app_settings.json"),
+            site,
+            lane: serde_json::Map::new(),
+        };
+        let settings = AppConfig { card_debounce_ms: config.card_debounce_ms + 1, ..config };
+
+        let overrides = layered.lane_overrides(&settings).unwrap();
+        let mut keys: Vec<&str> = overrides.keys().map(String::as_str).collect();
+        keys.sort_unstable();
+        assert_eq!(keys, ["card_debounce_ms", "config_version"]);
+    }
+
+    #[test]
+    fn validation_reports_each_bad_field() {
+        let config = AppConfig {
+            cacm_tool_url: "ftp://cacm".to_string(),
+            gate_name: "GATE 1".to_string(),
+            gate_type: 2,
+            emoney_readers: Vec::new(),
+            ..Default::default()
+        };
+        let errors = config.validate();
+        for field in ["cacm_tool_url", "gate_name", "gate_type", "emoney_readers"] {
+            assert!(errors.iter().any(|e| e.field == field && e.severity == FieldSeverity::Error), "no error for {}", field);
+        }
+        assert!(blocking_errors(&errors).is_some());
+    }
+}
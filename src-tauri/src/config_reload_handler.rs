@@ -0,0 +1,208 @@
+// src-tauri/src/config_reload_handler.rs
+use serde::Serialize;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+use tauri::{Emitter, Manager};
+
+use crate::audit_handler::{AuditKind, AuditLogState};
+use crate::anpr_handler::AnprState;
+use crate::config_handler::{self, AppConfig, AppConfigState, LayeredConfig};
+use crate::container_ocr_handler::OcrState;
+use crate::rfid_handler::RFIDManagerState;
+use crate::uhf_handler::UhfState;
+
+const FILE_WATCH_INTERVAL: Duration = Duration::from_secs(2);
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Parts of the lane that hold state derived from the configuration.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigSubsystem {
+    /// The e-money readers: each owns an open serial port.
+    Reader,
+    /// ADAM portal and button modules: reconnected per command, probed on change.
+    Adam,
+    /// Long-range UHF truck tag reader: holds an LLRP session.
+    Uhf,
+    /// ANPR cameras: the lane listens for their plate events.
+    Anpr,
+    /// Container OCR systems: the lane listens for their recognitions.
+    Ocr,
+    /// CGS, DeviceGateway and CaCMTool endpoints: read per request, nothing to restart.
+    Urls,
+}
+
+impl ConfigSubsystem {
+    fn fields(self) -> &'static [&'static str] {
+        match self {
+            ConfigSubsystem::Reader => &["emoney_readers", "emoney_init_key", "card_debounce_ms"],
+            ConfigSubsystem::Adam => &["adam_portal_ip", "adam_portal_port", "adam_button_ip", "adam_button_port"],
+            ConfigSubsystem::Uhf => &["uhf_enabled", "uhf_reader_ip", "uhf_reader_port", "uhf_antennas", "uhf_min_rssi", "uhf_duplicate_window_secs"],
+            ConfigSubsystem::Anpr => &["anpr_enabled", "anpr_listen_port", "anpr_cameras"],
+            ConfigSubsystem::Ocr => &["ocr_enabled", "ocr_listen_port", "ocr_sources"],
+            ConfigSubsystem::Urls => &["cgs_gateway_url", "device_gateway_url", "cacm_tool_url", "soap_auth_mode"],
+        }
+    }
+}
+
+const ALL_SUBSYSTEMS: &[ConfigSubsystem] = &[ConfigSubsystem::Reader, ConfigSubsystem::Adam, ConfigSubsystem::Uhf, ConfigSubsystem::Anpr, ConfigSubsystem::Ocr, ConfigSubsystem::Urls];
+
+#[derive(Serialize, Clone)]
+struct ConfigChangedPayload {
+    origin: String,
+    subsystems: Vec<ConfigSubsystem>,
+    fields: Vec<String>,
+}
+
+/// Subsystems affected by the difference between two configs.
+pub fn changed_subsystems(old: &AppConfig, new: &AppConfig) -> Vec<ConfigSubsystem> {
+    let changes = config_handler::diff_configs(old, new);
+    ALL_SUBSYSTEMS.iter()
+        .copied()
+        .filter(|subsystem| changes.iter().any(|c| subsystem.fields().contains(&c.field.as_str())))
+        .collect()
+}
+
+async fn probe_tcp(device: &str, ip: &str, port: u16) -> Result<(), String> {
+    let address = format!("{}:{}", ip, port);
+    match tokio::time::timeout(PROBE_TIMEOUT, tokio::net::TcpStream::connect(&address)).await {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err(e)) => Err(format!("{} at {} unreachable: {}", device, address, e)),
+        Err(_) => Err(format!("{} at {} did not answer within {:?}", device, address, PROBE_TIMEOUT)),
+    }
+}
+
+async fn reinitialize(app_handle: &tauri::AppHandle, subsystem: ConfigSubsystem, config: &AppConfig) -> Result<(), String> {
+    match subsystem {
+        ConfigSubsystem::Reader => {
+            let service = app_handle.state::<RFIDManagerState>().inner().clone();
+            service.restart_readers(config).await
+        }
+        ConfigSubsystem::Adam => {
+            probe_tcp("ADAM module", &config.adam_portal_ip, config.adam_portal_port).await?;
+            probe_tcp("ADAM module", &config.adam_button_ip, config.adam_button_port).await
+        }
+        ConfigSubsystem::Uhf => {
+            if config.uhf_enabled {
+                probe_tcp("UHF reader", &config.uhf_reader_ip, config.uhf_reader_port).await?;
+            }
+            app_handle.state::<UhfState>().request_reconnect();
+            Ok(())
+        }
+        ConfigSubsystem::Anpr => {
+            app_handle.state::<AnprState>().request_restart();
+            Ok(())
+        }
+        ConfigSubsystem::Ocr => {
+            app_handle.state::<OcrState>().request_restart();
+            Ok(())
+        }
+        ConfigSubsystem::Urls => Ok(()),
+    }
+}
+
+/// Re-initializes only the subsystems touched by `old` -> `new`. `new` must already be
+/// the active config. If any subsystem fails, the previous config is written back,
+/// made active again and every subsystem touched so far is restored.
+pub async fn reconfigure(
+    app_handle: &tauri::AppHandle,
+    old: &AppConfig,
+    new: &AppConfig,
+    origin: &str,
+) -> Result<Vec<ConfigSubsystem>, String> {
+    let subsystems = changed_subsystems(old, new);
+    let mut switched = Vec::new();
+    for &subsystem in &subsystems {
+        log::info!("CONFIG: Re-initializing {:?} after {} change", subsystem, origin);
+        if let Err(e) = reinitialize(app_handle, subsystem, new).await {
+            log::error!("CONFIG: Re-initializing {:?} failed, rolling back: {}", subsystem, e);
+            // The failed subsystem may have released its old resources too.
+            switched.push(subsystem);
+            rollback(app_handle, old, &switched).await;
+            return Err(format!("Settings not applied, {:?} failed to start with them: {}", subsystem, e));
+        }
+        switched.push(subsystem);
+    }
+
+    let payload = ConfigChangedPayload {
+        origin: origin.to_string(),
+        subsystems: subsystems.clone(),
+        fields: config_handler::diff_configs(old, new).into_iter().map(|c| c.field).collect(),
+    };
+    if let Err(e) = app_handle.emit("config_changed", &payload) {
+        log::error!("Failed to emit config_changed event: {}", e);
+    }
+    Ok(subsystems)
+}
+
+async fn rollback(app_handle: &tauri::AppHandle, old: &AppConfig, switched: &[ConfigSubsystem]) {
+    let config_state = app_handle.state::<AppConfigState>();
+    if let Err(e) = config_handler::write_app_settings(app_handle, old.clone(), &config_state) {
+        log::error!("CONFIG: Failed to restore previous settings: {}", e);
+    }
+    for &subsystem in switched {
+        if let Err(e) = reinitialize(app_handle, subsystem, old).await {
+            log::error!("CONFIG: Failed to restore {:?} with previous settings: {}", subsystem, e);
+        }
+    }
+}
+
+fn modified_times(paths: &[PathBuf]) -> Vec<Option<SystemTime>> {
+    paths.iter()
+        .map(|path| fs::metadata(path).and_then(|m| m.modified()).ok())
+        .collect()
+}
+
+/// Applies a config that changed on disk. Invalid edits are ignored (and logged) so
+/// a half-saved file never takes the lane down.
+async fn apply_external_change(app_handle: &tauri::AppHandle, data_dir: &std::path::Path) -> Result<(), String> {
+    let loaded = LayeredConfig::load(data_dir)?.config;
+    if let Some(message) = config_handler::blocking_errors(&loaded.validate()) {
+        return Err(format!("Ignoring external settings edit: {}", message));
+    }
+
+    let config_state = app_handle.state::<AppConfigState>();
+    let previous = config_state.0.lock().map_err(|_| "Failed to acquire config lock")?.clone();
+    let changes = config_handler::diff_configs(&previous, &loaded);
+    if changes.is_empty() {
+        return Ok(());
+    }
+
+    log::info!("CONFIG: Settings changed on disk: {:?}", changes.iter().map(|c| &c.field).collect::<Vec<_>>());
+    *config_state.0.lock().map_err(|_| "Failed to acquire config lock")? = loaded.clone();
+    reconfigure(app_handle, &previous, &loaded, "file").await?;
+    app_handle.state::<AuditLogState>().record(
+        AuditKind::ConfigChange,
+        "file",
+        &loaded.gate_name,
+        serde_json::json!({ "changes": changes }),
+    );
+    Ok(())
+}
+
+/// Polls the site and lane files for edits made outside the app.
+pub fn spawn_config_file_watcher(app_handle: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let data_dir = match config_handler::get_app_data_dir(&app_handle) {
+            Ok(dir) => dir,
+            Err(e) => {
+                log::error!("CONFIG: File watcher not started: {}", e);
+                return;
+            }
+        };
+        let paths = vec![config_handler::site_config_path(&data_dir), data_dir.join("app_settings.json")];
+        let mut last_seen = modified_times(&paths);
+        loop {
+            tokio::time::sleep(FILE_WATCH_INTERVAL).await;
+            if modified_times(&paths) == last_seen {
+                continue;
+            }
+            if let Err(e) = apply_external_change(&app_handle, &data_dir).await {
+                log::error!("CONFIG: {}", e);
+            }
+            // Our own rollback may have rewritten the lane file; don't treat that as an edit.
+            last_seen = modified_times(&paths);
+        }
+    });
+}
@@ -0,0 +1,485 @@
+// src-tauri/src/container_ocr_handler.rs
+// Gate container OCR. OCR systems report the container numbers and ISO size-type
+// codes they recognize, over HTTP or as plain TCP lines. Numbers are verified with
+// their ISO 6346 check digit and compared with the gate passes before TruckInOut
+// is sent, and with the ISO codes in the CMS data once CGS returns it.
+// `examples/container_ocr_standin.rs` sends recognitions for trying this without an OCR system.
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{Emitter, Manager};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::sync::Notify;
+
+use crate::anpr_handler;
+use crate::config_handler::AppConfig;
+use crate::soap_services_handler::CMSData;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+/// Recognitions older than this belong to an earlier truck.
+const READ_VALIDITY: Duration = Duration::from_secs(180);
+
+/// A container number and ISO code as an OCR system recognized them.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ContainerRead {
+    /// Owner code, category, serial and check digit without spaces, e.g. `CSQU3054383`.
+    pub number: String,
+    pub iso_code: Option<String>,
+    pub check_digit_valid: bool,
+    /// Address of the OCR system that sent it.
+    pub source: String,
+    pub timestamp: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ContainerCheckOutcome {
+    Match,
+    Mismatch,
+    /// Nothing was recognized for this truck.
+    NoRead,
+    Disabled,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ContainerIssueKind {
+    /// The recognized number fails its ISO 6346 check digit: a misread or a bad number.
+    InvalidCheckDigit,
+    /// Recognized on the truck but on none of the gate passes.
+    NotOnGatePass,
+    /// On a gate pass but not recognized on the truck.
+    NotRecognized,
+    /// The ISO code on the container differs from `cntrIsocode`.
+    IsoCodeMismatch,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ContainerIssue {
+    pub container: String,
+    pub kind: ContainerIssueKind,
+    pub detail: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ContainerCheck {
+    pub outcome: ContainerCheckOutcome,
+    pub recognized: Vec<ContainerRead>,
+    pub issues: Vec<ContainerIssue>,
+}
+
+impl ContainerCheck {
+    fn disabled() -> Self {
+        ContainerCheck { outcome: ContainerCheckOutcome::Disabled, recognized: Vec::new(), issues: Vec::new() }
+    }
+
+    fn summary(&self) -> String {
+        self.issues.iter().map(|issue| issue.detail.as_str()).collect::<Vec<_>>().join("; ")
+    }
+}
+
+#[derive(Default)]
+pub struct OcrState {
+    reads: Mutex<Vec<(ContainerRead, Instant)>>,
+    restart: Notify,
+}
+
+impl OcrState {
+    /// Containers recognized on the truck in the lane.
+    pub fn current_reads(&self) -> Vec<ContainerRead> {
+        self.reads.lock()
+            .map(|reads| reads.iter()
+                .filter(|(_, seen)| seen.elapsed() <= READ_VALIDITY)
+                .map(|(read, _)| read.clone())
+                .collect())
+            .unwrap_or_default()
+    }
+
+    /// Forgets the recognitions once the truck they belong to has gone through.
+    pub fn clear(&self) {
+        if let Ok(mut reads) = self.reads.lock() {
+            reads.clear();
+        }
+    }
+
+    /// Closes the listener so it is opened again with the current settings.
+    pub fn request_restart(&self) {
+        self.restart.notify_one();
+    }
+
+    fn record(&self, app_handle: &tauri::AppHandle, read: ContainerRead) {
+        log::info!(
+            "OCR: Container {} ISO {:?} from {}{}",
+            read.number,
+            read.iso_code,
+            read.source,
+            if read.check_digit_valid { "" } else { " (check digit invalid)" },
+        );
+        if let Ok(mut reads) = self.reads.lock() {
+            reads.retain(|(previous, seen)| seen.elapsed() <= READ_VALIDITY && previous.number != read.number);
+            reads.push((read.clone(), Instant::now()));
+        }
+        if let Err(e) = app_handle.emit("container_recognized", &read) {
+            log::error!("Failed to emit container_recognized event: {}", e);
+        }
+    }
+}
+
+/// ISO 6346 letter values: A is 10 and multiples of 11 are skipped.
+fn letter_value(letter: char) -> Option<u32> {
+    let mut value = 10;
+    for candidate in 'A'..='Z' {
+        if value % 11 == 0 {
+            value += 1;
+        }
+        if candidate == letter {
+            return Some(value);
+        }
+        value += 1;
+    }
+    None
+}
+
+fn compact(raw: &str) -> String {
+    raw.chars().filter(char::is_ascii_alphanumeric).map(|c| c.to_ascii_uppercase()).collect()
+}
+
+/// Whether `number` is shaped like a container number: three owner letters, the
+/// category U, J or Z, six serial digits and a check digit.
+fn is_container_number(number: &str) -> bool {
+    let bytes = number.as_bytes();
+    bytes.len() == 11
+        && bytes[..3].iter().all(u8::is_ascii_uppercase)
+        && matches!(bytes[3], b'U' | b'J' | b'Z')
+        && bytes[4..].iter().all(u8::is_ascii_digit)
+}
+
+/// The ISO 6346 check digit of the first ten characters of a container number.
+pub fn check_digit(number: &str) -> Option<u32> {
+    let sum = number.chars().take(10).enumerate().try_fold(0u32, |sum, (position, c)| {
+        let value = if position < 4 { letter_value(c)? } else { c.to_digit(10)? };
+        Some(sum + value * (1 << position))
+    })?;
+    Some(sum % 11 % 10)
+}
+
+/// The container number without spaces, if it is shaped like one, and whether its
+/// check digit is right.
+pub fn parse_container_number(raw: &str) -> Option<(String, bool)> {
+    let number = compact(raw);
+    if !is_container_number(&number) {
+        return None;
+    }
+    let valid = check_digit(&number) == number[10..].parse().ok();
+    Some((number, valid))
+}
+
+fn parse_iso_code(raw: &str) -> Option<String> {
+    Some(compact(raw)).filter(|code| code.len() == 4)
+}
+
+#[derive(Deserialize)]
+struct Recognition {
+    #[serde(alias = "containerNumber", alias = "cntrNumber", alias = "number")]
+    container: String,
+    #[serde(default, alias = "isoCode", alias = "cntrIsocode", alias = "iso")]
+    iso_code: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RecognitionPayload {
+    Batch { containers: Vec<Recognition> },
+    Single(Recognition),
+}
+
+/// One recognition per line: the container number, optionally followed by the ISO
+/// code, with any separators, e.g. `CSQU 305438 3;45G1`.
+fn parse_line(line: &str) -> Option<Recognition> {
+    let text = compact(line);
+    match text.len() {
+        11 => Some(Recognition { container: text, iso_code: None }),
+        15 => Some(Recognition { container: text[..11].to_string(), iso_code: Some(text[11..].to_string()) }),
+        _ => None,
+    }
+}
+
+fn parse_recognitions(body: &str) -> Vec<Recognition> {
+    match serde_json::from_str::<RecognitionPayload>(body) {
+        Ok(RecognitionPayload::Batch { containers }) => containers,
+        Ok(RecognitionPayload::Single(recognition)) => vec![recognition],
+        Err(_) => body.lines().filter(|line| !line.trim().is_empty()).filter_map(parse_line).collect(),
+    }
+}
+
+fn container_read(recognition: &Recognition, source: IpAddr) -> Result<ContainerRead, String> {
+    let (number, check_digit_valid) = parse_container_number(&recognition.container)
+        .ok_or_else(|| format!("'{}' from {} is not a container number", recognition.container, source))?;
+    Ok(ContainerRead {
+        number,
+        iso_code: recognition.iso_code.as_deref().and_then(parse_iso_code),
+        check_digit_valid,
+        source: source.to_string(),
+        timestamp: chrono::Local::now().to_rfc3339(),
+    })
+}
+
+/// Records what a recognition message carries; returns how many containers it held.
+fn record_recognitions(app_handle: &tauri::AppHandle, source: IpAddr, recognitions: &[Recognition]) -> usize {
+    let ocr_state = app_handle.state::<OcrState>();
+    recognitions.iter()
+        .filter_map(|recognition| container_read(recognition, source)
+            .map_err(|e| log::warn!("OCR: {}", e))
+            .ok())
+        .map(|read| ocr_state.record(app_handle, read))
+        .count()
+}
+
+async fn handle_http(app_handle: &tauri::AppHandle, source: IpAddr, mut stream: TcpStream) -> Result<(), String> {
+    let body = tokio::time::timeout(REQUEST_TIMEOUT, anpr_handler::read_http_body(&mut stream)).await
+        .map_err(|_| format!("{} did not send its recognition in time", source))??;
+    let recorded = record_recognitions(app_handle, source, &parse_recognitions(&body));
+    let status = if recorded > 0 { "200 OK" } else { "400 Bad Request" };
+    let _ = stream.write_all(format!("HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status).as_bytes()).await;
+    Ok(())
+}
+
+/// Plain TCP: the OCR system keeps the connection and writes one recognition per line.
+async fn handle_lines(app_handle: &tauri::AppHandle, source: IpAddr, stream: TcpStream) -> Result<(), String> {
+    let mut lines = BufReader::new(stream).lines();
+    while let Some(line) = lines.next_line().await.map_err(|e| format!("Connection from {} failed: {}", source, e))? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match parse_line(&line) {
+            Some(recognition) => {
+                record_recognitions(app_handle, source, &[recognition]);
+            }
+            None => log::warn!("OCR: Ignoring line '{}' from {}", line.trim(), source),
+        }
+    }
+    Ok(())
+}
+
+async fn handle_connection(app_handle: &tauri::AppHandle, source: IpAddr, stream: TcpStream) -> Result<(), String> {
+    let mut start = [0u8; 5];
+    let peeked = tokio::time::timeout(REQUEST_TIMEOUT, stream.peek(&mut start)).await
+        .map_err(|_| format!("{} connected but sent nothing", source))?
+        .map_err(|e| format!("Connection from {} failed: {}", source, e))?;
+    let start = &start[..peeked];
+    if start.starts_with(b"POST ") || start.starts_with(b"PUT ") {
+        handle_http(app_handle, source, stream).await
+    } else {
+        handle_lines(app_handle, source, stream).await
+    }
+}
+
+/// Accepts recognitions until the listener fails or a restart is requested.
+async fn run_listener(app_handle: tauri::AppHandle, config: AppConfig) -> Result<(), String> {
+    let ocr_state = app_handle.state::<OcrState>();
+    anpr_handler::accept_connections("OCR", "OCR system", config.ocr_listen_port, &ocr_state.restart, |stream, peer| {
+        let known = config.ocr_sources.iter().any(|source| source.parse::<IpAddr>().is_ok_and(|ip| ip == peer.ip()));
+        if !known {
+            return None;
+        }
+        let app_handle = app_handle.clone();
+        Some(async move { handle_connection(&app_handle, peer.ip(), stream).await })
+    }).await
+}
+
+pub fn spawn_container_ocr_listener(app_handle: tauri::AppHandle) {
+    anpr_handler::spawn_listener(app_handle, "OCR", |config| config.ocr_enabled, run_listener);
+}
+
+/// Compares the recognized containers with the gate passes that are container numbers.
+/// Gate passes of other shapes, e.g. booking references, are not compared.
+pub fn compare_with_gate_passes(recognized: Vec<ContainerRead>, gate_passes: &[String]) -> ContainerCheck {
+    if recognized.is_empty() {
+        return ContainerCheck { outcome: ContainerCheckOutcome::NoRead, recognized, issues: Vec::new() };
+    }
+    let mut issues: Vec<ContainerIssue> = recognized.iter()
+        .filter(|read| !read.check_digit_valid)
+        .map(|read| ContainerIssue {
+            container: read.number.clone(),
+            kind: ContainerIssueKind::InvalidCheckDigit,
+            detail: format!("{} fails its check digit", read.number),
+        })
+        .collect();
+    let expected: Vec<String> = gate_passes.iter()
+        .filter_map(|gate_pass| parse_container_number(gate_pass).map(|(number, _)| number))
+        .collect();
+    if !expected.is_empty() {
+        issues.extend(expected.iter()
+            .filter(|number| !recognized.iter().any(|read| read.number == **number))
+            .map(|number| ContainerIssue {
+                container: number.clone(),
+                kind: ContainerIssueKind::NotRecognized,
+                detail: format!("{} is on the gate pass but was not recognized on the truck", number),
+            }));
+        issues.extend(recognized.iter()
+            .filter(|read| read.check_digit_valid && !expected.contains(&read.number))
+            .map(|read| ContainerIssue {
+                container: read.number.clone(),
+                kind: ContainerIssueKind::NotOnGatePass,
+                detail: format!("{} is on the truck but on no gate pass", read.number),
+            }));
+    }
+    let outcome = if issues.is_empty() { ContainerCheckOutcome::Match } else { ContainerCheckOutcome::Mismatch };
+    ContainerCheck { outcome, recognized, issues }
+}
+
+/// Adds an issue for every container whose recognized ISO code differs from the
+/// `cntrIsocode` CGS returned for it. Returns whether any was found.
+pub fn compare_iso_codes(check: &mut ContainerCheck, cms_items: &[CMSData]) -> bool {
+    let mut found = false;
+    for item in cms_items {
+        let Some((number, _)) = item.cntr_number.as_deref().and_then(parse_container_number) else {
+            continue;
+        };
+        let Some(expected) = item.cntr_isocode.as_deref().and_then(parse_iso_code) else {
+            continue;
+        };
+        let recognized = check.recognized.iter()
+            .find(|read| read.number == number)
+            .and_then(|read| read.iso_code.clone());
+        if let Some(recognized) = recognized.filter(|code| *code != expected) {
+            check.issues.push(ContainerIssue {
+                container: number.clone(),
+                detail: format!("{} carries ISO code {}, CMS has {}", number, recognized, expected),
+                kind: ContainerIssueKind::IsoCodeMismatch,
+            });
+            found = true;
+        }
+    }
+    if found {
+        check.outcome = ContainerCheckOutcome::Mismatch;
+    }
+    found
+}
+
+fn alert(app_handle: &tauri::AppHandle, check: &ContainerCheck) {
+    log::warn!("OCR: Container mismatch: {}", check.summary());
+    if let Err(e) = app_handle.emit("container_mismatch", check) {
+        log::error!("Failed to emit container_mismatch event: {}", e);
+    }
+}
+
+/// Checks the containers on the truck against the gate passes before TruckInOut is
+/// sent. A mismatch alerts the operator and, if the lane is set to, refuses gate-in.
+pub fn verify_before_gate_in(app_handle: &tauri::AppHandle, config: &AppConfig, gate_passes: &[String]) -> Result<ContainerCheck, String> {
+    if !config.ocr_enabled {
+        return Ok(ContainerCheck::disabled());
+    }
+    let check = compare_with_gate_passes(app_handle.state::<OcrState>().current_reads(), gate_passes);
+    if check.outcome == ContainerCheckOutcome::Mismatch {
+        alert(app_handle, &check);
+        if config.ocr_block_on_mismatch {
+            return Err(format!("Containers do not match the gate passes: {}", check.summary()));
+        }
+    }
+    Ok(check)
+}
+
+/// Checks the recognized ISO codes against the CMS data CGS returned for the truck.
+pub fn verify_cms_items(app_handle: &tauri::AppHandle, check: &mut ContainerCheck, cms_items: &[CMSData]) {
+    if check.outcome != ContainerCheckOutcome::Disabled && compare_iso_codes(check, cms_items) {
+        alert(app_handle, check);
+    }
+}
+
+#[tauri::command]
+pub fn get_recognized_containers_command(ocr_state: tauri::State<'_, OcrState>) -> Vec<ContainerRead> {
+    ocr_state.current_reads()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn letter_values_skip_multiples_of_eleven() {
+        assert_eq!(letter_value('A'), Some(10));
+        assert_eq!(letter_value('B'), Some(12));
+        assert_eq!(letter_value('K'), Some(21));
+        assert_eq!(letter_value('L'), Some(23));
+        assert_eq!(letter_value('U'), Some(32));
+        assert_eq!(letter_value('V'), Some(34));
+        assert_eq!(letter_value('Z'), Some(38));
+        assert_eq!(letter_value('1'), None);
+    }
+
+    #[test]
+    fn check_digit_of_known_containers() {
+        assert_eq!(check_digit("CSQU3054383"), Some(3));
+        assert_eq!(check_digit("MSKU9070323"), Some(3));
+    }
+
+    #[test]
+    fn wrong_check_digit_is_flagged() {
+        assert_eq!(parse_container_number("CSQU3054383"), Some(("CSQU3054383".to_string(), true)));
+        assert_eq!(parse_container_number("csqu 305438 3"), Some(("CSQU3054383".to_string(), true)));
+        assert_eq!(parse_container_number("CSQU3054384"), Some(("CSQU3054384".to_string(), false)));
+        assert_eq!(parse_container_number("CSQU3054382"), Some(("CSQU3054382".to_string(), false)));
+    }
+
+    #[test]
+    fn non_container_numbers_are_not_parsed() {
+        assert_eq!(parse_container_number("CSQA3054383"), None);
+        assert_eq!(parse_container_number("CSQU305438"), None);
+        assert_eq!(parse_container_number("GP-12345"), None);
+    }
+
+    fn read(number: &str) -> ContainerRead {
+        let (number, check_digit_valid) = parse_container_number(number).unwrap();
+        ContainerRead { number, iso_code: None, check_digit_valid, source: "10.0.0.5".into(), timestamp: String::new() }
+    }
+
+    fn kinds(check: &ContainerCheck) -> Vec<ContainerIssueKind> {
+        check.issues.iter().map(|issue| issue.kind).collect()
+    }
+
+    #[test]
+    fn recognized_containers_on_the_gate_passes_match() {
+        let gate_passes = vec!["csqu 305438 3".to_string(), "BK-7781".to_string()];
+        let check = compare_with_gate_passes(vec![read("CSQU3054383")], &gate_passes);
+        assert_eq!(check.outcome, ContainerCheckOutcome::Match);
+        assert!(check.issues.is_empty());
+    }
+
+    #[test]
+    fn missing_extra_and_bad_check_digit_containers_mismatch() {
+        let gate_passes = vec!["CSQU3054383".to_string()];
+        let check = compare_with_gate_passes(vec![read("MSKU9070323"), read("CSQU3054384")], &gate_passes);
+        assert_eq!(check.outcome, ContainerCheckOutcome::Mismatch);
+        assert_eq!(kinds(&check), vec![ContainerIssueKind::InvalidCheckDigit, ContainerIssueKind::NotRecognized, ContainerIssueKind::NotOnGatePass]);
+        assert_eq!(check.issues[2].container, "MSKU9070323");
+    }
+
+    #[test]
+    fn gate_passes_without_container_numbers_only_check_digits() {
+        let gate_passes = vec!["BK-7781".to_string()];
+        assert_eq!(compare_with_gate_passes(vec![read("MSKU9070323")], &gate_passes).outcome, ContainerCheckOutcome::Match);
+        let check = compare_with_gate_passes(vec![read("CSQU3054384")], &gate_passes);
+        assert_eq!(kinds(&check), vec![ContainerIssueKind::InvalidCheckDigit]);
+        assert_eq!(compare_with_gate_passes(Vec::new(), &gate_passes).outcome, ContainerCheckOutcome::NoRead);
+    }
+
+    #[test]
+    fn recognitions_parse_from_json_and_lines() {
+        let single = parse_recognitions(r#"{"containerNumber":"CSQU3054383","isoCode":"45G1"}"#);
+        assert_eq!(single.len(), 1);
+        assert_eq!(single[0].container, "CSQU3054383");
+        assert_eq!(single[0].iso_code.as_deref(), Some("45G1"));
+
+        let batch = parse_recognitions(r#"{"containers":[{"cntrNumber":"CSQU3054383"},{"number":"MSKU9070323","iso":"22G1"}]}"#);
+        assert_eq!(batch.iter().map(|r| r.container.as_str()).collect::<Vec<_>>(), vec!["CSQU3054383", "MSKU9070323"]);
+        assert_eq!(batch[0].iso_code, None);
+
+        let lines = parse_recognitions("CSQU 305438 3;45G1\n\nnot a container\nMSKU9070323\n");
+        assert_eq!(lines.len(), 2);
+        assert_eq!((lines[0].container.as_str(), lines[0].iso_code.as_deref()), ("CSQU3054383", Some("45G1")));
+        assert_eq!((lines[1].container.as_str(), lines[1].iso_code.as_deref()), ("MSKU9070323", None));
+    }
+}
@@ -0,0 +1,252 @@
+// src-tauri/src/credential_handler.rs
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::{engine::general_purpose, Engine as _};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::State;
+
+use crate::auth_handler::{self, AuthState, Role};
+use crate::config_handler::AppConfigState;
+
+const KEY_FILE_NAME: &str = "credentials.key";
+const STORE_FILE_NAME: &str = "credentials.json";
+
+/// Username/password pair used to authenticate a gate against CGS.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GateCredential {
+    pub username: String,
+    pub password: String,
+}
+
+// Deliberately hand-written so passwords never end up in the logs.
+impl std::fmt::Debug for GateCredential {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GateCredential")
+            .field("username", &self.username)
+            .field("password", &"********")
+            .finish()
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct EncryptedEntry {
+    nonce: String,
+    ciphertext: String,
+    updated_at: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct CredentialStatus {
+    pub gate_name: String,
+    pub configured: bool,
+    pub username: Option<String>,
+    pub updated_at: Option<String>,
+}
+
+/// Secrets encrypted with AES-256-GCM under a machine-local key file.
+///
+/// The key lives next to the store in the app data directory and never leaves
+/// the lane PC; copying `credentials.json` alone to another machine is useless.
+pub struct CredentialStore {
+    dir: PathBuf,
+    entries: HashMap<String, EncryptedEntry>,
+}
+
+pub struct CredentialStoreState(pub Mutex<CredentialStore>);
+
+fn soap_credential_id(gate_name: &str) -> String {
+    format!("soap:{}", gate_name)
+}
+
+impl CredentialStore {
+    pub fn new(dir: &Path) -> Self {
+        let path = dir.join(STORE_FILE_NAME);
+        let entries = if path.exists() {
+            match fs::read_to_string(&path).map(|c| serde_json::from_str(&c)) {
+                Ok(Ok(entries)) => entries,
+                Ok(Err(e)) => {
+                    log::error!("Failed to parse credential store {:?}: {}", path, e);
+                    HashMap::new()
+                }
+                Err(e) => {
+                    log::error!("Failed to read credential store {:?}: {}", path, e);
+                    HashMap::new()
+                }
+            }
+        } else {
+            HashMap::new()
+        };
+        CredentialStore { dir: dir.to_path_buf(), entries }
+    }
+
+    fn cipher(&self) -> Result<Aes256Gcm, String> {
+        let key_path = self.dir.join(KEY_FILE_NAME);
+        let key_bytes = if key_path.exists() {
+            fs::read(&key_path).map_err(|e| format!("Failed to read credential key: {}", e))?
+        } else if !self.entries.is_empty() {
+            // A new key would leave every stored secret undecryptable.
+            return Err(format!(
+                "Credential key {:?} is missing but the store holds {} secret(s); restore the key file or remove {}",
+                key_path,
+                self.entries.len(),
+                STORE_FILE_NAME
+            ));
+        } else {
+            log::info!("Creating new credential key at {:?}", key_path);
+            let key = Aes256Gcm::generate_key(OsRng);
+            write_private_file(&key_path, key.as_slice())?;
+            key.to_vec()
+        };
+        if key_bytes.len() != 32 {
+            return Err(format!("Credential key {:?} is corrupt (expected 32 bytes)", key_path));
+        }
+        Ok(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key_bytes)))
+    }
+
+    fn persist(&self) -> Result<(), String> {
+        let content = serde_json::to_string_pretty(&self.entries)
+            .map_err(|e| format!("Failed to serialize credential store: {}", e))?;
+        write_private_file(&self.dir.join(STORE_FILE_NAME), content.as_bytes())
+    }
+
+    pub fn put<T: Serialize>(&mut self, id: &str, value: &T) -> Result<(), String> {
+        let cipher = self.cipher()?;
+        let plaintext = serde_json::to_vec(value)
+            .map_err(|e| format!("Failed to serialize secret '{}': {}", id, e))?;
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = cipher
+            .encrypt(&nonce, plaintext.as_slice())
+            .map_err(|_| format!("Failed to encrypt secret '{}'", id))?;
+        self.entries.insert(id.to_string(), EncryptedEntry {
+            nonce: general_purpose::STANDARD.encode(nonce),
+            ciphertext: general_purpose::STANDARD.encode(ciphertext),
+            updated_at: chrono::Local::now().to_rfc3339(),
+        });
+        self.persist()
+    }
+
+    pub fn get<T: DeserializeOwned>(&self, id: &str) -> Result<Option<T>, String> {
+        let Some(entry) = self.entries.get(id) else {
+            return Ok(None);
+        };
+        let cipher = self.cipher()?;
+        let nonce = general_purpose::STANDARD.decode(&entry.nonce)
+            .map_err(|e| format!("Corrupt nonce for secret '{}': {}", id, e))?;
+        if nonce.len() != 12 {
+            return Err(format!("Corrupt nonce for secret '{}'", id));
+        }
+        let ciphertext = general_purpose::STANDARD.decode(&entry.ciphertext)
+            .map_err(|e| format!("Corrupt ciphertext for secret '{}': {}", id, e))?;
+        let plaintext = cipher
+            .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+            .map_err(|_| format!("Failed to decrypt secret '{}' (wrong key or tampered store)", id))?;
+        serde_json::from_slice(&plaintext)
+            .map(Some)
+            .map_err(|e| format!("Failed to parse secret '{}': {}", id, e))
+    }
+
+    pub fn remove(&mut self, id: &str) -> Result<bool, String> {
+        let existed = self.entries.remove(id).is_some();
+        if existed {
+            self.persist()?;
+        }
+        Ok(existed)
+    }
+
+    pub fn updated_at(&self, id: &str) -> Option<String> {
+        self.entries.get(id).map(|e| e.updated_at.clone())
+    }
+
+    pub fn soap_credential(&self, gate_name: &str) -> Result<Option<GateCredential>, String> {
+        self.get(&soap_credential_id(gate_name))
+    }
+
+    pub fn set_soap_credential(&mut self, gate_name: &str, credential: &GateCredential) -> Result<(), String> {
+        self.put(&soap_credential_id(gate_name), credential)
+    }
+}
+
+fn write_private_file(path: &Path, content: &[u8]) -> Result<(), String> {
+    fs::write(path, content).map_err(|e| format!("Failed to write {:?}: {}", path, e))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600))
+            .map_err(|e| format!("Failed to restrict permissions on {:?}: {}", path, e))?;
+    }
+    Ok(())
+}
+
+fn resolve_gate_name(gate_name: Option<String>, config_state: &State<'_, AppConfigState>) -> Result<String, String> {
+    match gate_name {
+        Some(name) if !name.trim().is_empty() => Ok(name.trim().to_string()),
+        _ => Ok(config_state.0.lock()
+            .map_err(|_| "Failed to acquire config lock")?
+            .gate_name
+            .clone()),
+    }
+}
+
+#[tauri::command]
+pub fn set_soap_credentials_command(
+    config_state: State<'_, AppConfigState>,
+    credential_state: State<'_, CredentialStoreState>,
+    auth_state: State<'_, AuthState>,
+    session_token: String,
+    gate_name: Option<String>,
+    username: String,
+    password: String,
+) -> Result<String, String> {
+    auth_handler::require_role(&auth_state, &config_state, &session_token, Role::Supervisor)?;
+    if username.trim().is_empty() || password.is_empty() {
+        return Err("Username and password must not be empty".to_string());
+    }
+    let gate_name = resolve_gate_name(gate_name, &config_state)?;
+    let mut store = credential_state.0.lock()
+        .map_err(|_| "Failed to acquire credential store lock")?;
+    store.set_soap_credential(&gate_name, &GateCredential { username: username.trim().to_string(), password })?;
+    log::info!("SOAP credentials stored for gate {}", gate_name);
+    Ok(format!("SOAP credentials stored for gate {}", gate_name))
+}
+
+#[tauri::command]
+pub fn clear_soap_credentials_command(
+    config_state: State<'_, AppConfigState>,
+    credential_state: State<'_, CredentialStoreState>,
+    auth_state: State<'_, AuthState>,
+    session_token: String,
+    gate_name: Option<String>,
+) -> Result<String, String> {
+    auth_handler::require_role(&auth_state, &config_state, &session_token, Role::Supervisor)?;
+    let gate_name = resolve_gate_name(gate_name, &config_state)?;
+    let mut store = credential_state.0.lock()
+        .map_err(|_| "Failed to acquire credential store lock")?;
+    if store.remove(&soap_credential_id(&gate_name))? {
+        log::info!("SOAP credentials removed for gate {}", gate_name);
+        Ok(format!("SOAP credentials removed for gate {}", gate_name))
+    } else {
+        Ok(format!("No SOAP credentials were stored for gate {}", gate_name))
+    }
+}
+
+#[tauri::command]
+pub fn get_soap_credentials_status_command(
+    config_state: State<'_, AppConfigState>,
+    credential_state: State<'_, CredentialStoreState>,
+    gate_name: Option<String>,
+) -> Result<CredentialStatus, String> {
+    let gate_name = resolve_gate_name(gate_name, &config_state)?;
+    let store = credential_state.0.lock()
+        .map_err(|_| "Failed to acquire credential store lock")?;
+    let credential = store.soap_credential(&gate_name)?;
+    Ok(CredentialStatus {
+        configured: credential.is_some(),
+        username: credential.map(|c| c.username),
+        updated_at: store.updated_at(&soap_credential_id(&gate_name)),
+        gate_name,
+    })
+}
@@ -0,0 +1,152 @@
+// src-tauri/src/db_handler.rs
+use rusqlite::{params, Connection};
+use std::path::Path;
+use std::sync::Mutex;
+
+const DATABASE_FILE_NAME: &str = "checkpoint.db";
+
+/// Tables are created idempotently on every start; new tables are appended here.
+const SCHEMA: &str = r#"
+CREATE TABLE IF NOT EXISTS upload_queue (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    endpoint TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    uploaded_at TEXT
+);
+CREATE INDEX IF NOT EXISTS idx_upload_queue_pending ON upload_queue (uploaded_at, id);
+
+CREATE TABLE IF NOT EXISTS payment_journal (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    transaction_id TEXT NOT NULL,
+    gate_name TEXT NOT NULL,
+    reader_id TEXT NOT NULL,
+    card_type TEXT NOT NULL,
+    card_no TEXT NOT NULL,
+    amount INTEGER NOT NULL,
+    balance_after INTEGER NOT NULL,
+    mid TEXT NOT NULL,
+    tid TEXT NOT NULL,
+    transaction_counter INTEGER NOT NULL,
+    transaction_data TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    settled_at TEXT,
+    settlement_file TEXT
+);
+CREATE INDEX IF NOT EXISTS idx_payment_journal_settlement ON payment_journal (card_type, created_at);
+
+CREATE TABLE IF NOT EXISTS truck_visits (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    tag_number TEXT NOT NULL,
+    transaction_id TEXT NOT NULL,
+    gate_name TEXT NOT NULL,
+    truck_in_time TEXT NOT NULL,
+    truck_out_time TEXT
+);
+CREATE INDEX IF NOT EXISTS idx_truck_visits_open ON truck_visits (tag_number, truck_out_time);
+
+CREATE TABLE IF NOT EXISTS validation_cache (
+    kind TEXT NOT NULL,
+    key TEXT NOT NULL,
+    validated_at TEXT NOT NULL,
+    PRIMARY KEY (kind, key)
+);
+
+CREATE TABLE IF NOT EXISTS degraded_decisions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    operation TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    gate_name TEXT NOT NULL,
+    policy TEXT NOT NULL,
+    allowed INTEGER NOT NULL,
+    reason TEXT NOT NULL,
+    operation_xml TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    replay_attempts INTEGER NOT NULL DEFAULT 0,
+    replay_error TEXT,
+    replayed_at TEXT,
+    replay_accepted INTEGER
+);
+CREATE INDEX IF NOT EXISTS idx_degraded_decisions_pending ON degraded_decisions (allowed, replayed_at, id);
+
+CREATE TABLE IF NOT EXISTS blacklist (
+    kind TEXT NOT NULL,
+    value TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    source TEXT NOT NULL,
+    added_by TEXT,
+    added_at TEXT NOT NULL,
+    PRIMARY KEY (kind, value)
+);
+
+CREATE TABLE IF NOT EXISTS blacklist_sync (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    version INTEGER,
+    synced_at TEXT,
+    last_error TEXT
+);
+
+CREATE TABLE IF NOT EXISTS disputed_deductions (
+    transaction_id TEXT PRIMARY KEY,
+    reason TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    resolved_at TEXT
+);
+
+CREATE TABLE IF NOT EXISTS device_commands (
+    command_id TEXT PRIMARY KEY,
+    command TEXT NOT NULL,
+    started_at TEXT NOT NULL,
+    success INTEGER,
+    message TEXT,
+    executed_at TEXT,
+    acknowledged_at TEXT
+);
+"#;
+
+/// Columns added to tables after they first shipped, as (table, column, type).
+/// `CREATE TABLE IF NOT EXISTS` leaves existing tables alone, so these are added
+/// on start where missing.
+const ADDED_COLUMNS: &[(&str, &str, &str)] = &[
+    ("upload_queue", "failed_at", "TEXT"),
+];
+
+/// Local SQLite database shared by the queue, journal and cache tables.
+pub struct DatabaseState(pub Mutex<Connection>);
+
+pub fn open_database(dir: &Path) -> Result<DatabaseState, String> {
+    let path = dir.join(DATABASE_FILE_NAME);
+    log::info!("DB: Opening local database at {:?}", path);
+    let conn = Connection::open(&path)
+        .map_err(|e| format!("Failed to open database {:?}: {}", path, e))?;
+    conn.execute_batch("PRAGMA journal_mode = WAL; PRAGMA foreign_keys = ON;")
+        .map_err(|e| format!("Failed to configure database: {}", e))?;
+    initialize_schema(&conn)?;
+    Ok(DatabaseState(Mutex::new(conn)))
+}
+
+fn initialize_schema(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch(SCHEMA)
+        .map_err(|e| format!("Failed to initialize database schema: {}", e))?;
+    for (table, column, column_type) in ADDED_COLUMNS {
+        let exists = conn.prepare(&format!("SELECT 1 FROM pragma_table_info('{}') WHERE name = ?1", table))
+            .and_then(|mut stmt| stmt.exists(params![column]))
+            .map_err(|e| format!("Failed to inspect table {}: {}", table, e))?;
+        if !exists {
+            log::info!("DB: Adding column {}.{}", table, column);
+            conn.execute_batch(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, column_type))
+                .map_err(|e| format!("Failed to add column {}.{}: {}", table, column, e))?;
+        }
+    }
+    Ok(())
+}
+
+/// A fresh in-memory database with the full schema, for unit tests.
+#[cfg(test)]
+pub fn open_in_memory() -> DatabaseState {
+    let conn = Connection::open_in_memory().expect("in-memory database");
+    initialize_schema(&conn).expect("database schema");
+    DatabaseState(Mutex::new(conn))
+}
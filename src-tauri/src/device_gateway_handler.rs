@@ -0,0 +1,525 @@
+// src-tauri/src/device_gateway_handler.rs
+// DeviceGatewayService client. Every interval the lane reports the health of its
+// readers, ADAM modules, printer, scanner and network link together with the
+// software version, then pulls the device commands queued for the gate, runs
+// them and acknowledges each one. Executed command ids are kept in SQLite, so a
+// command is never run twice, even across restarts.
+use rusqlite::{params, OptionalExtension};
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{Manager, State};
+
+use crate::adam_handler;
+use crate::audit_handler::{AuditKind, AuditLogState};
+use crate::blacklist_handler;
+use crate::config_handler::{AppConfig, AppConfigState};
+use crate::credential_handler::CredentialStoreState;
+use crate::db_handler::DatabaseState;
+use crate::remote_config_handler;
+use crate::rfid_handler::{DeviceStatus, RFIDManagerState};
+use crate::soap_services_handler::{self, xml_escape, SoapError};
+use crate::uhf_handler::UhfState;
+
+const DEVICE_GATEWAY_NAMESPACE: &str = "http://halotec-indonesia.com/";
+const REPORT_DEVICE_STATUS: &str = "ReportDeviceStatus";
+const GET_PENDING_COMMANDS: &str = "GetPendingCommands";
+const ACKNOWLEDGE_COMMAND: &str = "AcknowledgeCommand";
+
+const RESTART_READERS: &str = "RESTART_READERS";
+const RECONNECT_UHF: &str = "RECONNECT_UHF";
+const PULL_CONFIG: &str = "PULL_CONFIG";
+const SYNC_BLACKLIST: &str = "SYNC_BLACKLIST";
+
+const DISABLED_RECHECK_INTERVAL: Duration = Duration::from_secs(60);
+const NETWORK_PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+/// How many executed commands the status view shows.
+const RECENT_COMMANDS_LIMIT: usize = 20;
+/// How long executed command ids are kept; the gateway stops re-sending long before.
+const COMMAND_HISTORY_DAYS: i64 = 30;
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    Ok,
+    Fault,
+    /// Nothing has exercised the device yet, e.g. no slip printed since start.
+    Unknown,
+}
+
+impl HealthStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            HealthStatus::Ok => "OK",
+            HealthStatus::Fault => "FAULT",
+            HealthStatus::Unknown => "UNKNOWN",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ComponentHealth {
+    /// `reader:<id>`, `adam_portal`, `adam_button`, `printer`, `scanner` or `network`.
+    pub device: String,
+    pub status: HealthStatus,
+    pub detail: Option<String>,
+    pub checked_at: String,
+}
+
+impl ComponentHealth {
+    fn new(device: &str, result: Result<String, String>) -> Self {
+        let (status, detail) = match result {
+            Ok(detail) => (HealthStatus::Ok, Some(detail)),
+            Err(e) => (HealthStatus::Fault, Some(e)),
+        };
+        ComponentHealth { device: device.to_string(), status, detail, checked_at: chrono::Local::now().to_rfc3339() }
+    }
+
+    fn unknown(device: &str) -> Self {
+        ComponentHealth { device: device.to_string(), status: HealthStatus::Unknown, detail: None, checked_at: chrono::Local::now().to_rfc3339() }
+    }
+}
+
+/// A command from the gateway and what came of it.
+#[derive(Debug, Serialize, Clone)]
+pub struct DeviceCommandOutcome {
+    pub command_id: String,
+    pub command: String,
+    pub success: bool,
+    pub message: String,
+    pub executed_at: String,
+    pub acknowledged: bool,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct DeviceGatewayStatus {
+    pub enabled: bool,
+    pub software_version: String,
+    pub last_reported: Option<String>,
+    pub last_error: Option<String>,
+    pub health: Vec<ComponentHealth>,
+    pub recent_commands: VecDeque<DeviceCommandOutcome>,
+}
+
+/// Health of devices that are only seen when they are used, plus the client's status.
+#[derive(Default)]
+pub struct DeviceGatewayState {
+    observed: Mutex<BTreeMap<String, ComponentHealth>>,
+    status: Mutex<DeviceGatewayStatus>,
+}
+
+impl DeviceGatewayState {
+    /// Records how the last use of `device` went, reported with the next heartbeat.
+    pub fn observe(&self, device: &str, result: Result<String, String>) {
+        if let Ok(mut observed) = self.observed.lock() {
+            observed.insert(device.to_string(), ComponentHealth::new(device, result));
+        }
+    }
+
+    fn observed(&self, device: &str) -> ComponentHealth {
+        self.observed.lock().ok()
+            .and_then(|observed| observed.get(device).cloned())
+            .unwrap_or_else(|| ComponentHealth::unknown(device))
+    }
+
+    fn record_command(&self, outcome: DeviceCommandOutcome) {
+        if let Ok(mut status) = self.status.lock() {
+            status.recent_commands.retain(|c| c.command_id != outcome.command_id);
+            status.recent_commands.push_front(outcome);
+            status.recent_commands.truncate(RECENT_COMMANDS_LIMIT);
+        }
+    }
+}
+
+async fn reader_health(app_handle: &tauri::AppHandle, config: &AppConfig) -> Vec<ComponentHealth> {
+    let status = app_handle.state::<RFIDManagerState>().status().await;
+    config.emoney_readers.iter().map(|reader| {
+        let device = format!("reader:{}", reader.id);
+        let result = match &status {
+            Ok(status) => match (status.readers.get(&reader.id), status.reader_status.get(&reader.id)) {
+                (Some(port), Some(DeviceStatus::Connected)) => Ok(format!("Connected on {}", port)),
+                (Some(port), _) => Err(format!("Disconnected from {}", port)),
+                (None, _) => Err("Not open".to_string()),
+            },
+            Err(e) => Err(e.clone()),
+        };
+        ComponentHealth::new(&device, result)
+    }).collect()
+}
+
+/// Whether the CGS host accepts a TCP connection.
+async fn network_health(config: &AppConfig) -> ComponentHealth {
+    let result = async {
+        let url = url::Url::parse(&config.cgs_gateway_url).map_err(|e| format!("Invalid CGS URL: {}", e))?;
+        let host = url.host_str().ok_or("CGS URL has no host")?.to_string();
+        let port = url.port_or_known_default().ok_or("CGS URL has no port")?;
+        tokio::time::timeout(NETWORK_PROBE_TIMEOUT, tokio::net::TcpStream::connect((host.as_str(), port)))
+            .await
+            .map_err(|_| format!("No answer from {}:{}", host, port))?
+            .map_err(|e| format!("Cannot reach {}:{}: {}", host, port, e))?;
+        Ok(format!("{}:{} reachable", host, port))
+    }.await;
+    ComponentHealth::new("network", result)
+}
+
+async fn collect_health(app_handle: &tauri::AppHandle, config: &AppConfig) -> Vec<ComponentHealth> {
+    let mut health = reader_health(app_handle, config).await;
+    let portal = adam_handler::probe(&config.adam_portal_ip, config.adam_portal_port).await;
+    health.push(ComponentHealth::new("adam_portal", portal.map(|_| "Connected".to_string())));
+    let button = adam_handler::probe(&config.adam_button_ip, config.adam_button_port).await;
+    health.push(ComponentHealth::new("adam_button", button.map(|_| "Connected".to_string())));
+    let gateway_state = app_handle.state::<DeviceGatewayState>();
+    health.push(gateway_state.observed("printer"));
+    health.push(gateway_state.observed("scanner"));
+    health.push(network_health(config).await);
+    health
+}
+
+fn status_xml(config: &AppConfig, software_version: &str, health: &[ComponentHealth]) -> String {
+    let devices: String = health.iter().map(|component| format!(
+        "<DeviceStatus><device>{}</device><status>{}</status><detail>{}</detail><checkedAt>{}</checkedAt></DeviceStatus>",
+        xml_escape(&component.device),
+        component.status.as_str(),
+        xml_escape(component.detail.as_deref().unwrap_or_default()),
+        component.checked_at,
+    )).collect();
+    format!(
+        r#"<{op} xmlns="{ns}"><gateId>{gate}</gateId><softwareVersion>{version}</softwareVersion><reportedAt>{now}</reportedAt><devices>{devices}</devices></{op}>"#,
+        op = REPORT_DEVICE_STATUS,
+        ns = DEVICE_GATEWAY_NAMESPACE,
+        gate = xml_escape(&config.gate_name),
+        version = xml_escape(software_version),
+        now = chrono::Local::now().to_rfc3339(),
+        devices = devices,
+    )
+}
+
+/// Text of every `<tag>` element in `xml`. Simplified parsing, like the CGS responses.
+fn element_texts<'a>(xml: &'a str, tag: &str) -> Vec<&'a str> {
+    let (open, close) = (format!("<{}>", tag), format!("</{}>", tag));
+    xml.split(open.as_str())
+        .skip(1)
+        .filter_map(|rest| rest.split(close.as_str()).next())
+        .collect()
+}
+
+fn operation_accepted(operation: &str, response_xml: &str) -> bool {
+    element_texts(response_xml, &format!("{}Result", operation))
+        .first()
+        .is_some_and(|result| result.trim().eq_ignore_ascii_case("true"))
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct DeviceCommand {
+    id: String,
+    command: String,
+}
+
+fn parse_commands(response_xml: &str) -> Vec<DeviceCommand> {
+    element_texts(response_xml, "DeviceCommand").into_iter()
+        .filter_map(|block| {
+            let id = element_texts(block, "commandId").first()?.trim().to_string();
+            let command = element_texts(block, "command").first()?.trim().to_uppercase();
+            (!id.is_empty()).then_some(DeviceCommand { id, command })
+        })
+        .collect()
+}
+
+/// The outcome of a command already taken on, if any. One that was started but never
+/// finished, e.g. because the app stopped, is reported failed rather than run again.
+fn executed_command(db_state: &DatabaseState, command_id: &str) -> Result<Option<DeviceCommandOutcome>, String> {
+    let conn = db_state.0.lock().map_err(|_| "Failed to acquire database lock")?;
+    conn.query_row(
+        "SELECT command, started_at, success, message, executed_at, acknowledged_at IS NOT NULL FROM device_commands WHERE command_id = ?1",
+        params![command_id],
+        |row| {
+            let started_at: String = row.get(1)?;
+            let success: Option<bool> = row.get(2)?;
+            let executed_at: Option<String> = row.get(4)?;
+            Ok(DeviceCommandOutcome {
+                command_id: command_id.to_string(),
+                command: row.get(0)?,
+                success: success.unwrap_or(false),
+                message: match success {
+                    Some(_) => row.get::<_, Option<String>>(3)?.unwrap_or_default(),
+                    None => "Interrupted before it finished; not run again".to_string(),
+                },
+                executed_at: executed_at.unwrap_or(started_at),
+                acknowledged: row.get(5)?,
+            })
+        },
+    ).optional().map_err(|e| e.to_string())
+}
+
+/// Claims a command before it runs. Returns false when it was already claimed.
+fn start_command(db_state: &DatabaseState, command: &DeviceCommand) -> Result<bool, String> {
+    let conn = db_state.0.lock().map_err(|_| "Failed to acquire database lock")?;
+    let cutoff = (chrono::Local::now() - chrono::Duration::days(COMMAND_HISTORY_DAYS)).to_rfc3339();
+    conn.execute("DELETE FROM device_commands WHERE started_at < ?1", params![cutoff])
+        .map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT OR IGNORE INTO device_commands (command_id, command, started_at) VALUES (?1, ?2, ?3)",
+        params![command.id, command.command, chrono::Local::now().to_rfc3339()],
+    ).map(|inserted| inserted == 1).map_err(|e| e.to_string())
+}
+
+fn finish_command(db_state: &DatabaseState, outcome: &DeviceCommandOutcome) -> Result<(), String> {
+    let conn = db_state.0.lock().map_err(|_| "Failed to acquire database lock")?;
+    conn.execute(
+        "UPDATE device_commands SET success = ?1, message = ?2, executed_at = ?3,
+             acknowledged_at = CASE WHEN ?4 THEN COALESCE(acknowledged_at, ?5) ELSE acknowledged_at END
+         WHERE command_id = ?6",
+        params![
+            outcome.success,
+            outcome.message,
+            outcome.executed_at,
+            outcome.acknowledged,
+            chrono::Local::now().to_rfc3339(),
+            outcome.command_id,
+        ],
+    ).map(|_| ()).map_err(|e| e.to_string())
+}
+
+async fn call_gateway(app_handle: &tauri::AppHandle, config: &AppConfig, operation: &str, operation_xml: &str) -> Result<String, SoapError> {
+    let credential_state = app_handle.state::<CredentialStoreState>();
+    soap_services_handler::call_soap_service(
+        config,
+        &credential_state,
+        &config.device_gateway_url,
+        DEVICE_GATEWAY_NAMESPACE,
+        operation,
+        operation_xml,
+    ).await
+}
+
+async fn execute_command(app_handle: &tauri::AppHandle, config: &AppConfig, command: &str) -> Result<String, String> {
+    match command {
+        RESTART_READERS => app_handle.state::<RFIDManagerState>().restart_readers(config).await
+            .map(|_| "Readers re-initialized".to_string()),
+        RECONNECT_UHF => {
+            app_handle.state::<UhfState>().request_reconnect();
+            Ok("UHF reader reconnect requested".to_string())
+        }
+        PULL_CONFIG => remote_config_handler::pull_remote_config(app_handle).await
+            .map(|outcome| format!("Remote config {:?}", outcome)),
+        SYNC_BLACKLIST => blacklist_handler::sync_blacklist(app_handle).await
+            .map(|changed| match changed {
+                Some(changed) => format!("Blacklist synced, {} change(s)", changed),
+                None => "Blacklist sync is turned off for this lane".to_string(),
+            }),
+        other => Err(format!("Unsupported device command '{}'", other)),
+    }
+}
+
+async fn acknowledge(app_handle: &tauri::AppHandle, config: &AppConfig, outcome: &DeviceCommandOutcome) -> Result<(), String> {
+    let operation_xml = format!(
+        r#"<{op} xmlns="{ns}"><gateId>{gate}</gateId><commandId>{id}</commandId><success>{success}</success><message>{message}</message></{op}>"#,
+        op = ACKNOWLEDGE_COMMAND,
+        ns = DEVICE_GATEWAY_NAMESPACE,
+        gate = xml_escape(&config.gate_name),
+        id = xml_escape(&outcome.command_id),
+        success = outcome.success,
+        message = xml_escape(&outcome.message),
+    );
+    let response_xml = call_gateway(app_handle, config, ACKNOWLEDGE_COMMAND, &operation_xml).await.map_err(|e| e.to_string())?;
+    if operation_accepted(ACKNOWLEDGE_COMMAND, &response_xml) {
+        Ok(())
+    } else {
+        Err(format!("Device gateway did not accept the acknowledgement of command {}", outcome.command_id))
+    }
+}
+
+/// Runs the commands queued for the gate. A command whose acknowledgement was lost is
+/// acknowledged again with its recorded outcome instead of running twice.
+async fn run_pending_commands(app_handle: &tauri::AppHandle, config: &AppConfig) -> Result<usize, String> {
+    let operation_xml = format!(
+        r#"<{op} xmlns="{ns}"><gateId>{gate}</gateId></{op}>"#,
+        op = GET_PENDING_COMMANDS,
+        ns = DEVICE_GATEWAY_NAMESPACE,
+        gate = xml_escape(&config.gate_name),
+    );
+    let response_xml = call_gateway(app_handle, config, GET_PENDING_COMMANDS, &operation_xml).await.map_err(|e| e.to_string())?;
+    let gateway_state = app_handle.state::<DeviceGatewayState>();
+    let db_state = app_handle.state::<DatabaseState>();
+    let mut executed = 0;
+    for command in parse_commands(&response_xml) {
+        let previous = match start_command(&db_state, &command)? {
+            true => None,
+            false => executed_command(&db_state, &command.id)?,
+        };
+        let mut outcome = match previous {
+            Some(outcome) => outcome,
+            None => {
+                log::info!("DEVICE: Running {} (command {})", command.command, command.id);
+                let result = execute_command(app_handle, config, &command.command).await;
+                executed += 1;
+                app_handle.state::<AuditLogState>().record(AuditKind::DeviceCommand, "device_gateway", &config.gate_name, serde_json::json!({
+                    "command_id": command.id,
+                    "command": command.command,
+                    "success": result.is_ok(),
+                    "error": result.as_ref().err(),
+                }));
+                let (success, message) = match result {
+                    Ok(message) => (true, message),
+                    Err(e) => {
+                        log::warn!("DEVICE: Command {} ({}) failed: {}", command.id, command.command, e);
+                        (false, e)
+                    }
+                };
+                let outcome = DeviceCommandOutcome {
+                    command_id: command.id,
+                    command: command.command,
+                    success,
+                    message,
+                    executed_at: chrono::Local::now().to_rfc3339(),
+                    acknowledged: false,
+                };
+                finish_command(&db_state, &outcome)?;
+                outcome
+            }
+        };
+        let acked = acknowledge(app_handle, config, &outcome).await;
+        outcome.acknowledged = acked.is_ok();
+        finish_command(&db_state, &outcome)?;
+        gateway_state.record_command(outcome);
+        acked?;
+    }
+    Ok(executed)
+}
+
+/// Sends one heartbeat and runs the pending commands. Returns `None` when the
+/// device gateway is turned off for this lane.
+pub async fn report_and_poll(app_handle: &tauri::AppHandle) -> Result<Option<usize>, String> {
+    let config = app_handle.state::<AppConfigState>().0.lock()
+        .map_err(|_| "Failed to acquire config lock")?
+        .clone();
+    if !config.device_gateway_enabled {
+        return Ok(None);
+    }
+    let software_version = env!("CARGO_PKG_VERSION").to_string();
+    let health = collect_health(app_handle, &config).await;
+    let faults: Vec<&str> = health.iter()
+        .filter(|component| component.status == HealthStatus::Fault)
+        .map(|component| component.device.as_str())
+        .collect();
+    if !faults.is_empty() {
+        log::warn!("DEVICE: Reporting faults on {}", faults.join(", "));
+    }
+
+    let operation_xml = status_xml(&config, &software_version, &health);
+    let reported = call_gateway(app_handle, &config, REPORT_DEVICE_STATUS, &operation_xml).await
+        .map_err(|e| e.to_string())
+        .and_then(|response_xml| if operation_accepted(REPORT_DEVICE_STATUS, &response_xml) {
+            Ok(())
+        } else {
+            Err("Device gateway did not accept the status report".to_string())
+        });
+    if let Ok(mut status) = app_handle.state::<DeviceGatewayState>().status.lock() {
+        status.software_version = software_version;
+        status.health = health;
+        if reported.is_ok() {
+            status.last_reported = Some(chrono::Local::now().to_rfc3339());
+        }
+    }
+    reported?;
+    run_pending_commands(app_handle, &config).await.map(Some)
+}
+
+pub fn spawn_device_gateway_client(app_handle: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let outcome = report_and_poll(&app_handle).await;
+            if let Ok(mut status) = app_handle.state::<DeviceGatewayState>().status.lock() {
+                status.last_error = outcome.as_ref().err().cloned();
+            }
+            if let Err(e) = &outcome {
+                log::error!("DEVICE: {}", e);
+            }
+            let interval = match outcome {
+                Ok(None) => DISABLED_RECHECK_INTERVAL,
+                _ => app_handle.state::<AppConfigState>().0.lock()
+                    .map(|c| Duration::from_secs(c.device_gateway_interval_secs))
+                    .unwrap_or(DISABLED_RECHECK_INTERVAL),
+            };
+            tokio::time::sleep(interval).await;
+        }
+    });
+}
+
+#[tauri::command]
+pub fn get_device_gateway_status_command(
+    config_state: State<'_, AppConfigState>,
+    gateway_state: State<'_, DeviceGatewayState>,
+) -> Result<DeviceGatewayStatus, String> {
+    let enabled = config_state.0.lock().map_err(|_| "Failed to acquire config lock")?.device_gateway_enabled;
+    let mut status = gateway_state.status.lock().map_err(|_| "Failed to acquire device gateway lock")?.clone();
+    status.enabled = enabled;
+    Ok(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db_handler;
+
+    /// Response body as `examples/device_gateway_mock.rs` sends it.
+    fn envelope(operation: &str, result: &str) -> String {
+        format!(
+            r#"<?xml version="1.0" encoding="utf-8"?><soap:Envelope xmlns:soap="http://schemas.xmlsoap.org/soap/envelope/"><soap:Body><{op}Response xmlns="http://halotec-indonesia.com/"><{op}Result>{result}</{op}Result></{op}Response></soap:Body></soap:Envelope>"#,
+            op = operation,
+            result = result,
+        )
+    }
+
+    fn command(id: &str, command: &str) -> DeviceCommand {
+        DeviceCommand { id: id.to_string(), command: command.to_string() }
+    }
+
+    #[test]
+    fn pending_commands_are_parsed_from_the_response() {
+        let response = envelope(
+            GET_PENDING_COMMANDS,
+            "<DeviceCommand><commandId>c1</commandId><command>restart_readers</command></DeviceCommand>\
+             <DeviceCommand><commandId> c2 </commandId><command> PULL_CONFIG </command></DeviceCommand>\
+             <DeviceCommand><commandId></commandId><command>SYNC_BLACKLIST</command></DeviceCommand>\
+             <DeviceCommand><commandId>c4</commandId></DeviceCommand>",
+        );
+        assert_eq!(parse_commands(&response), vec![command("c1", RESTART_READERS), command("c2", PULL_CONFIG)]);
+        assert!(parse_commands(&envelope(GET_PENDING_COMMANDS, "")).is_empty());
+    }
+
+    #[test]
+    fn operation_result_must_be_true() {
+        assert!(operation_accepted(ACKNOWLEDGE_COMMAND, &envelope(ACKNOWLEDGE_COMMAND, "true")));
+        assert!(operation_accepted(REPORT_DEVICE_STATUS, &envelope(REPORT_DEVICE_STATUS, " True ")));
+        assert!(!operation_accepted(ACKNOWLEDGE_COMMAND, &envelope(ACKNOWLEDGE_COMMAND, "false")));
+        assert!(!operation_accepted(ACKNOWLEDGE_COMMAND, &envelope(REPORT_DEVICE_STATUS, "true")));
+    }
+
+    #[test]
+    fn executed_commands_are_remembered() {
+        let db = db_handler::open_in_memory();
+        let pull = command("c1", PULL_CONFIG);
+        assert!(executed_command(&db, "c1").unwrap().is_none());
+        assert!(start_command(&db, &pull).unwrap());
+        assert!(!start_command(&db, &pull).unwrap(), "claimed once only");
+
+        let interrupted = executed_command(&db, "c1").unwrap().unwrap();
+        assert!(!interrupted.success && !interrupted.acknowledged);
+
+        let mut outcome = DeviceCommandOutcome {
+            command_id: "c1".to_string(),
+            command: PULL_CONFIG.to_string(),
+            success: true,
+            message: "Remote config Unchanged".to_string(),
+            executed_at: chrono::Local::now().to_rfc3339(),
+            acknowledged: false,
+        };
+        finish_command(&db, &outcome).unwrap();
+        outcome.acknowledged = true;
+        finish_command(&db, &outcome).unwrap();
+        let stored = executed_command(&db, "c1").unwrap().unwrap();
+        assert_eq!((stored.success, stored.message.as_str(), stored.acknowledged), (true, "Remote config Unchanged", true));
+    }
+}
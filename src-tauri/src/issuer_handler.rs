@@ -0,0 +1,374 @@
+// src-tauri/src/issuer_handler.rs
+// E-money card issuers. A card's issuer is found by selecting each issuer's
+// application on it, and that issuer's own command set then runs the deduction over
+// the reader's contactless link. None of the command sets below is taken from an
+// issuer kit yet (see `source` on each), so they only run against the simulated card.
+use serde::{Deserialize, Serialize};
+
+use crate::money::{Currency, Money};
+use crate::rfid_handler::CardIdentity;
+
+/// Card scheme of an e-money card.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum CardType {
+    MandiriEmoney,
+    BcaFlazz,
+    BniTapcash,
+    BriBrizzi,
+    #[default]
+    Unknown,
+}
+
+impl CardType {
+    /// Value sent to CaCMTool as `cardtype`.
+    pub fn cacm_code(self) -> &'static str {
+        match self {
+            CardType::MandiriEmoney => "MANDIRI",
+            CardType::BcaFlazz => "BCA",
+            CardType::BniTapcash => "BNI",
+            CardType::BriBrizzi => "BRI",
+            CardType::Unknown => "N/A",
+        }
+    }
+}
+
+/// Every issuer this build can take payments from.
+pub const SUPPORTED_CARD_TYPES: &[CardType] = &[
+    CardType::MandiriEmoney,
+    CardType::BcaFlazz,
+    CardType::BniTapcash,
+    CardType::BriBrizzi,
+];
+
+/// Half-duplex APDU exchange with the card on a reader.
+pub trait CardLink {
+    /// Sends one command APDU and returns the response including SW1 SW2.
+    fn transmit(&mut self, apdu: &[u8]) -> Result<Vec<u8>, String>;
+
+    /// Whether the card on the other end is simulated rather than a real card.
+    fn is_simulated(&self) -> bool;
+}
+
+/// Outcome of a deduction: balances, the reader SAM's transaction counter and the raw
+/// transaction record the issuer settles from.
+#[derive(Clone, Debug)]
+pub struct Deduction {
+    pub balance_before: Money,
+    pub balance_after: Money,
+    pub counter: u32,
+    pub transaction_data: Vec<u8>,
+    /// Set when the card accepted the debit but its answer did not add up; the card
+    /// has been charged, so this is journaled for the issuer to resolve.
+    pub dispute: Option<String>,
+}
+
+/// One issuer's card recognition and deduction flow.
+pub trait CardIssuer: Send + Sync {
+    fn card_type(&self) -> CardType;
+
+    /// Whether the card number looks like one of this issuer's. Only a hint for
+    /// showing a tapped card; payments go by `select`.
+    fn claims_card_no(&self, card_no: &str) -> bool;
+
+    /// Selects this issuer's application on the card. `Ok(false)` means the card
+    /// does not carry it.
+    fn select(&self, link: &mut dyn CardLink) -> Result<bool, String>;
+
+    /// Deducts `amount` from the card on `link`, whose application `select` chose.
+    fn deduct(&self, link: &mut dyn CardLink, card: &CardIdentity, amount: Money) -> Result<Deduction, String>;
+}
+
+/// Issuer whose cards select an application by AID and answer balance and debit
+/// commands with a 4-byte big-endian balance in rupiah; the debit response continues with
+/// the SAM's 4-byte transaction counter and the transaction record.
+struct ApduIssuer {
+    card_type: CardType,
+    /// Leading digits of the card number (CAN), for the display hint only.
+    can_prefixes: &'static [&'static str],
+    /// Where the AID and instruction bytes below come from.
+    source: &'static str,
+    /// Whether they were checked against the issuer's integration kit. Uncertified
+    /// command sets are only run against simulated cards.
+    certified: bool,
+    aid: &'static [u8],
+    cla: u8,
+    balance_ins: u8,
+    debit_ins: u8,
+}
+
+const STATUS_OK: [u8; 2] = [0x90, 0x00];
+/// ISO 7816-4 "file or application not found", the answer to a SELECT by an unknown AID.
+const STATUS_NOT_FOUND: [u8; 2] = [0x6A, 0x82];
+
+fn check_status(device: CardType, response: Vec<u8>) -> Result<Vec<u8>, String> {
+    match response.len().checked_sub(2) {
+        Some(split) if response[split..] == STATUS_OK => Ok(response[..split].to_vec()),
+        Some(split) => Err(format!("{:?} card answered SW {:02X}{:02X}", device, response[split], response[split + 1])),
+        None => Err(format!("{:?} card sent a truncated response", device)),
+    }
+}
+
+fn parse_balance(device: CardType, data: &[u8]) -> Result<u32, String> {
+    data.get(..4)
+        .map(|bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .ok_or_else(|| format!("{:?} card sent no balance", device))
+}
+
+impl CardIssuer for ApduIssuer {
+    fn card_type(&self) -> CardType {
+        self.card_type
+    }
+
+    fn claims_card_no(&self, card_no: &str) -> bool {
+        self.can_prefixes.iter().any(|prefix| card_no.starts_with(prefix))
+    }
+
+    fn select(&self, link: &mut dyn CardLink) -> Result<bool, String> {
+        let mut select = vec![0x00, 0xA4, 0x04, 0x00, self.aid.len() as u8];
+        select.extend_from_slice(self.aid);
+        let response = link.transmit(&select)?;
+        if response.ends_with(&STATUS_NOT_FOUND) {
+            return Ok(false);
+        }
+        check_status(self.card_type, response).map(|_| true)
+    }
+
+    fn deduct(&self, link: &mut dyn CardLink, card: &CardIdentity, amount: Money) -> Result<Deduction, String> {
+        if amount.currency() != Currency::Idr {
+            return Err(format!("{:?} cards hold rupiah, not {}", self.card_type, amount.currency().code()));
+        }
+        if !self.certified && !link.is_simulated() {
+            return Err(format!(
+                "The {} command set is not certified ({}); it is not sent to real cards",
+                self.card_type.cacm_code(),
+                self.source
+            ));
+        }
+        let amount = amount.to_u32()?;
+        let balance = check_status(self.card_type, link.transmit(&[self.cla, self.balance_ins, 0x00, 0x00, 0x04])?)?;
+        let balance_before = parse_balance(self.card_type, &balance)?;
+        if balance_before < amount {
+            return Err(format!("Insufficient balance on card {}: {} < {}", card.card_no, balance_before, amount));
+        }
+
+        let mut debit = vec![self.cla, self.debit_ins, 0x00, 0x00, 0x04];
+        debit.extend_from_slice(&amount.to_be_bytes());
+        debit.push(0x04);
+        let response = check_status(self.card_type, link.transmit(&debit)?)?;
+
+        // The card accepted the debit, so it has been charged: an answer that does not
+        // add up from here on is a dispute, not a failed payment.
+        let expected_after = balance_before - amount;
+        let balance_after = parse_balance(self.card_type, &response);
+        let counter = response.get(4..8)
+            .map(|bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]));
+        let dispute = match (&balance_after, counter) {
+            (Err(e), _) => Some(e.clone()),
+            (_, None) => Some(format!("{:?} card sent no transaction counter", self.card_type)),
+            (Ok(after), _) if *after != expected_after => Some(format!(
+                "Card {} reported balance {} after deducting {} from {}",
+                card.card_no, after, amount, balance_before
+            )),
+            _ => None,
+        };
+        Ok(Deduction {
+            balance_before: Money::from_u32(Currency::Idr, balance_before),
+            balance_after: Money::from_u32(Currency::Idr, balance_after.unwrap_or(expected_after)),
+            counter: counter.unwrap_or_default(),
+            transaction_data: response.get(8..).unwrap_or_default().to_vec(),
+            dispute,
+        })
+    }
+}
+
+static MANDIRI: ApduIssuer = ApduIssuer {
+    card_type: CardType::MandiriEmoney,
+    can_prefixes: &["6032"],
+    source: "no issuer kit yet; placeholder bytes to be replaced from the Bank Mandiri e-money reader integration kit",
+    certified: false,
+    aid: &[0xA0, 0x00, 0x00, 0x05, 0x71, 0x4E, 0x4A, 0x43],
+    cla: 0x00,
+    balance_ins: 0xB5,
+    debit_ins: 0xB6,
+};
+
+static BCA_FLAZZ: ApduIssuer = ApduIssuer {
+    card_type: CardType::BcaFlazz,
+    can_prefixes: &["0145"],
+    source: "no issuer kit yet; placeholder bytes to be replaced from the BCA Flazz reader integration kit",
+    certified: false,
+    aid: &[0xA0, 0x00, 0x00, 0x00, 0x18, 0x0F, 0x00, 0x00, 0x01, 0x80, 0x01],
+    cla: 0x90,
+    balance_ins: 0x32,
+    debit_ins: 0x34,
+};
+
+static BNI_TAPCASH: ApduIssuer = ApduIssuer {
+    card_type: CardType::BniTapcash,
+    can_prefixes: &["7546"],
+    source: "no issuer kit yet; placeholder bytes to be replaced from the BNI TapCash reader integration kit",
+    certified: false,
+    aid: &[0xA0, 0x00, 0x00, 0x04, 0x24, 0x54, 0x43],
+    cla: 0x90,
+    balance_ins: 0x6C,
+    debit_ins: 0x46,
+};
+
+static BRI_BRIZZI: ApduIssuer = ApduIssuer {
+    card_type: CardType::BriBrizzi,
+    can_prefixes: &["6013"],
+    source: "no issuer kit yet; placeholder bytes to be replaced from the BRI Brizzi reader integration kit",
+    certified: false,
+    aid: &[0xA0, 0x00, 0x00, 0x00, 0x03, 0x42, 0x52, 0x49],
+    cla: 0x90,
+    balance_ins: 0x6C,
+    debit_ins: 0xDC,
+};
+
+static ISSUERS: &[&dyn CardIssuer] = &[&MANDIRI, &BCA_FLAZZ, &BNI_TAPCASH, &BRI_BRIZZI];
+
+/// Finds the card's issuer by selecting each issuer's application in turn. The one
+/// that answers is left selected for `CardIssuer::deduct`.
+pub fn identify_issuer(link: &mut dyn CardLink) -> Result<Option<&'static dyn CardIssuer>, String> {
+    for issuer in ISSUERS.iter().copied() {
+        if issuer.select(link)? {
+            return Ok(Some(issuer));
+        }
+    }
+    Ok(None)
+}
+
+/// The card type its number suggests, for showing a tapped card before any
+/// command has been sent to it.
+pub fn card_type_hint(card_no: &str) -> CardType {
+    ISSUERS.iter()
+        .find(|issuer| issuer.claims_card_no(card_no))
+        .map_or(CardType::Unknown, |issuer| issuer.card_type())
+}
+
+/// AID of the issuer whose card numbers look like `card_no`. Only the simulated
+/// card link uses this, to answer SELECT the way that issuer's card would.
+pub(crate) fn simulated_aid(card_no: &str) -> Option<&'static [u8]> {
+    [&MANDIRI, &BCA_FLAZZ, &BNI_TAPCASH, &BRI_BRIZZI].into_iter()
+        .find(|issuer| issuer.claims_card_no(card_no))
+        .map(|issuer| issuer.aid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    /// Answers each APDU with the next scripted response and records what was sent.
+    struct StubLink {
+        responses: VecDeque<Vec<u8>>,
+        sent: Vec<Vec<u8>>,
+        simulated: bool,
+    }
+
+    impl StubLink {
+        fn new(responses: &[&[u8]]) -> Self {
+            StubLink { responses: responses.iter().map(|r| r.to_vec()).collect(), sent: Vec::new(), simulated: true }
+        }
+    }
+
+    impl CardLink for StubLink {
+        fn transmit(&mut self, apdu: &[u8]) -> Result<Vec<u8>, String> {
+            self.sent.push(apdu.to_vec());
+            self.responses.pop_front().ok_or_else(|| "Card removed".to_string())
+        }
+
+        fn is_simulated(&self) -> bool {
+            self.simulated
+        }
+    }
+
+    fn ok(data: &[u8]) -> Vec<u8> {
+        [data, &STATUS_OK[..]].concat()
+    }
+
+    fn debit_response(balance_after: u32, counter: u32) -> Vec<u8> {
+        ok(&[&balance_after.to_be_bytes()[..], &counter.to_be_bytes(), &[0xAB, 0xCD]].concat())
+    }
+
+    fn card() -> CardIdentity {
+        CardIdentity::from_raw("SIM_6032000000000001")
+    }
+
+    fn idr(amount: u32) -> Money {
+        Money::from_u32(Currency::Idr, amount)
+    }
+
+    #[test]
+    fn status_words_are_checked_and_stripped() {
+        assert_eq!(check_status(CardType::MandiriEmoney, vec![1, 2, 0x90, 0x00]).unwrap(), vec![1, 2]);
+        assert_eq!(check_status(CardType::MandiriEmoney, vec![0x90, 0x00]).unwrap(), Vec::<u8>::new());
+        let refused = check_status(CardType::MandiriEmoney, vec![1, 0x69, 0x85]).unwrap_err();
+        assert!(refused.contains("6985"), "{}", refused);
+        assert!(check_status(CardType::MandiriEmoney, vec![0x90]).is_err());
+    }
+
+    #[test]
+    fn balance_is_four_big_endian_bytes() {
+        assert_eq!(parse_balance(CardType::BcaFlazz, &[0x00, 0x01, 0x86, 0xA0]).unwrap(), 100_000);
+        assert_eq!(parse_balance(CardType::BcaFlazz, &[0x00, 0x00, 0x00, 0x05, 0xFF]).unwrap(), 5);
+        assert!(parse_balance(CardType::BcaFlazz, &[0x00, 0x01, 0x86]).is_err());
+    }
+
+    #[test]
+    fn issuer_is_the_one_whose_application_answers() {
+        let mut link = StubLink::new(&[&STATUS_NOT_FOUND, &STATUS_NOT_FOUND, &STATUS_OK]);
+        let issuer = identify_issuer(&mut link).unwrap().unwrap();
+        assert_eq!(issuer.card_type(), CardType::BniTapcash);
+        assert_eq!(&link.sent[2][5..], BNI_TAPCASH.aid);
+
+        let mut none = StubLink::new(&[&STATUS_NOT_FOUND[..]; 4]);
+        assert!(identify_issuer(&mut none).unwrap().is_none());
+        // Any other refusal is an error rather than "not this issuer".
+        assert!(identify_issuer(&mut StubLink::new(&[&[0x6F, 0x00]])).is_err());
+    }
+
+    #[test]
+    fn deduction_reads_balance_then_debits() {
+        let mut link = StubLink::new(&[&ok(&100_000u32.to_be_bytes()), &debit_response(75_000, 7)]);
+        let deduction = MANDIRI.deduct(&mut link, &card(), idr(25_000)).unwrap();
+        assert_eq!((deduction.balance_before, deduction.balance_after, deduction.counter), (idr(100_000), idr(75_000), 7));
+        assert_eq!(deduction.transaction_data, vec![0xAB, 0xCD]);
+        assert!(deduction.dispute.is_none());
+        assert_eq!(&link.sent[1][5..9], &25_000u32.to_be_bytes());
+    }
+
+    #[test]
+    fn short_balance_is_refused_before_the_debit() {
+        let mut link = StubLink::new(&[&ok(&10_000u32.to_be_bytes())]);
+        assert!(MANDIRI.deduct(&mut link, &card(), idr(25_000)).is_err());
+        assert_eq!(link.sent.len(), 1);
+    }
+
+    #[test]
+    fn accepted_debit_with_odd_answer_is_a_dispute() {
+        let cases: [(Vec<u8>, u32); 3] = [
+            (debit_response(80_000, 7), 80_000),
+            (ok(&75_000u32.to_be_bytes()), 75_000),
+            (ok(&[]), 75_000),
+        ];
+        for (response, balance_after) in cases {
+            let mut link = StubLink::new(&[&ok(&100_000u32.to_be_bytes()), &response]);
+            let deduction = MANDIRI.deduct(&mut link, &card(), idr(25_000)).unwrap();
+            assert!(deduction.dispute.is_some(), "no dispute for {:02X?}", response);
+            assert_eq!(deduction.balance_after, idr(balance_after));
+        }
+        // A refused debit is a failed payment, not a dispute.
+        let mut refused = StubLink::new(&[&ok(&100_000u32.to_be_bytes()), &[0x69, 0x85]]);
+        assert!(MANDIRI.deduct(&mut refused, &card(), idr(25_000)).is_err());
+    }
+
+    #[test]
+    fn uncertified_command_sets_are_not_sent_to_real_cards() {
+        let mut link = StubLink::new(&[&ok(&100_000u32.to_be_bytes()), &debit_response(75_000, 7)]);
+        link.simulated = false;
+        assert!(MANDIRI.deduct(&mut link, &card(), idr(25_000)).is_err());
+        assert!(link.sent.is_empty());
+    }
+}
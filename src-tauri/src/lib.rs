@@ -1,14 +1,33 @@
 // src-tauri/src/lib.rs
 use tauri::Manager;
-use std::sync::{Arc, Mutex}; // Added Arc
+use std::sync::Mutex;
 
 // Declare your modules
+pub mod anpr_handler;
+pub mod audit_handler;
+pub mod auth_handler;
+pub mod blacklist_handler;
+pub mod bundle_handler;
 pub mod config_handler;
+pub mod config_reload_handler;
+pub mod container_ocr_handler;
+pub mod credential_handler;
+pub mod db_handler;
+pub mod device_gateway_handler;
+pub mod issuer_handler;
+pub mod money;
+pub mod offline_handler;
+pub mod override_handler;
+pub mod remote_config_handler;
 pub mod rfid_handler;
+pub mod settlement_handler;
+pub mod tariff_handler;
+pub mod visit_handler;
 pub mod adam_handler;
 pub mod soap_services_handler;
 pub mod rest_services_handler;
 pub mod print_handler;
+pub mod uhf_handler;
 
 #[derive(Clone, serde::Serialize)]
 struct EventPayload {
@@ -23,11 +42,6 @@ pub fn run() {
     log::info!("Starting Checkpoint Manager Tauri application (Rust Backend v2 - from lib.rs)...");
 
     let initial_config = config_handler::AppConfig::default();
-    let rfid_manager = rfid_handler::RFIDManager::new(); // This is an instance of RFIDManager
-
-    // The state managed should be the Arc<Mutex<RFIDManager>>
-    // RFIDManagerState is the type alias for Arc<Mutex<RFIDManager>>
-    let rfid_manager_state: rfid_handler::RFIDManagerState = Arc::new(Mutex::new(rfid_manager));
 
     tauri::Builder::default()
         // Register all plugins
@@ -41,11 +55,39 @@ pub fn run() {
 
         // Manage application state
         .manage(config_handler::AppConfigState(Mutex::new(initial_config)))
-        .manage(rfid_manager_state) // Manage the Arc<Mutex<RFIDManager>>
+        .manage(adam_handler::PortalAuthorizationState::default())
+        .manage(remote_config_handler::RemoteConfigState::default())
+        .manage(uhf_handler::UhfState::default())
+        .manage(anpr_handler::AnprState::default())
+        .manage(container_ocr_handler::OcrState::default())
+        .manage(tariff_handler::TariffState::default())
+        .manage(device_gateway_handler::DeviceGatewayState::default())
         .setup(|app| {
             log::info!("Tauri setup hook initiated from lib.rs.");
             let handle = app.handle();
 
+            // The RFID service task owns the readers; commands talk to it through this handle.
+            app.manage(rfid_handler::RFIDService::spawn(handle.clone()));
+
+            match config_handler::get_app_data_dir(handle) {
+                Ok(data_dir) => {
+                    app.manage(credential_handler::CredentialStoreState(Mutex::new(
+                        credential_handler::CredentialStore::new(&data_dir),
+                    )));
+                    app.manage(auth_handler::AuthState(Mutex::new(
+                        auth_handler::AuthManager::new(&data_dir),
+                    )));
+                    app.manage(db_handler::open_database(&data_dir)?);
+                    app.manage(audit_handler::AuditLogState(Mutex::new(
+                        audit_handler::AuditLog::open(&data_dir),
+                    )));
+                }
+                Err(e) => {
+                    log::error!("Failed to resolve app data directory for the credential store: {}", e);
+                    return Err(e.into());
+                }
+            }
+
             // Initialize config state by loading from file or using defaults
             // The get_app_settings command also updates the state.
             let config_state_manager: tauri::State<config_handler::AppConfigState> = app.state();
@@ -54,10 +96,34 @@ pub fn run() {
                     log::info!("Config loaded/initialized successfully during setup: {:?}", loaded_cfg);
                 }
                 Err(e) => {
-                    log::error!("Failed to get/initialize config during setup, defaults will be used: {}", e);
+                    // A lane managed by CaCMTool can still start from the last config it verified.
+                    match remote_config_handler::load_last_known_good(handle) {
+                        Ok(Some(lkg_config)) => {
+                            log::warn!("Failed to load local config ({}), starting from the last known good remote config", e);
+                            let config_state_manager: tauri::State<config_handler::AppConfigState> = app.state();
+                            let locked = config_state_manager.0.lock();
+                            if let Ok(mut config) = locked {
+                                *config = lkg_config;
+                            }
+                        }
+                        Ok(None) => log::error!("Failed to get/initialize config during setup, defaults will be used: {}", e),
+                        Err(lkg_error) => log::error!("Failed to get/initialize config during setup ({}) and the last known good remote config is unusable ({}), defaults will be used", e, lkg_error),
+                    }
                 }
             }
 
+            auth_handler::spawn_session_sweeper(handle.clone());
+            rest_services_handler::spawn_upload_queue_worker(handle.clone());
+            offline_handler::spawn_degraded_replay_worker(handle.clone());
+            config_reload_handler::spawn_config_file_watcher(handle.clone());
+            remote_config_handler::spawn_remote_config_poller(handle.clone());
+            blacklist_handler::spawn_blacklist_sync(handle.clone());
+            device_gateway_handler::spawn_device_gateway_client(handle.clone());
+            uhf_handler::spawn_uhf_reader(handle.clone());
+            anpr_handler::spawn_anpr_listener(handle.clone());
+            container_ocr_handler::spawn_container_ocr_listener(handle.clone());
+            rfid_handler::spawn_reader_supervisor(handle.clone());
+
             #[cfg(debug_assertions)]
             {
                 match app.get_webview_window("main") {
@@ -75,11 +141,25 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             config_handler::get_app_settings,
             config_handler::save_app_settings,
+            config_handler::validate_app_settings_command,
+            config_handler::get_effective_config_command,
+            auth_handler::auth_setup_required_command,
+            auth_handler::operator_login_command,
+            auth_handler::operator_logout_command,
+            auth_handler::operator_session_status_command,
+            auth_handler::create_operator_command,
+            auth_handler::set_operator_password_command,
+            auth_handler::set_operator_active_command,
+            auth_handler::list_operators_command,
+            credential_handler::set_soap_credentials_command,
+            credential_handler::clear_soap_credentials_command,
+            credential_handler::get_soap_credentials_status_command,
             rfid_handler::initialize_rfid_reader_command,
             rfid_handler::start_rfid_detection_command,
             rfid_handler::stop_rfid_detection_command, // Keep this
             rfid_handler::rfid_payment_command,
             rfid_handler::get_rfid_status_command,   // Keep this
+            rfid_handler::list_serial_ports_command,
             soap_services_handler::validate_rfid_card_command,
             soap_services_handler::send_gate_in_command,
             soap_services_handler::send_truck_in_command,
@@ -87,6 +167,27 @@ pub fn run() {
             print_handler::print_cms_command,
             adam_handler::control_adam_portal_command,
             adam_handler::get_adam_button_status_command, // Ensure this is registered if it exists
+            override_handler::manual_override_command,
+            audit_handler::verify_audit_log_command,
+            audit_handler::export_audit_log_command,
+            remote_config_handler::set_remote_config_secret_command,
+            remote_config_handler::pull_remote_config_command,
+            remote_config_handler::get_remote_config_status_command,
+            bundle_handler::export_config_bundle_command,
+            bundle_handler::preview_config_bundle_command,
+            bundle_handler::import_config_bundle_command,
+            uhf_handler::get_current_truck_tag_command,
+            anpr_handler::check_truck_plate_command,
+            container_ocr_handler::get_recognized_containers_command,
+            rest_services_handler::get_upload_queue_status_command,
+            offline_handler::get_offline_status_command,
+            blacklist_handler::add_blacklist_entry_command,
+            blacklist_handler::remove_blacklist_entry_command,
+            blacklist_handler::list_blacklist_command,
+            blacklist_handler::get_blacklist_status_command,
+            device_gateway_handler::get_device_gateway_status_command,
+            settlement_handler::generate_settlement_command,
+            tariff_handler::calculate_tariff_command,
             process_gatepass_qr_command
         ])
         .run(tauri::generate_context!())
@@ -94,13 +195,18 @@ pub fn run() {
 }
 
 #[tauri::command]
-async fn process_gatepass_qr_command(qr_data: String) -> Result<String, String> {
+async fn process_gatepass_qr_command(app_handle: tauri::AppHandle, qr_data: String) -> Result<String, String> {
     log::info!("Backend (lib.rs): Received GatePass QR for validation: {}", qr_data);
+    // Any scan, readable or not, shows the scanner works.
+    app_handle.state::<device_gateway_handler::DeviceGatewayState>()
+        .observe("scanner", Ok(format!("Last scan at {}", chrono::Local::now().to_rfc3339())));
     if qr_data.to_uppercase().contains("INVALID") || qr_data.len() < 4 {
         log::warn!("GatePass QR validation failed: {}", qr_data);
         Err(format!("Invalid GatePass format or content: {}", qr_data))
     } else {
         log::info!("GatePass QR {} validated successfully (simulated).", qr_data);
+        // Lets TruckInOut go through on this pass if CGS is down by the time it is sent.
+        offline_handler::remember_validated(&app_handle.state::<db_handler::DatabaseState>(), offline_handler::CacheKind::GatePass, &qr_data);
         Ok(format!("GatePass {} accepted and processed.", qr_data))
     }
 }
\ No newline at end of file
@@ -0,0 +1,188 @@
+// src-tauri/src/money.rs
+// Exact money amounts in integer minor units. Rupiah has no sen in circulation,
+// so an IDR minor unit is one whole rupiah.
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Default, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Currency {
+    #[default]
+    Idr,
+}
+
+impl Currency {
+    pub fn code(self) -> &'static str {
+        match self {
+            Currency::Idr => "IDR",
+        }
+    }
+
+    fn symbol(self) -> &'static str {
+        match self {
+            Currency::Idr => "Rp",
+        }
+    }
+}
+
+/// An amount of money. Serialized as a bare integer of minor units so configs,
+/// the UI and CaCMTool keep seeing plain numbers; `17000.0` from older files
+/// and `{"minor": 17000, "currency": "IDR"}` are read as well.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct Money {
+    minor: i64,
+    currency: Currency,
+}
+
+impl Money {
+    pub const fn idr(rupiah: i64) -> Self {
+        Money { minor: rupiah, currency: Currency::Idr }
+    }
+
+    pub fn minor_units(self) -> i64 {
+        self.minor
+    }
+
+    pub fn currency(self) -> Currency {
+        self.currency
+    }
+
+    pub fn is_positive(self) -> bool {
+        self.minor > 0
+    }
+
+    pub fn checked_add(self, other: Money) -> Option<Money> {
+        if self.currency != other.currency {
+            return None;
+        }
+        self.minor.checked_add(other.minor).map(|minor| Money { minor, currency: self.currency })
+    }
+
+    pub fn checked_sub(self, other: Money) -> Option<Money> {
+        if self.currency != other.currency {
+            return None;
+        }
+        self.minor.checked_sub(other.minor).map(|minor| Money { minor, currency: self.currency })
+    }
+
+    pub fn checked_mul(self, factor: u32) -> Option<Money> {
+        self.minor.checked_mul(i64::from(factor)).map(|minor| Money { minor, currency: self.currency })
+    }
+
+    /// Total of `amounts`, or `None` on overflow or mixed currencies.
+    pub fn total(amounts: impl IntoIterator<Item = Money>) -> Option<Money> {
+        amounts.into_iter().try_fold(Money::default(), Money::checked_add)
+    }
+
+    /// The amount as the unsigned 32-bit minor units card commands carry.
+    pub fn to_u32(self) -> Result<u32, String> {
+        u32::try_from(self.minor).map_err(|_| format!("Amount {} is out of range for the card", self))
+    }
+
+    pub fn from_u32(currency: Currency, minor: u32) -> Self {
+        Money { minor: i64::from(minor), currency }
+    }
+
+    /// Minor units with `.` between thousands, as Indonesian slips print them.
+    pub fn grouped(self) -> String {
+        let digits = self.minor.unsigned_abs().to_string();
+        let mut out = String::with_capacity(digits.len() + digits.len() / 3 + 1);
+        if self.minor < 0 {
+            out.push('-');
+        }
+        for (i, digit) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i) % 3 == 0 {
+                out.push('.');
+            }
+            out.push(digit);
+        }
+        out
+    }
+}
+
+impl fmt::Display for Money {
+    /// `Rp 17.000`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.currency.symbol(), self.grouped())
+    }
+}
+
+impl Serialize for Money {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i64(self.minor)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum MoneyRepr {
+    Integer(i64),
+    Float(f64),
+    Text(String),
+    Full {
+        minor: i64,
+        #[serde(default)]
+        currency: Currency,
+    },
+}
+
+fn exact_minor(value: f64) -> Option<i64> {
+    (value.is_finite() && value.fract() == 0.0 && value.abs() < 9.0e15).then_some(value as i64)
+}
+
+impl<'de> Deserialize<'de> for Money {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let minor = match MoneyRepr::deserialize(deserializer)? {
+            MoneyRepr::Integer(minor) => Some(minor),
+            MoneyRepr::Float(value) => exact_minor(value),
+            MoneyRepr::Text(text) => {
+                let text = text.trim();
+                text.parse::<i64>().ok().or_else(|| text.parse::<f64>().ok().and_then(exact_minor))
+            }
+            MoneyRepr::Full { minor, currency } => return Ok(Money { minor, currency }),
+        };
+        minor
+            .map(Money::idr)
+            .ok_or_else(|| serde::de::Error::custom("amount must be a whole number of rupiah"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(json: &str) -> Result<Money, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    #[test]
+    fn reads_integer_float_text_and_full_forms() {
+        assert_eq!(parse("17000").unwrap(), Money::idr(17000));
+        assert_eq!(parse("17000.0").unwrap(), Money::idr(17000));
+        assert_eq!(parse(r#""17000""#).unwrap(), Money::idr(17000));
+        assert_eq!(parse(r#"" 17000.0 ""#).unwrap(), Money::idr(17000));
+        assert_eq!(parse(r#"{"minor": 17000, "currency": "IDR"}"#).unwrap(), Money::idr(17000));
+        assert_eq!(parse(r#"{"minor": 17000}"#).unwrap(), Money::idr(17000));
+    }
+
+    #[test]
+    fn rejects_fractional_and_non_numeric_amounts() {
+        assert!(parse("17000.5").is_err());
+        assert!(parse(r#""17000.5""#).is_err());
+        assert!(parse(r#""Rp 17.000""#).is_err());
+    }
+
+    #[test]
+    fn serializes_as_bare_minor_units() {
+        assert_eq!(serde_json::to_string(&Money::idr(17000)).unwrap(), "17000");
+    }
+
+    #[test]
+    fn formats_with_thousands_separators() {
+        assert_eq!(Money::idr(17000).to_string(), "Rp 17.000");
+        assert_eq!(Money::idr(1_234_567).to_string(), "Rp 1.234.567");
+        assert_eq!(Money::idr(500).to_string(), "Rp 500");
+        assert_eq!(Money::idr(0).to_string(), "Rp 0");
+        assert_eq!(Money::idr(-17000).to_string(), "Rp -17.000");
+    }
+}
@@ -0,0 +1,409 @@
+// src-tauri/src/offline_handler.rs
+// Degraded-mode gate operation. Tags and gate passes CGS accepted are cached for a
+// while, gate passes only until a truck has used them; when CGS cannot be reached the site's policy decides whether the truck
+// goes through, every such decision is journaled, and the ones let through are
+// sent to CGS once it answers again.
+use rusqlite::{params, OptionalExtension};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::future::Future;
+use std::time::Duration;
+use tauri::{Manager, State};
+
+use crate::config_handler::{AppConfig, AppConfigState, DegradedModePolicy};
+use crate::credential_handler::CredentialStoreState;
+use crate::db_handler::DatabaseState;
+use crate::soap_services_handler::{self, SoapError};
+
+const REPLAY_INTERVAL: Duration = Duration::from_secs(30);
+const REPLAY_BATCH_SIZE: i64 = 20;
+/// Replays CGS answers with a fault this many times before it is recorded as rejected.
+const REPLAY_MAX_ATTEMPTS: i64 = 5;
+
+/// What a validation cache entry vouches for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CacheKind {
+    Tag,
+    GatePass,
+}
+
+impl CacheKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            CacheKind::Tag => "tag",
+            CacheKind::GatePass => "gate_pass",
+        }
+    }
+}
+
+/// Records that CGS accepted `key`. Failures are only logged; the cache is a fallback.
+pub fn remember_validated(db_state: &DatabaseState, kind: CacheKind, key: &str) {
+    if key.is_empty() {
+        return;
+    }
+    let result = db_state.0.lock()
+        .map_err(|_| "Failed to acquire database lock".to_string())
+        .and_then(|conn| conn.execute(
+            "INSERT INTO validation_cache (kind, key, validated_at) VALUES (?1, ?2, ?3)
+             ON CONFLICT (kind, key) DO UPDATE SET validated_at = excluded.validated_at",
+            params![kind.as_str(), key, chrono::Local::now().to_rfc3339()],
+        ).map_err(|e| e.to_string()));
+    if let Err(e) = result {
+        log::error!("OFFLINE: Failed to cache {} {}: {}", kind.as_str(), key, e);
+    }
+}
+
+/// Drops `key` from the cache, e.g. a gate pass once a truck has used it.
+pub fn forget_validated(db_state: &DatabaseState, kind: CacheKind, key: &str) {
+    let result = db_state.0.lock()
+        .map_err(|_| "Failed to acquire database lock".to_string())
+        .and_then(|conn| conn.execute(
+            "DELETE FROM validation_cache WHERE kind = ?1 AND key = ?2",
+            params![kind.as_str(), key],
+        ).map_err(|e| e.to_string()));
+    if let Err(e) = result {
+        log::error!("OFFLINE: Failed to drop {} {} from the cache: {}", kind.as_str(), key, e);
+    }
+}
+
+fn cache_cutoff(config: &AppConfig) -> String {
+    let ttl = chrono::Duration::seconds(i64::try_from(config.validation_cache_ttl_secs).unwrap_or(i64::MAX));
+    (chrono::Local::now() - ttl).to_rfc3339()
+}
+
+/// Whether CGS accepted `key` within the cache TTL.
+pub fn is_known(db_state: &DatabaseState, config: &AppConfig, kind: CacheKind, key: &str) -> bool {
+    let result = db_state.0.lock()
+        .map_err(|_| "Failed to acquire database lock".to_string())
+        .and_then(|conn| conn.query_row(
+            "SELECT 1 FROM validation_cache WHERE kind = ?1 AND key = ?2 AND validated_at >= ?3",
+            params![kind.as_str(), key, cache_cutoff(config)],
+            |_| Ok(()),
+        ).optional().map_err(|e| e.to_string()));
+    match result {
+        Ok(found) => found.is_some(),
+        Err(e) => {
+            log::error!("OFFLINE: Failed to look up {} {}: {}", kind.as_str(), key, e);
+            false
+        }
+    }
+}
+
+fn prune_cache(db_state: &DatabaseState, config: &AppConfig) -> Result<usize, String> {
+    let conn = db_state.0.lock().map_err(|_| "Failed to acquire database lock")?;
+    conn.execute("DELETE FROM validation_cache WHERE validated_at < ?1", params![cache_cutoff(config)])
+        .map_err(|e| e.to_string())
+}
+
+/// Applies the degraded-mode policy to a CGS `operation` that could not be sent.
+/// `known` tells whether everything it vouches for is in the validation cache.
+/// The decision is journaled; allowed operations are replayed to CGS later.
+pub fn decide(
+    db_state: &DatabaseState,
+    config: &AppConfig,
+    operation: &str,
+    subject: &str,
+    known: bool,
+    operation_xml: &str,
+) -> Result<(), String> {
+    let (allowed, reason) = match (config.degraded_mode, known) {
+        (DegradedModePolicy::Block, _) => (false, "the lane is set to block while CGS is unreachable"),
+        (DegradedModePolicy::AllowKnown, true) => (true, "accepted by CGS within the cache period"),
+        (DegradedModePolicy::AllowKnown, false) => (false, "not accepted by CGS within the cache period"),
+        (DegradedModePolicy::AllowAll, _) => (true, "allowed pending reconciliation with CGS"),
+    };
+    journal_decision(db_state, config, operation, subject, operation_xml, Decision { policy: config.degraded_mode.as_str(), allowed, reason });
+
+    if allowed {
+        log::warn!("OFFLINE: {} for {} allowed in degraded mode: {}", operation, subject, reason);
+        Ok(())
+    } else {
+        log::warn!("OFFLINE: {} for {} refused in degraded mode: {}", operation, subject, reason);
+        Err(format!("CGS is unreachable and {} was not let through: {}", subject, reason))
+    }
+}
+
+/// Queues an operation a supervisor forced through, to be sent to CGS whatever the
+/// degraded-mode policy says.
+pub fn queue_override(db_state: &DatabaseState, config: &AppConfig, operation: &str, subject: &str, operation_xml: &str) {
+    journal_decision(db_state, config, operation, subject, operation_xml, Decision { policy: "override", allowed: true, reason: "forced by a supervisor" });
+}
+
+struct Decision<'a> {
+    policy: &'a str,
+    allowed: bool,
+    reason: &'a str,
+}
+
+fn journal_decision(db_state: &DatabaseState, config: &AppConfig, operation: &str, subject: &str, operation_xml: &str, decision: Decision<'_>) {
+    let journaled = db_state.0.lock()
+        .map_err(|_| "Failed to acquire database lock".to_string())
+        .and_then(|conn| conn.execute(
+            "INSERT INTO degraded_decisions (operation, subject, gate_name, policy, allowed, reason, operation_xml, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                operation,
+                subject,
+                config.gate_name,
+                decision.policy,
+                decision.allowed,
+                decision.reason,
+                operation_xml,
+                chrono::Local::now().to_rfc3339(),
+            ],
+        ).map_err(|e| e.to_string()));
+    if let Err(e) = journaled {
+        log::error!("OFFLINE: Failed to journal degraded {} for {}: {}", operation, subject, e);
+    }
+}
+
+struct PendingReplay {
+    id: i64,
+    operation: String,
+    subject: String,
+    operation_xml: String,
+    attempts: i64,
+}
+
+fn pending_replays(db_state: &DatabaseState) -> Result<Vec<PendingReplay>, String> {
+    let conn = db_state.0.lock().map_err(|_| "Failed to acquire database lock")?;
+    let mut stmt = conn.prepare(
+        "SELECT id, operation, subject, operation_xml, replay_attempts FROM degraded_decisions
+         WHERE allowed = 1 AND replayed_at IS NULL ORDER BY id LIMIT ?1",
+    ).map_err(|e| e.to_string())?;
+    let rows = stmt.query_map(params![REPLAY_BATCH_SIZE], |row| {
+        Ok(PendingReplay {
+            id: row.get(0)?,
+            operation: row.get(1)?,
+            subject: row.get(2)?,
+            operation_xml: row.get(3)?,
+            attempts: row.get(4)?,
+        })
+    }).map_err(|e| e.to_string())?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+}
+
+enum ReplayResult<'a> {
+    /// CGS answered; whether it accepted the operation.
+    Answered(bool),
+    /// CGS could not be reached. Not counted as an attempt.
+    Unreachable(&'a str),
+    /// CGS answered with a fault. With `give_up` the operation is recorded as rejected.
+    Failed { error: &'a str, give_up: bool },
+}
+
+fn record_replay_result(db_state: &DatabaseState, id: i64, result: ReplayResult<'_>) -> Result<(), String> {
+    let conn = db_state.0.lock().map_err(|_| "Failed to acquire database lock")?;
+    let now = chrono::Local::now().to_rfc3339();
+    match result {
+        ReplayResult::Answered(accepted) => conn.execute(
+            "UPDATE degraded_decisions SET replayed_at = ?1, replay_accepted = ?2, replay_attempts = replay_attempts + 1, replay_error = NULL WHERE id = ?3",
+            params![now, accepted, id],
+        ),
+        ReplayResult::Unreachable(error) => conn.execute(
+            "UPDATE degraded_decisions SET replay_error = ?1 WHERE id = ?2",
+            params![error, id],
+        ),
+        ReplayResult::Failed { error, give_up: false } => conn.execute(
+            "UPDATE degraded_decisions SET replay_attempts = replay_attempts + 1, replay_error = ?1 WHERE id = ?2",
+            params![error, id],
+        ),
+        ReplayResult::Failed { error, give_up: true } => conn.execute(
+            "UPDATE degraded_decisions SET replayed_at = ?1, replay_accepted = 0, replay_attempts = replay_attempts + 1, replay_error = ?2 WHERE id = ?3",
+            params![now, error, id],
+        ),
+    }.map(|_| ()).map_err(|e| e.to_string())
+}
+
+/// One replay round over the journal, oldest first, with `send` standing in for CGS.
+/// An unreachable CGS ends the round. A fault only holds back that subject's later
+/// operations, so CGS still sees each truck's operations in order, and after
+/// `REPLAY_MAX_ATTEMPTS` faults the operation is recorded as rejected.
+async fn replay_round<F, Fut>(db_state: &DatabaseState, mut send: F) -> Result<usize, String>
+where
+    F: FnMut(String, String) -> Fut,
+    Fut: Future<Output = Result<bool, SoapError>>,
+{
+    let mut replayed = 0;
+    let mut held_back = HashSet::new();
+    for pending in pending_replays(db_state)? {
+        if held_back.contains(&pending.subject) {
+            continue;
+        }
+        match send(pending.operation.clone(), pending.operation_xml.clone()).await {
+            Ok(accepted) => {
+                record_replay_result(db_state, pending.id, ReplayResult::Answered(accepted))?;
+                replayed += 1;
+                if !accepted {
+                    log::warn!("OFFLINE: CGS rejected {} for {} that was allowed in degraded mode", pending.operation, pending.subject);
+                }
+            }
+            Err(SoapError::Unreachable(e)) => {
+                record_replay_result(db_state, pending.id, ReplayResult::Unreachable(&e))?;
+                log::warn!("OFFLINE: Replay of {} for {} failed, CGS unreachable: {}", pending.operation, pending.subject, e);
+                break;
+            }
+            Err(SoapError::Failed(e)) => {
+                let give_up = pending.attempts + 1 >= REPLAY_MAX_ATTEMPTS;
+                record_replay_result(db_state, pending.id, ReplayResult::Failed { error: &e, give_up })?;
+                if give_up {
+                    log::error!(
+                        "OFFLINE: Giving up on {} for {} after {} faults, recorded as rejected: {}",
+                        pending.operation, pending.subject, REPLAY_MAX_ATTEMPTS, e
+                    );
+                } else {
+                    log::warn!("OFFLINE: Replay of {} for {} failed: {}", pending.operation, pending.subject, e);
+                    held_back.insert(pending.subject);
+                }
+            }
+        }
+    }
+    Ok(replayed)
+}
+
+/// Sends operations let through in degraded mode to CGS; see `replay_round`.
+pub async fn replay_degraded_decisions(app_handle: &tauri::AppHandle) -> Result<usize, String> {
+    let config = app_handle.state::<AppConfigState>().0.lock()
+        .map_err(|_| "Failed to acquire config lock")?
+        .clone();
+    let db_state = app_handle.state::<DatabaseState>();
+    let credential_state = app_handle.state::<CredentialStoreState>();
+    prune_cache(&db_state, &config)?;
+
+    let (config, credential_state) = (&config, credential_state.inner());
+    let replayed = replay_round(&db_state, |operation, operation_xml| async move {
+        soap_services_handler::call_cgs(config, credential_state, &operation, &operation_xml).await
+            .map(|response_xml| soap_services_handler::cgs_accepted(&operation, &response_xml))
+    }).await?;
+    if replayed > 0 {
+        log::info!("OFFLINE: Replayed {} degraded-mode decision(s) to CGS", replayed);
+    }
+    Ok(replayed)
+}
+
+pub fn spawn_degraded_replay_worker(app_handle: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            if let Err(e) = replay_degraded_decisions(&app_handle).await {
+                log::error!("OFFLINE: Degraded-mode replay failed: {}", e);
+            }
+            tokio::time::sleep(REPLAY_INTERVAL).await;
+        }
+    });
+}
+
+#[derive(Serialize, Debug)]
+pub struct OfflineStatus {
+    pub policy: DegradedModePolicy,
+    pub cached_entries: i64,
+    pub pending_replays: i64,
+    /// Operations CGS turned down when they were replayed.
+    pub rejected_on_replay: i64,
+    pub last_replay_error: Option<String>,
+}
+
+#[tauri::command]
+pub fn get_offline_status_command(
+    config_state: State<'_, AppConfigState>,
+    db_state: State<'_, DatabaseState>,
+) -> Result<OfflineStatus, String> {
+    let policy = config_state.0.lock().map_err(|_| "Failed to acquire config lock")?.degraded_mode;
+    let conn = db_state.0.lock().map_err(|_| "Failed to acquire database lock")?;
+    let cached_entries = conn.query_row("SELECT COUNT(*) FROM validation_cache", [], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    conn.query_row(
+        "SELECT COALESCE(SUM(CASE WHEN replayed_at IS NULL THEN 1 ELSE 0 END), 0),
+                COALESCE(SUM(CASE WHEN replay_accepted = 0 THEN 1 ELSE 0 END), 0),
+                (SELECT replay_error FROM degraded_decisions WHERE replay_error IS NOT NULL ORDER BY id DESC LIMIT 1)
+         FROM degraded_decisions WHERE allowed = 1",
+        [],
+        |row| Ok(OfflineStatus {
+            policy,
+            cached_entries,
+            pending_replays: row.get(0)?,
+            rejected_on_replay: row.get(1)?,
+            last_replay_error: row.get(2)?,
+        }),
+    ).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db_handler;
+    use std::cell::RefCell;
+
+    /// Journals one allowed decision per `(subject, xml)`; the xml names the row in `send`.
+    fn journal(db_state: &DatabaseState, rows: &[(&str, &str)]) {
+        let config = AppConfig { degraded_mode: DegradedModePolicy::AllowAll, ..AppConfig::default() };
+        for (subject, xml) in rows {
+            decide(db_state, &config, "TruckInOut", subject, false, xml).unwrap();
+        }
+    }
+
+    /// Runs a round in which CGS answers each row with `answer(xml)`; returns the rows sent.
+    async fn round(db_state: &DatabaseState, answer: impl Fn(&str) -> Result<bool, SoapError>) -> Vec<String> {
+        let sent = RefCell::new(Vec::new());
+        replay_round(db_state, |_, xml| {
+            sent.borrow_mut().push(xml.clone());
+            let result = answer(&xml);
+            async move { result }
+        }).await.unwrap();
+        sent.into_inner()
+    }
+
+    fn pending(db_state: &DatabaseState) -> Vec<String> {
+        pending_replays(db_state).unwrap().into_iter().map(|p| p.operation_xml).collect()
+    }
+
+    fn fault(xml: &str, failing: &str) -> Result<bool, SoapError> {
+        if xml == failing {
+            Err(SoapError::Failed("soap:Server fault".to_string()))
+        } else {
+            Ok(true)
+        }
+    }
+
+    #[tokio::test]
+    async fn unreachable_cgs_ends_the_round() {
+        let db = db_handler::open_in_memory();
+        journal(&db, &[("T1", "A"), ("T2", "B")]);
+        let sent = round(&db, |_| Err(SoapError::Unreachable("timed out".to_string()))).await;
+        assert_eq!(sent, vec!["A"]);
+        assert_eq!(pending(&db), vec!["A", "B"]);
+        assert_eq!(pending_replays(&db).unwrap()[0].attempts, 0);
+    }
+
+    #[tokio::test]
+    async fn fault_holds_back_only_that_subject() {
+        let db = db_handler::open_in_memory();
+        journal(&db, &[("T1", "A"), ("T1", "B"), ("T2", "C")]);
+        let sent = round(&db, |xml| fault(xml, "A")).await;
+        assert_eq!(sent, vec!["A", "C"]);
+        assert_eq!(pending(&db), vec!["A", "B"]);
+
+        let sent = round(&db, |_| Ok(true)).await;
+        assert_eq!(sent, vec!["A", "B"]);
+        assert!(pending(&db).is_empty());
+    }
+
+    #[tokio::test]
+    async fn repeated_faults_are_recorded_as_rejected() {
+        let db = db_handler::open_in_memory();
+        journal(&db, &[("T1", "A"), ("T1", "B")]);
+        for _ in 1..REPLAY_MAX_ATTEMPTS {
+            assert_eq!(round(&db, |xml| fault(xml, "A")).await, vec!["A"]);
+        }
+        // The last fault gives up on A, and T1's next operation goes in the same round.
+        assert_eq!(round(&db, |xml| fault(xml, "A")).await, vec!["A", "B"]);
+        assert!(pending(&db).is_empty());
+
+        let conn = db.0.lock().unwrap();
+        let (accepted, attempts): (bool, i64) = conn.query_row(
+            "SELECT replay_accepted, replay_attempts FROM degraded_decisions WHERE operation_xml = 'A'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        ).unwrap();
+        assert_eq!((accepted, attempts), (false, REPLAY_MAX_ATTEMPTS));
+    }
+}
@@ -0,0 +1,140 @@
+// src-tauri/src/override_handler.rs
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, Manager, State};
+
+use crate::adam_handler;
+use crate::audit_handler::{AuditKind, AuditLogState};
+use crate::auth_handler::{self, AuthState, Role};
+use crate::config_handler::AppConfigState;
+use crate::credential_handler::CredentialStoreState;
+use crate::db_handler::DatabaseState;
+use crate::rest_services_handler;
+use crate::soap_services_handler;
+
+const OVERRIDE_AUDIT_ENDPOINT: &str = "/api/ManualOverride";
+const MIN_REASON_TEXT_LEN: usize = 10;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OverrideAction {
+    /// Open the portal without touching any transaction.
+    OpenPortal,
+    /// Send the truck-in confirmation (Message6TAR) for a transaction CGS rejected,
+    /// queued if CGS is unreachable, and open the portal.
+    ForceComplete,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum OverrideReasonCode {
+    Emergency,
+    SystemOutage,
+    CgsRejectionOverride,
+    EquipmentFailure,
+    PaymentFailure,
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ManualOverrideRequest {
+    pub action: OverrideAction,
+    pub reason_code: OverrideReasonCode,
+    pub reason_text: String,
+    pub transaction_id: Option<String>,
+    pub tag_number: Option<String>,
+}
+
+/// What gets uploaded to CaCMTool for every override, successful or not.
+#[derive(Debug, Serialize, Clone)]
+pub struct OverrideRecord {
+    #[serde(rename = "overrideId")] pub override_id: String,
+    pub action: OverrideAction,
+    #[serde(rename = "reasonCode")] pub reason_code: OverrideReasonCode,
+    #[serde(rename = "reasonText")] pub reason_text: String,
+    #[serde(rename = "transactionId")] pub transaction_id: Option<String>,
+    #[serde(rename = "tagNumber")] pub tag_number: Option<String>,
+    pub supervisor: String,
+    #[serde(rename = "gateId")] pub gate_id: String,
+    pub timestamp: String,
+    pub success: bool,
+    pub outcome: String,
+}
+
+#[tauri::command]
+pub async fn manual_override_command(
+    config_state: State<'_, AppConfigState>,
+    auth_state: State<'_, AuthState>,
+    db_state: State<'_, DatabaseState>,
+    audit_state: State<'_, AuditLogState>,
+    app_handle: tauri::AppHandle,
+    session_token: String,
+    request: ManualOverrideRequest,
+) -> Result<OverrideRecord, String> {
+    let session = auth_handler::require_role(&auth_state, &config_state, &session_token, Role::Supervisor)?;
+
+    let reason_text = request.reason_text.trim().to_string();
+    if reason_text.chars().count() < MIN_REASON_TEXT_LEN {
+        return Err(format!("A reason description of at least {} characters is required", MIN_REASON_TEXT_LEN));
+    }
+    let transaction_id = request.transaction_id
+        .map(|id| id.trim().to_string())
+        .filter(|id| !id.is_empty());
+    if request.action == OverrideAction::ForceComplete && transaction_id.is_none() {
+        return Err("Force-completing requires the transaction ID".to_string());
+    }
+
+    let config = config_state.0.lock().map_err(|_| "Failed to acquire config lock")?.clone();
+    log::warn!(
+        "OVERRIDE: {:?} by supervisor {} at {} (reason {:?}: {}, TX: {:?})",
+        request.action, session.username, config.gate_name, request.reason_code, reason_text, transaction_id
+    );
+
+    // A refusal by CGS does not stop the override; the record carries it for reconciliation.
+    let completion = match (request.action, transaction_id.as_deref()) {
+        (OverrideAction::ForceComplete, Some(transaction_id)) => {
+            let credential_state = app_handle.state::<CredentialStoreState>();
+            let result = soap_services_handler::force_complete_truck_in(&config, &credential_state, &db_state, transaction_id).await;
+            if let Err(e) = &result {
+                log::warn!("OVERRIDE: Forced completion of {}: {}", transaction_id, e);
+            }
+            Some(result.unwrap_or_else(|e| e))
+        }
+        _ => None,
+    };
+    let outcome = adam_handler::open_portal(&config).await;
+    let portal_outcome = match &outcome {
+        Ok(()) => "Portal opened".to_string(),
+        Err(e) => format!("Portal open failed: {}", e),
+    };
+
+    let record = OverrideRecord {
+        override_id: uuid::Uuid::new_v4().to_string(),
+        action: request.action,
+        reason_code: request.reason_code,
+        reason_text,
+        transaction_id,
+        tag_number: request.tag_number,
+        supervisor: session.username,
+        gate_id: config.gate_name.clone(),
+        timestamp: chrono::Local::now().to_rfc3339(),
+        success: outcome.is_ok(),
+        outcome: match completion {
+            Some(completion) => format!("{}; {}", completion, portal_outcome),
+            None => portal_outcome,
+        },
+    };
+
+    // The record is kept even when the portal failed to open: the attempt itself is auditable.
+    audit_state.record(
+        AuditKind::ManualOverride,
+        &record.supervisor,
+        &record.gate_id,
+        serde_json::to_value(&record).unwrap_or_default(),
+    );
+    rest_services_handler::enqueue_cacm_upload(&db_state, OVERRIDE_AUDIT_ENDPOINT, &record)?;
+    if let Err(e) = app_handle.emit("manual_override_recorded", &record) {
+        log::error!("Failed to emit manual_override_recorded event: {}", e);
+    }
+
+    outcome.map(|_| record)
+}
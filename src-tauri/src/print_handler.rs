@@ -6,6 +6,7 @@ use tauri_plugin_shell::ShellExt; // For app_handle.shell().open()
 use tauri::Manager; // For app_handle.path()
 
 // Ensure correct path to your PaymentResultDetails and CMSData structs
+use crate::device_gateway_handler::DeviceGatewayState;
 use crate::rfid_handler::PaymentResultDetails;
 use crate::soap_services_handler::CMSData;
 
@@ -22,25 +23,41 @@ pub struct CmsSlipCommandPayload {
     pub tractor_number: Option<String>,
 }
 
+/// The outcome of every print is the printer's health as reported to the device gateway.
+fn observe_printer(app_handle: &tauri::AppHandle, result: &Result<String, String>) {
+    app_handle.state::<DeviceGatewayState>().observe("printer", result.clone());
+}
+
 #[tauri::command]
 pub async fn print_payment_slip_command(
     app_handle: tauri::AppHandle,
     slip_details: PaymentResultDetails
 ) -> Result<String, String> {
+    let result = print_payment_slip(&app_handle, &slip_details);
+    observe_printer(&app_handle, &result);
+    result
+}
+
+fn print_payment_slip(app_handle: &tauri::AppHandle, slip_details: &PaymentResultDetails) -> Result<String, String> {
     log::info!("PRINT: Generating payment slip for TX: {}", slip_details.transaction_id);
 
+    let charges: String = slip_details.tariff_items.iter()
+        .map(|item| format!("  {} x{} {}\n", item.description, item.quantity, item.amount))
+        .collect();
     let content = format!(
         "-- PAYMENT SLIP --\n\
         Gate: {}\n\
         Transaction ID: {}\n\
         Card No: {}\n\
-        Amount Paid: {:.2}\n\
-        Balance After: {:.2}\n\
+        {}\
+        Amount Paid: {}\n\
+        Balance After: {}\n\
         Timestamp: {}\n\
         ------------------\n(Simulated Print)",
         slip_details.gate_name,
         slip_details.transaction_id,
         slip_details.card_no,
+        charges,
         slip_details.amount_paid,
         slip_details.balance_after,
         slip_details.timestamp
@@ -70,6 +87,12 @@ pub async fn print_cms_command(
     app_handle: tauri::AppHandle,
     cms_data: CmsSlipCommandPayload
 ) -> Result<String, String> {
+    let result = print_cms(&app_handle, &cms_data);
+    observe_printer(&app_handle, &result);
+    result
+}
+
+fn print_cms(app_handle: &tauri::AppHandle, cms_data: &CmsSlipCommandPayload) -> Result<String, String> {
     log::info!("PRINT: Generating CMS slip for TX ID: {}", cms_data.transaction_id);
     let mut content = String::new();
     content.push_str("-- CMS SLIP --\n");
@@ -0,0 +1,401 @@
+// src-tauri/src/remote_config_handler.rs
+use hmac::{Hmac, Mac};
+use reqwest::{header, Client as ReqwestClient, StatusCode};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{Manager, State};
+
+use crate::audit_handler::{AuditKind, AuditLogState};
+use crate::auth_handler::{self, AuthState, Role};
+use crate::config_handler::{self, AppConfig, AppConfigState};
+use crate::config_reload_handler;
+use crate::credential_handler::CredentialStoreState;
+
+const REMOTE_CONFIG_ENDPOINT: &str = "/api/LaneConfig";
+const SIGNATURE_HEADER: &str = "X-Config-Signature";
+/// Top-level key CaCMTool puts in the signed body; it only ever goes up.
+const REVISION_KEY: &str = "config_revision";
+const LAST_KNOWN_GOOD_FILE_NAME: &str = "remote_config.lkg.json";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+const DISABLED_RECHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// The last remote config that was verified and applied, kept for offline starts.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct LastKnownGood {
+    gate_name: String,
+    etag: Option<String>,
+    signature: String,
+    fetched_at: String,
+    /// Files written before revisions were signed read as 0, so any signed revision is newer.
+    #[serde(default)]
+    revision: u64,
+    body: String,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct RemoteConfigStatus {
+    pub enabled: bool,
+    pub secret_configured: bool,
+    pub last_checked: Option<String>,
+    pub last_applied: Option<String>,
+    pub etag: Option<String>,
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PullOutcome {
+    /// Server answered 304 or sent the config already in effect.
+    Unchanged,
+    Applied,
+    /// Remote config is turned off for this lane.
+    Disabled,
+}
+
+#[derive(Default)]
+pub struct RemoteConfigState(pub Mutex<RemoteConfigStatus>);
+
+pub(crate) fn signing_secret_id(gate_name: &str) -> String {
+    format!("remote_config:{}", gate_name)
+}
+
+fn hex_decode(value: &str) -> Option<Vec<u8>> {
+    let value = value.trim();
+    if value.len() % 2 != 0 {
+        return None;
+    }
+    (0..value.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(value.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Checks the HMAC-SHA256 of `body` under the gate's shared secret, in constant time.
+/// Blacklist deltas are signed the same way.
+pub(crate) fn verify_signature(secret: &str, body: &[u8], signature_hex: &str) -> Result<(), String> {
+    let signature = hex_decode(signature_hex).ok_or("Remote config signature is not valid hex")?;
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes())
+        .map_err(|e| format!("Invalid remote config secret: {}", e))?;
+    mac.update(body);
+    mac.verify_slice(&signature).map_err(|_| "Remote config signature does not match".to_string())
+}
+
+pub(crate) fn signing_secret(app_handle: &tauri::AppHandle, gate_name: &str) -> Result<String, String> {
+    let credential_state = app_handle.state::<CredentialStoreState>();
+    let store = credential_state.0.lock().map_err(|_| "Failed to acquire credential store lock")?;
+    store.get::<String>(&signing_secret_id(gate_name))?
+        .ok_or_else(|| format!("No remote config signing secret is stored for gate {}", gate_name))
+}
+
+/// Parses a signed body and makes sure it is meant for this gate and passes validation.
+/// Returns the config together with the revision it was signed under.
+fn parse_remote_config(body: &str, gate_name: &str) -> Result<(AppConfig, u64), String> {
+    let mut value: serde_json::Value = serde_json::from_str(body)
+        .map_err(|e| format!("Remote config is not valid JSON: {}", e))?;
+    let revision = value.as_object_mut()
+        .and_then(|map| map.remove(REVISION_KEY))
+        .ok_or_else(|| format!("Remote config has no {}", REVISION_KEY))?;
+    let revision = revision.as_u64()
+        .ok_or_else(|| format!("Invalid {}: {}", REVISION_KEY, revision))?;
+    config_handler::migrate_config_value(&mut value)?;
+    let config: AppConfig = serde_json::from_value(value)
+        .map_err(|e| format!("Remote config does not match the settings format: {}", e))?;
+    if config.gate_name != gate_name {
+        return Err(format!("Remote config is for gate {}, not {}", config.gate_name, gate_name));
+    }
+    if let Some(message) = config_handler::blocking_errors(&config.validate()) {
+        return Err(message);
+    }
+    Ok((config, revision))
+}
+
+/// Decides whether a verified body may replace the last known good one. Returns false
+/// when it is the same revision sent again, and refuses anything older, so a replayed
+/// signed config can never roll the lane back.
+fn is_newer_revision(previous: Option<&LastKnownGood>, gate_name: &str, revision: u64, body: &str) -> Result<bool, String> {
+    let Some(previous) = previous.filter(|lkg| lkg.gate_name == gate_name) else {
+        return Ok(true);
+    };
+    if revision > previous.revision {
+        Ok(true)
+    } else if revision == previous.revision && body == previous.body {
+        Ok(false)
+    } else {
+        Err(format!(
+            "Remote config revision {} is not newer than the applied revision {}",
+            revision, previous.revision
+        ))
+    }
+}
+
+fn read_last_known_good(data_dir: &Path) -> Option<LastKnownGood> {
+    let path = data_dir.join(LAST_KNOWN_GOOD_FILE_NAME);
+    let content = fs::read_to_string(&path).ok()?;
+    match serde_json::from_str(&content) {
+        Ok(lkg) => Some(lkg),
+        Err(e) => {
+            log::error!("REMOTE: Ignoring unreadable last known good config {:?}: {}", path, e);
+            None
+        }
+    }
+}
+
+fn write_last_known_good(data_dir: &Path, lkg: &LastKnownGood) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(lkg)
+        .map_err(|e| format!("Failed to serialize last known good config: {}", e))?;
+    fs::write(data_dir.join(LAST_KNOWN_GOOD_FILE_NAME), content)
+        .map_err(|e| format!("Failed to write last known good config: {}", e))
+}
+
+/// Returns the last verified remote config when the local settings cannot be loaded.
+/// The signature is checked again, so a tampered copy is never used.
+pub fn load_last_known_good(app_handle: &tauri::AppHandle) -> Result<Option<AppConfig>, String> {
+    let data_dir = config_handler::get_app_data_dir(app_handle)?;
+    let Some(lkg) = read_last_known_good(&data_dir) else {
+        return Ok(None);
+    };
+    let secret = signing_secret(app_handle, &lkg.gate_name)?;
+    verify_signature(&secret, lkg.body.as_bytes(), &lkg.signature)?;
+    parse_remote_config(&lkg.body, &lkg.gate_name).map(|(config, _)| Some(config))
+}
+
+/// Fetches this lane's config from CaCMTool and applies it when it differs from the
+/// active one. Nothing is applied unless the body carries a valid signature.
+pub async fn pull_remote_config(app_handle: &tauri::AppHandle) -> Result<PullOutcome, String> {
+    let config = app_handle.state::<AppConfigState>().0.lock()
+        .map_err(|_| "Failed to acquire config lock")?
+        .clone();
+    if !config.remote_config_enabled {
+        return Ok(PullOutcome::Disabled);
+    }
+    let data_dir = config_handler::get_app_data_dir(app_handle)?;
+    let secret = signing_secret(app_handle, &config.gate_name)?;
+    let previous_lkg = read_last_known_good(&data_dir);
+
+    let url = format!("{}{}/{}", config.cacm_tool_url.trim_end_matches('/'), REMOTE_CONFIG_ENDPOINT, config.gate_name);
+    let client = ReqwestClient::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .map_err(|e| format!("Failed to build HTTP client: {}", e))?;
+    let mut request = client.get(&url);
+    if let Some(etag) = previous_lkg.as_ref().and_then(|lkg| lkg.etag.as_ref()) {
+        request = request.header(header::IF_NONE_MATCH, etag);
+    }
+    log::debug!("REMOTE: Checking {}", url);
+    let response = request.send().await
+        .map_err(|e| format!("Remote config request failed: {}", e))?;
+
+    if response.status() == StatusCode::NOT_MODIFIED {
+        return Ok(PullOutcome::Unchanged);
+    }
+    if !response.status().is_success() {
+        return Err(format!("Remote config request returned {}", response.status()));
+    }
+    let etag = response.headers().get(header::ETAG)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let signature = response.headers().get(SIGNATURE_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string)
+        .ok_or_else(|| format!("Remote config response has no {} header", SIGNATURE_HEADER))?;
+    let body = response.text().await
+        .map_err(|e| format!("Failed to read remote config: {}", e))?;
+
+    verify_signature(&secret, body.as_bytes(), &signature)?;
+    let (remote, revision) = parse_remote_config(&body, &config.gate_name)?;
+    if !is_newer_revision(previous_lkg.as_ref(), &config.gate_name, revision, &body)? {
+        return Ok(PullOutcome::Unchanged);
+    }
+    let lkg = LastKnownGood { gate_name: config.gate_name.clone(), etag, signature, fetched_at: chrono::Local::now().to_rfc3339(), revision, body };
+
+    let changes = config_handler::diff_configs(&config, &remote);
+    if changes.is_empty() {
+        write_last_known_good(&data_dir, &lkg)?;
+        return Ok(PullOutcome::Unchanged);
+    }
+
+    log::info!("REMOTE: Applying config from CaCMTool: {:?}", changes.iter().map(|c| &c.field).collect::<Vec<_>>());
+    config_handler::write_app_settings(app_handle, remote.clone(), &app_handle.state::<AppConfigState>())?;
+    config_reload_handler::reconfigure(app_handle, &config, &remote, "remote").await?;
+    // Only a config that actually came up is worth falling back to.
+    write_last_known_good(&data_dir, &lkg)?;
+    app_handle.state::<AuditLogState>().record(
+        AuditKind::ConfigChange,
+        "remote",
+        &remote.gate_name,
+        serde_json::json!({ "changes": changes, "etag": lkg.etag, "revision": lkg.revision }),
+    );
+    Ok(PullOutcome::Applied)
+}
+
+async fn pull_and_record(app_handle: &tauri::AppHandle) -> Result<PullOutcome, String> {
+    let result = pull_remote_config(app_handle).await;
+    let remote_state = app_handle.state::<RemoteConfigState>();
+    if let Ok(mut status) = remote_state.0.lock() {
+        let now = chrono::Local::now().to_rfc3339();
+        status.enabled = !matches!(result, Ok(PullOutcome::Disabled));
+        if status.enabled {
+            status.last_checked = Some(now.clone());
+        }
+        match &result {
+            Ok(PullOutcome::Applied) => {
+                status.last_applied = Some(now);
+                status.last_error = None;
+            }
+            Ok(_) => status.last_error = None,
+            Err(e) => status.last_error = Some(e.clone()),
+        }
+    }
+    if let Err(e) = &result {
+        log::error!("REMOTE: {}", e);
+    }
+    result
+}
+
+pub fn spawn_remote_config_poller(app_handle: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let outcome = pull_and_record(&app_handle).await;
+            let interval = match outcome {
+                Ok(PullOutcome::Disabled) => DISABLED_RECHECK_INTERVAL,
+                _ => app_handle.state::<AppConfigState>().0.lock()
+                    .map(|c| Duration::from_secs(c.remote_config_interval_secs))
+                    .unwrap_or(DISABLED_RECHECK_INTERVAL),
+            };
+            tokio::time::sleep(interval).await;
+        }
+    });
+}
+
+/// Stores the HMAC secret CaCMTool signs this gate's config with.
+#[tauri::command]
+pub fn set_remote_config_secret_command(
+    config_state: State<'_, AppConfigState>,
+    credential_state: State<'_, CredentialStoreState>,
+    auth_state: State<'_, AuthState>,
+    session_token: String,
+    secret: String,
+) -> Result<String, String> {
+    auth_handler::require_role(&auth_state, &config_state, &session_token, Role::Supervisor)?;
+    if secret.len() < 16 {
+        return Err("The signing secret must be at least 16 characters".to_string());
+    }
+    let gate_name = config_state.0.lock().map_err(|_| "Failed to acquire config lock")?.gate_name.clone();
+    let mut store = credential_state.0.lock().map_err(|_| "Failed to acquire credential store lock")?;
+    store.put(&signing_secret_id(&gate_name), &secret)?;
+    log::info!("REMOTE: Signing secret stored for gate {}", gate_name);
+    Ok(format!("Remote config signing secret stored for gate {}", gate_name))
+}
+
+/// Checks CaCMTool now instead of waiting for the next poll.
+#[tauri::command]
+pub async fn pull_remote_config_command(
+    app_handle: tauri::AppHandle,
+    config_state: State<'_, AppConfigState>,
+    auth_state: State<'_, AuthState>,
+    session_token: String,
+) -> Result<PullOutcome, String> {
+    auth_handler::require_role(&auth_state, &config_state, &session_token, Role::Supervisor)?;
+    pull_and_record(&app_handle).await
+}
+
+#[tauri::command]
+pub fn get_remote_config_status_command(
+    app_handle: tauri::AppHandle,
+    config_state: State<'_, AppConfigState>,
+    credential_state: State<'_, CredentialStoreState>,
+    remote_state: State<'_, RemoteConfigState>,
+) -> Result<RemoteConfigStatus, String> {
+    let config = config_state.0.lock().map_err(|_| "Failed to acquire config lock")?.clone();
+    let mut status = remote_state.0.lock().map_err(|_| "Failed to acquire remote config lock")?.clone();
+    status.enabled = config.remote_config_enabled;
+    status.secret_configured = credential_state.0.lock()
+        .map_err(|_| "Failed to acquire credential store lock")?
+        .updated_at(&signing_secret_id(&config.gate_name))
+        .is_some();
+    status.etag = config_handler::get_app_data_dir(&app_handle)
+        .ok()
+        .and_then(|dir| read_last_known_good(&dir))
+        .and_then(|lkg| lkg.etag);
+    Ok(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "0123456789abcdef";
+
+    fn sign(body: &str) -> String {
+        let mut mac = HmacSha256::new_from_slice(SECRET.as_bytes()).unwrap();
+        mac.update(body.as_bytes());
+        mac.finalize().into_bytes().iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    fn body(revision: u64, gate_name: &str) -> String {
+        let mut value = serde_json::to_value(AppConfig { gate_name: gate_name.to_string(), ..AppConfig::default() }).unwrap();
+        value[REVISION_KEY] = serde_json::json!(revision);
+        value.to_string()
+    }
+
+    fn lkg(revision: u64, body: &str) -> LastKnownGood {
+        LastKnownGood {
+            gate_name: "G1".to_string(),
+            etag: None,
+            signature: sign(body),
+            fetched_at: String::new(),
+            revision,
+            body: body.to_string(),
+        }
+    }
+
+    #[test]
+    fn signature_covers_the_whole_body() {
+        let signed = body(5, "G1");
+        assert!(verify_signature(SECRET, signed.as_bytes(), &sign(&signed)).is_ok());
+        let replayed_as_newer = signed.replace("\"config_revision\":5", "\"config_revision\":6");
+        assert_ne!(replayed_as_newer, signed);
+        assert!(verify_signature(SECRET, replayed_as_newer.as_bytes(), &sign(&signed)).is_err());
+        assert!(verify_signature(SECRET, signed.as_bytes(), "zz").is_err());
+    }
+
+    #[test]
+    fn body_must_carry_a_revision_for_this_gate() {
+        let (config, revision) = parse_remote_config(&body(7, "G1"), "G1").unwrap();
+        assert_eq!((config.gate_name.as_str(), revision), ("G1", 7));
+        assert!(parse_remote_config(&body(7, "G2"), "G1").is_err());
+
+        let mut unrevised: serde_json::Value = serde_json::from_str(&body(7, "G1")).unwrap();
+        unrevised.as_object_mut().unwrap().remove(REVISION_KEY);
+        assert!(parse_remote_config(&unrevised.to_string(), "G1").is_err());
+        unrevised[REVISION_KEY] = serde_json::json!("7");
+        assert!(parse_remote_config(&unrevised.to_string(), "G1").is_err());
+    }
+
+    #[test]
+    fn only_newer_revisions_replace_the_last_known_good() {
+        let current = lkg(5, &body(5, "G1"));
+        assert!(is_newer_revision(None, "G1", 1, &body(1, "G1")).unwrap());
+        assert!(is_newer_revision(Some(&current), "G1", 6, &body(6, "G1")).unwrap());
+        assert!(!is_newer_revision(Some(&current), "G1", 5, &current.body).unwrap());
+        assert!(is_newer_revision(Some(&current), "G1", 4, &body(4, "G1")).is_err());
+        // Same revision number with different content is a replay, not a resend.
+        let altered = body(5, "G1").replace("\"G1\"", "\"G1\" ");
+        assert!(is_newer_revision(Some(&current), "G1", 5, &altered).is_err());
+        // A last known good left over from another gate name does not pin the revision.
+        assert!(is_newer_revision(Some(&current), "G2", 1, &body(1, "G2")).unwrap());
+    }
+
+    #[test]
+    fn last_known_good_without_a_revision_reads_as_zero() {
+        let stored = r#"{"gate_name":"G1","etag":null,"signature":"00","fetched_at":"","body":"{}"}"#;
+        let lkg: LastKnownGood = serde_json::from_str(stored).unwrap();
+        assert_eq!(lkg.revision, 0);
+    }
+}
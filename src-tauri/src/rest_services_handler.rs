@@ -1,15 +1,26 @@
 use reqwest::Client as ReqwestClient;
 use serde::Deserialize;
 use crate::config_handler::{AppConfigState, AppConfig}; // Import AppConfig
-use tauri::State;
+use crate::db_handler::DatabaseState;
+use rusqlite::params;
+use std::time::Duration;
+use tauri::{Manager, State};
+use crate::money::Money;
+use crate::tariff_handler::TariffItem;
 use crate::rfid_handler::PaymentResultDetails; // Ensure this path is correct
 
+const UPLOAD_QUEUE_INTERVAL: Duration = Duration::from_secs(30);
+const UPLOAD_QUEUE_BATCH_SIZE: i64 = 20;
+/// A record CaCMTool keeps failing is given up on after this many attempts, so it
+/// cannot hold back the records queued after it.
+const UPLOAD_MAX_ATTEMPTS: i64 = 10;
+
 #[derive(serde::Serialize, Debug)]
 pub struct SaveOutPaymentInfoPayload {
     #[serde(rename = "trId")] pub tr_id: i32,
     pub cardnumber: Option<String>,
-    pub deduct_amount: i32,
-    pub card_remain_balance: i32,
+    pub deduct_amount: Money,
+    pub card_remain_balance: Money,
     pub cardtype: Option<String>,
     pub midreader: Option<String>,
     pub tidreader: Option<String>,
@@ -20,6 +31,7 @@ pub struct SaveOutPaymentInfoPayload {
     #[serde(rename = "gateId")] pub gate_id: Option<String>,
     pub mode: Option<String>,
     #[serde(rename = "datePayment")] pub date_payment: Option<String>,
+    #[serde(rename = "tariffItems", skip_serializing_if = "Vec::is_empty")] pub tariff_items: Vec<TariffItem>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -44,18 +56,19 @@ pub async fn save_payment_to_cacm_tool_command(
     let payload = SaveOutPaymentInfoPayload {
         tr_id: original_transaction_id,
         cardnumber: Some(payment_details.card_no),
-        deduct_amount: payment_details.amount_paid as i32,
-        card_remain_balance: payment_details.balance_after as i32,
-        cardtype: Some("N/A".to_string()), // Or from payment_details if available
-        midreader: Some("READER_MID_SIM".to_string()), // Placeholder
-        tidreader: Some("READER_TID_SIM".to_string()), // Placeholder
-        transcounter: Some("N/A".to_string()),       // Placeholder
-        transactiondata: Some(payment_details.transaction_id), // This is likely the payment system's TX ID
+        deduct_amount: payment_details.amount_paid,
+        card_remain_balance: payment_details.balance_after,
+        cardtype: Some(payment_details.card_type.cacm_code().to_string()),
+        midreader: Some(payment_details.mid),
+        tidreader: Some(payment_details.tid),
+        transcounter: Some(payment_details.transaction_counter.to_string()),
+        transactiondata: Some(payment_details.transaction_data),
         deduct_status: Some(if payment_details.success { "00".to_string() } else { "02".to_string() }),
         stid: Some(stid_tag_number),
         gate_id: Some(config.gate_name.clone()),
         mode: Some("AUTOGATE_V2".to_string()), // Or specific gate mode
         date_payment: Some(payment_details.timestamp),
+        tariff_items: payment_details.tariff_items,
     };
 
     log::debug!("REST: Calling CaCMTool SavePayment. URL: {}, Payload: {:?}", url, payload);
@@ -77,4 +90,264 @@ pub async fn save_payment_to_cacm_tool_command(
             Err(format!("Failed to send request to CaCMTool: {}", e))
         }
     }
-}
\ No newline at end of file
+}
+
+#[derive(serde::Serialize, Debug)]
+pub struct UploadQueueStatus {
+    pub pending: i64,
+    pub failed_attempts: i64,
+    pub oldest_pending: Option<String>,
+    /// Records given up on; they stay in the queue for inspection.
+    pub failed: i64,
+}
+
+struct QueuedUpload {
+    id: i64,
+    endpoint: String,
+    payload: String,
+    attempts: i64,
+}
+
+enum UploadError {
+    /// CaCMTool could not take the record now; it is sent again later.
+    Transient(String),
+    /// CaCMTool refused the record; sending it again will not help.
+    Rejected(String),
+}
+
+/// Stores a JSON payload for delivery to CaCMTool by the upload queue worker.
+/// Survives restarts and CaCMTool outages.
+pub fn enqueue_cacm_upload<T: serde::Serialize>(db_state: &DatabaseState, endpoint: &str, payload: &T) -> Result<i64, String> {
+    let payload = serde_json::to_string(payload)
+        .map_err(|e| format!("Failed to serialize upload payload: {}", e))?;
+    let conn = db_state.0.lock().map_err(|_| "Failed to acquire database lock")?;
+    conn.execute(
+        "INSERT INTO upload_queue (endpoint, payload, created_at) VALUES (?1, ?2, ?3)",
+        params![endpoint, payload, chrono::Local::now().to_rfc3339()],
+    ).map_err(|e| format!("Failed to queue upload for {}: {}", endpoint, e))?;
+    Ok(conn.last_insert_rowid())
+}
+
+fn next_queued_uploads(db_state: &DatabaseState) -> Result<Vec<QueuedUpload>, String> {
+    let conn = db_state.0.lock().map_err(|_| "Failed to acquire database lock")?;
+    let mut stmt = conn.prepare(
+        "SELECT id, endpoint, payload, attempts FROM upload_queue WHERE uploaded_at IS NULL AND failed_at IS NULL ORDER BY id LIMIT ?1",
+    ).map_err(|e| e.to_string())?;
+    let rows = stmt.query_map(params![UPLOAD_QUEUE_BATCH_SIZE], |row| {
+        Ok(QueuedUpload { id: row.get(0)?, endpoint: row.get(1)?, payload: row.get(2)?, attempts: row.get(3)? })
+    }).map_err(|e| e.to_string())?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+}
+
+/// Records one attempt; `error` is `None` for a delivered record, and `give_up`
+/// takes a failed one out of the queue.
+fn record_upload_result(db_state: &DatabaseState, id: i64, error: Option<&str>, give_up: bool) -> Result<(), String> {
+    let conn = db_state.0.lock().map_err(|_| "Failed to acquire database lock")?;
+    let now = chrono::Local::now().to_rfc3339();
+    match error {
+        None => conn.execute(
+            "UPDATE upload_queue SET uploaded_at = ?1, attempts = attempts + 1, last_error = NULL WHERE id = ?2",
+            params![now, id],
+        ),
+        Some(e) => conn.execute(
+            "UPDATE upload_queue SET attempts = attempts + 1, last_error = ?1, failed_at = CASE WHEN ?2 THEN ?3 END WHERE id = ?4",
+            params![e, give_up, now, id],
+        ),
+    }.map(|_| ()).map_err(|e| e.to_string())
+}
+
+async fn post_queued_upload(client: &ReqwestClient, config: &AppConfig, upload: &QueuedUpload) -> Result<(), UploadError> {
+    let url = format!("{}{}", config.cacm_tool_url.trim_end_matches('/'), upload.endpoint);
+    let response = client.post(&url)
+        .header("Content-Type", "application/json")
+        .body(upload.payload.clone())
+        .send().await
+        .map_err(|e| UploadError::Transient(format!("Failed to send request to CaCMTool: {}", e)))?;
+    let status_code = response.status();
+    if status_code.is_success() {
+        return Ok(());
+    }
+    let err_text = response.text().await.unwrap_or_else(|_| "Unknown API error content".to_string());
+    let message = format!("CaCMTool API request failed (status {}): {}", status_code, err_text);
+    let retryable = status_code == reqwest::StatusCode::REQUEST_TIMEOUT || status_code == reqwest::StatusCode::TOO_MANY_REQUESTS;
+    if status_code.is_client_error() && !retryable {
+        Err(UploadError::Rejected(message))
+    } else {
+        Err(UploadError::Transient(message))
+    }
+}
+
+/// Delivers queued uploads in order. A transient failure ends the round so records
+/// reach CaCMTool in the order they were created; a record CaCMTool refuses, or one
+/// that has failed `UPLOAD_MAX_ATTEMPTS` times, is marked failed and skipped.
+async fn flush_pending_uploads(db_state: &DatabaseState, config: &AppConfig, client: &ReqwestClient) -> Result<usize, String> {
+    let mut delivered = 0;
+    for upload in next_queued_uploads(db_state)? {
+        match post_queued_upload(client, config, &upload).await {
+            Ok(()) => {
+                record_upload_result(db_state, upload.id, None, false)?;
+                delivered += 1;
+            }
+            Err(UploadError::Rejected(e)) => {
+                log::error!("REST: CaCMTool refused queued upload {} to {}; giving up: {}", upload.id, upload.endpoint, e);
+                record_upload_result(db_state, upload.id, Some(&e), true)?;
+            }
+            Err(UploadError::Transient(e)) if upload.attempts + 1 >= UPLOAD_MAX_ATTEMPTS => {
+                log::error!("REST: Queued upload {} to {} failed {} times; giving up: {}", upload.id, upload.endpoint, upload.attempts + 1, e);
+                record_upload_result(db_state, upload.id, Some(&e), true)?;
+            }
+            Err(UploadError::Transient(e)) => {
+                log::warn!("REST: Queued upload {} to {} failed: {}", upload.id, upload.endpoint, e);
+                record_upload_result(db_state, upload.id, Some(&e), false)?;
+                break;
+            }
+        }
+    }
+    if delivered > 0 {
+        log::info!("REST: Delivered {} queued upload(s) to CaCMTool", delivered);
+    }
+    Ok(delivered)
+}
+
+pub async fn flush_upload_queue(app_handle: &tauri::AppHandle) -> Result<usize, String> {
+    let config = app_handle.state::<AppConfigState>().0.lock()
+        .map_err(|_| "Failed to acquire config lock")?
+        .clone();
+    flush_pending_uploads(&app_handle.state::<DatabaseState>(), &config, &ReqwestClient::new()).await
+}
+
+pub fn spawn_upload_queue_worker(app_handle: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            if let Err(e) = flush_upload_queue(&app_handle).await {
+                log::error!("REST: Upload queue flush failed: {}", e);
+            }
+            tokio::time::sleep(UPLOAD_QUEUE_INTERVAL).await;
+        }
+    });
+}
+
+#[tauri::command]
+pub fn get_upload_queue_status_command(db_state: State<'_, DatabaseState>) -> Result<UploadQueueStatus, String> {
+    upload_queue_status(&db_state)
+}
+
+fn upload_queue_status(db_state: &DatabaseState) -> Result<UploadQueueStatus, String> {
+    let conn = db_state.0.lock().map_err(|_| "Failed to acquire database lock")?;
+    conn.query_row(
+        "SELECT COALESCE(SUM(CASE WHEN failed_at IS NULL THEN 1 ELSE 0 END), 0),
+                COALESCE(SUM(CASE WHEN failed_at IS NULL AND attempts > 0 THEN 1 ELSE 0 END), 0),
+                MIN(CASE WHEN failed_at IS NULL THEN created_at END),
+                COALESCE(SUM(CASE WHEN failed_at IS NOT NULL THEN 1 ELSE 0 END), 0)
+         FROM upload_queue WHERE uploaded_at IS NULL",
+        [],
+        |row| Ok(UploadQueueStatus { pending: row.get(0)?, failed_attempts: row.get(1)?, oldest_pending: row.get(2)?, failed: row.get(3)? }),
+    ).map_err(|e| format!("Failed to read upload queue status: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    /// Answers `/ok` with 200, `/bad` with 400 and anything else with 503.
+    fn stub_cacm_tool() -> AppConfig {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let mut reader = BufReader::new(stream);
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut content_length = 0;
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = header.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            content_length = value.trim().parse().unwrap();
+                        }
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+                let status = match request_line.split_whitespace().nth(1) {
+                    Some("/ok") => "200 OK",
+                    Some("/bad") => "400 Bad Request",
+                    _ => "503 Service Unavailable",
+                };
+                let response = format!("HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status);
+                reader.get_mut().write_all(response.as_bytes()).unwrap();
+            }
+        });
+        AppConfig { cacm_tool_url: format!("http://{}", address), ..Default::default() }
+    }
+
+    fn row_state(db_state: &DatabaseState, id: i64) -> (bool, bool, i64) {
+        let conn = db_state.0.lock().unwrap();
+        conn.query_row(
+            "SELECT uploaded_at IS NOT NULL, failed_at IS NOT NULL, attempts FROM upload_queue WHERE id = ?1",
+            params![id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        ).unwrap()
+    }
+
+    #[tokio::test]
+    async fn queued_uploads_are_delivered_in_order() {
+        let config = stub_cacm_tool();
+        let db_state = crate::db_handler::open_in_memory();
+        let first = enqueue_cacm_upload(&db_state, "/ok", &serde_json::json!({ "n": 1 })).unwrap();
+        let second = enqueue_cacm_upload(&db_state, "/ok", &serde_json::json!({ "n": 2 })).unwrap();
+        assert!(first < second);
+
+        assert_eq!(flush_pending_uploads(&db_state, &config, &ReqwestClient::new()).await.unwrap(), 2);
+        assert_eq!(row_state(&db_state, first), (true, false, 1));
+        assert_eq!(row_state(&db_state, second), (true, false, 1));
+        assert_eq!(upload_queue_status(&db_state).unwrap().pending, 0);
+    }
+
+    #[tokio::test]
+    async fn a_refused_upload_is_given_up_and_does_not_block_the_rest() {
+        let config = stub_cacm_tool();
+        let db_state = crate::db_handler::open_in_memory();
+        let refused = enqueue_cacm_upload(&db_state, "/bad", &serde_json::json!({})).unwrap();
+        let next = enqueue_cacm_upload(&db_state, "/ok", &serde_json::json!({})).unwrap();
+
+        assert_eq!(flush_pending_uploads(&db_state, &config, &ReqwestClient::new()).await.unwrap(), 1);
+        assert_eq!(row_state(&db_state, refused), (false, true, 1));
+        assert_eq!(row_state(&db_state, next), (true, false, 1));
+        let status = upload_queue_status(&db_state).unwrap();
+        assert_eq!((status.pending, status.failed), (0, 1));
+    }
+
+    #[tokio::test]
+    async fn a_transient_failure_ends_the_round_in_order() {
+        let config = stub_cacm_tool();
+        let db_state = crate::db_handler::open_in_memory();
+        let down = enqueue_cacm_upload(&db_state, "/down", &serde_json::json!({})).unwrap();
+        let next = enqueue_cacm_upload(&db_state, "/ok", &serde_json::json!({})).unwrap();
+
+        assert_eq!(flush_pending_uploads(&db_state, &config, &ReqwestClient::new()).await.unwrap(), 0);
+        assert_eq!(row_state(&db_state, down), (false, false, 1));
+        assert_eq!(row_state(&db_state, next), (false, false, 0));
+    }
+
+    #[tokio::test]
+    async fn an_upload_is_given_up_after_the_last_attempt() {
+        let config = stub_cacm_tool();
+        let db_state = crate::db_handler::open_in_memory();
+        let down = enqueue_cacm_upload(&db_state, "/down", &serde_json::json!({})).unwrap();
+        let next = enqueue_cacm_upload(&db_state, "/ok", &serde_json::json!({})).unwrap();
+        db_state.0.lock().unwrap()
+            .execute("UPDATE upload_queue SET attempts = ?1 WHERE id = ?2", params![UPLOAD_MAX_ATTEMPTS - 1, down])
+            .unwrap();
+
+        assert_eq!(flush_pending_uploads(&db_state, &config, &ReqwestClient::new()).await.unwrap(), 1);
+        assert_eq!(row_state(&db_state, down), (false, true, UPLOAD_MAX_ATTEMPTS));
+        assert_eq!(row_state(&db_state, next), (true, false, 1));
+    }
+}
@@ -1,9 +1,15 @@
 use std::time::Duration;
 use tauri::{State, Manager, Emitter};
-use tokio::sync::mpsc;
-use crate::config_handler::AppConfigState;
-use std::sync::{Arc, Mutex};
-use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::{mpsc, oneshot};
+use crate::audit_handler::{AuditKind, AuditLogState};
+use crate::blacklist_handler::{self, BlacklistKind};
+use crate::db_handler::DatabaseState;
+use crate::settlement_handler;
+use crate::config_handler::{self, AppConfig, AppConfigState, ReaderConfig};
+use crate::issuer_handler::{self, CardLink, CardType};
+use crate::money::Money;
+use crate::tariff_handler::{self, TariffItem, TariffState};
+use std::collections::{BTreeMap, VecDeque};
 // use serialport; // Uncomment when implementing actual serial logic
 
 #[derive(Clone, serde::Serialize, serde::Deserialize, Debug)] // Added Deserialize
@@ -19,36 +25,205 @@ pub struct PaymentResultDetails {
     pub message: String,
     pub transaction_id: String,
     pub card_no: String,
-    pub amount_paid: f64,
-    pub balance_after: f64,
+    pub amount_paid: Money,
+    pub balance_after: Money,
     pub timestamp: String,
     pub gate_name: String, // <<< Ensure this field exists
+    #[serde(default)]
+    pub reader_id: String,
+    #[serde(default)]
+    pub card_type: CardType,
+    /// Reader SAM ids and the card's transaction counter and raw transaction
+    /// data (hex), as the issuer needs them for settlement.
+    #[serde(default)]
+    pub mid: String,
+    #[serde(default)]
+    pub tid: String,
+    #[serde(default)]
+    pub transaction_counter: u32,
+    #[serde(default)]
+    pub transaction_data: String,
+    /// The tariff lines the amount was made up of.
+    #[serde(default)]
+    pub tariff_items: Vec<TariffItem>,
+    /// Why the deduction is disputed, when the card took the debit but its answer
+    /// did not add up.
+    #[serde(default)]
+    pub dispute: Option<String>,
+}
+
+/// A payment as handed to a reader: the card, the amount and the issuers the
+/// site accepts.
+#[derive(Debug)]
+pub struct PaymentRequest {
+    pub card_data: String,
+    pub amount: Money,
+    pub accepted_issuers: Vec<CardType>,
 }
 
 #[derive(serde::Serialize)]
 struct EventPayload {
     message: String,
     data: Option<String>,
+    reader_id: String,
+}
+
+/// Connection state of a reader as reported in `device_status_changed`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceStatus {
+    Connected,
+    Disconnected,
+}
+
+#[derive(Clone, serde::Serialize, Debug)]
+struct DeviceStatusEvent {
+    device: &'static str,
+    reader_id: String,
+    status: DeviceStatus,
+    port: Option<String>,
+    message: String,
+}
+
+/// What the reader reported about a card, parsed once for every consumer.
+#[derive(Clone, serde::Serialize, Debug, PartialEq, Eq)]
+pub struct CardIdentity {
+    /// Raw string from the reader, as passed to `rfid_payment_command`.
+    pub raw: String,
+    pub card_no: String,
+}
+
+impl CardIdentity {
+    pub fn from_raw(raw: &str) -> Self {
+        CardIdentity {
+            raw: raw.to_string(),
+            card_no: extract_card_number(raw),
+        }
+    }
+
+    /// The card type its number suggests; the issuer is only settled at payment.
+    pub fn card_type(&self) -> CardType {
+        issuer_handler::card_type_hint(&self.card_no)
+    }
+}
+
+/// Payload of `card_present` and `card_removed`.
+#[derive(Clone, serde::Serialize, Debug)]
+struct CardEventPayload {
+    reader_id: String,
+    card: CardIdentity,
+    card_type: CardType,
+    timestamp: String,
+}
+
+/// A card seen by one of the lane's readers.
+#[derive(Debug, Clone)]
+struct CardTap {
+    reader_id: String,
+    card_data: String,
+    detected_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl CardTap {
+    fn new(reader_id: &str, card_data: &str) -> Self {
+        CardTap {
+            reader_id: reader_id.to_string(),
+            card_data: card_data.to_string(),
+            detected_at: chrono::Utc::now(),
+        }
+    }
+
+    fn payload(&self) -> CardEventPayload {
+        let card = CardIdentity::from_raw(&self.card_data);
+        CardEventPayload {
+            reader_id: self.reader_id.clone(),
+            card_type: card.card_type(),
+            card,
+            timestamp: self.detected_at.to_rfc3339(),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum CardEvent {
+    Present(CardTap),
+    Removed(CardTap),
+}
+
+/// How long a card may go unseen before it counts as removed; a few polls, so
+/// one missed read does not split a tap in two.
+const CARD_REMOVAL_GRACE: Duration = Duration::from_millis(300);
+
+/// Turns one reader's poll results into present/removed events. A card left on
+/// the reader is reported once, and the same card presented again within the
+/// debounce interval is treated as a re-read and not reported at all.
+#[derive(Default)]
+struct CardPresence {
+    /// Card on the reader, when it was last seen and whether it was announced.
+    on_reader: Option<(String, std::time::Instant, bool)>,
+    last_announced: Option<(String, std::time::Instant)>,
+}
+
+impl CardPresence {
+    fn update(&mut self, reader_id: &str, polled: Option<String>, debounce: Duration, now: std::time::Instant) -> Vec<CardEvent> {
+        let mut events = Vec::new();
+
+        if let Some((card, last_seen, announced)) = self.on_reader.take() {
+            let still_there = polled.as_deref() == Some(card.as_str());
+            if still_there || (polled.is_none() && now.duration_since(last_seen) < CARD_REMOVAL_GRACE) {
+                self.on_reader = Some((card, if still_there { now } else { last_seen }, announced));
+                return events;
+            }
+            if announced {
+                events.push(CardEvent::Removed(CardTap::new(reader_id, &card)));
+            }
+        }
+
+        if let Some(card) = polled {
+            let re_read = self.last_announced.as_ref()
+                .is_some_and(|(last, at)| *last == card && now.duration_since(*at) < debounce);
+            if re_read {
+                log::debug!("RFID: Ignoring re-read of {} on reader {}", card, reader_id);
+            } else {
+                self.last_announced = Some((card.clone(), now));
+                events.push(CardEvent::Present(CardTap::new(reader_id, &card)));
+            }
+            self.on_reader = Some((card, now, !re_read));
+        }
+        events
+    }
 }
 
-// Thread-safe RFID reader implementation
+// RFID reader implementation, owned by its actor task (see `run_reader`)
 pub struct RFIDReader {
+    id: String,
     port_name: String,
     baud_rate: u32,
-    // For actual implementation, use Arc<Mutex<Box<dyn serialport::SerialPort + Send>>>
-    // This allows safe sharing between threads
+    /// Merchant and terminal ids of the reader's SAM, quoted in settlement files.
+    mid: String,
+    tid: String,
+    /// Card number and transaction counter of the simulated card link (remove in production).
+    sim_card_no: String,
+    sim_transaction_counter: u32,
+    // For actual implementation, hold the Box<dyn serialport::SerialPort> here;
+    // only the reader's actor ever touches it, so no lock is needed
 }
 
 impl RFIDReader {
-    pub fn new(port_name: &str, baud_rate: u32) -> Self {
+    pub fn new(id: &str, port_name: &str, baud_rate: u32) -> Self {
         RFIDReader {
+            id: id.to_string(),
             port_name: port_name.to_string(),
             baud_rate,
+            mid: String::new(),
+            tid: String::new(),
+            sim_card_no: String::new(),
+            sim_transaction_counter: 0,
         }
     }
 
     pub fn init_port(&mut self) -> Result<(), String> {
-        log::info!("RFID: Initializing port {} @ {} baud", self.port_name, self.baud_rate);
+        log::info!("RFID: Initializing reader {} on port {} @ {} baud", self.id, self.port_name, self.baud_rate);
         
         // TODO: Implement actual serial port opening
         // let port = serialport::new(&self.port_name, self.baud_rate)
@@ -61,106 +236,729 @@ impl RFIDReader {
         // - Send wake-up command
         // - Configure reader settings
         // - Verify communication
+        // - Read the SAM's MID/TID
+
+        // Simulated SAM ids (remove in production)
+        self.mid = "000000000000001".to_string();
+        self.tid = format!("SIM{:05}", self.port_name.bytes().map(u32::from).sum::<u32>() % 100_000);
         
-        log::info!("RFID: Port {} initialized successfully", self.port_name);
+        log::info!("RFID: Reader {} on port {} initialized successfully", self.id, self.port_name);
         Ok(())
     }
 
-    pub fn poll_for_card(&mut self) -> Option<String> {
+    pub fn poll_for_card(&mut self) -> Result<Option<String>, String> {
         // TODO: Implement actual card detection
         // Send command to check for card presence
         // Parse response and extract card UID/data
-        // Return card data if present, None otherwise
+        // Return card data if present, None otherwise; serial I/O errors are Err
         
         // For now, return None (no simulation in polling method)
-        None
+        Ok(None)
     }
 
-    pub fn process_payment(&mut self, card_data_raw: &str, amount: f64) -> Result<PaymentResultDetails, String> {
-        log::info!("Processing payment for card: {}, amount: ${:.2}", card_data_raw, amount);
-        
-        // TODO: Implement actual payment processing
-        // 1. Validate card data
-        // 2. Check current balance
-        // 3. Process deduction
-        // 4. Update card balance
-        // 5. Return transaction details
-        
-        // Simulate processing time
-        std::thread::sleep(Duration::from_millis(500));
-        
-        // Simulated successful payment
+    pub fn process_payment(&mut self, request: &PaymentRequest) -> Result<PaymentResultDetails, String> {
+        log::info!("Processing payment on reader {} for card: {}, amount: {}", self.id, request.card_data, request.amount);
+
+        let card = CardIdentity::from_raw(&request.card_data);
+        self.sim_card_no = card.card_no.clone();
+        let issuer = issuer_handler::identify_issuer(self)?
+            .ok_or_else(|| format!("Card {} is not from a supported e-money issuer", card.card_no))?;
+        let card_type = issuer.card_type();
+        if !request.accepted_issuers.contains(&card_type) {
+            return Err(format!("{} cards are not accepted at this site", card_type.cacm_code()));
+        }
+        if !request.amount.is_positive() {
+            return Err(format!("Invalid payment amount {}", request.amount));
+        }
+
+        let deduction = issuer.deduct(self, &card, request.amount)?;
+        let message = match &deduction.dispute {
+            Some(dispute) => format!("Payment taken but disputed: {}", dispute),
+            None => "Payment processed successfully".to_string(),
+        };
+
         Ok(PaymentResultDetails {
             success: true,
-            message: "Payment processed successfully".to_string(),
+            message,
             transaction_id: format!("TXN_{}", chrono::Utc::now().timestamp_millis()),
-            card_no: self.extract_card_number(card_data_raw),
-            amount_paid: amount,
-            balance_after: 95000.0, // Simulated balance
+            card_no: card.card_no,
+            amount_paid: request.amount,
+            balance_after: deduction.balance_after,
             timestamp: chrono::Utc::now().to_rfc3339(),
             gate_name: "SIMULATED_GATE".to_string(), // Provide actual gate name if available
+            reader_id: self.id.clone(),
+            card_type,
+            mid: self.mid.clone(),
+            tid: self.tid.clone(),
+            transaction_counter: deduction.counter,
+            transaction_data: deduction.transaction_data.iter().map(|b| format!("{:02X}", b)).collect(),
+            tariff_items: Vec::new(),
+            dispute: deduction.dispute,
         })
     }
 
-    fn extract_card_number(&self, raw_data: &str) -> String {
-        // Extract meaningful card number from raw data
-        // This depends on your card data format
-        if let Some(card_part) = raw_data.split('_').nth(1) {
-            card_part.replace("RFID_C", "").replace("_B", "")
-        } else {
-            "UNKNOWN".to_string()
+}
+
+impl CardLink for RFIDReader {
+    fn transmit(&mut self, apdu: &[u8]) -> Result<Vec<u8>, String> {
+        // TODO: Wrap the APDU in the reader's pass-through frame, write it to the
+        // port and unwrap the card's response.
+
+        // Simulated card with a 100,000 balance: SELECT succeeds for the application
+        // of the issuer its number belongs to, a read returns the balance and a debit
+        // (4-byte amount) returns the balance after it, the transaction counter and a
+        // transaction record.
+        const SIM_BALANCE: u32 = 100_000;
+        let mut response = match apdu {
+            [_, 0xA4, _, _, _, aid @ ..] => {
+                if issuer_handler::simulated_aid(&self.sim_card_no) != Some(aid) {
+                    return Ok(vec![0x6A, 0x82]);
+                }
+                Vec::new()
+            }
+            [_, _, _, _, 0x04] => SIM_BALANCE.to_be_bytes().to_vec(),
+            [_, _, _, _, 0x04, a, b, c, d, ..] => {
+                // Simulate processing time; this runs on the blocking pool (see `with_reader`)
+                std::thread::sleep(Duration::from_millis(500));
+                let amount = u32::from_be_bytes([*a, *b, *c, *d]);
+                self.sim_transaction_counter += 1;
+                let mut response = SIM_BALANCE.saturating_sub(amount).to_be_bytes().to_vec();
+                response.extend_from_slice(&self.sim_transaction_counter.to_be_bytes());
+                response.extend_from_slice(&[*a, *b, *c, *d]);
+                response.extend_from_slice(&chrono::Utc::now().timestamp().to_be_bytes());
+                response
+            }
+            _ => return Ok(vec![0x6D, 0x00]),
+        };
+        response.extend_from_slice(&[0x90, 0x00]);
+        Ok(response)
+    }
+
+    fn is_simulated(&self) -> bool {
+        // Until `transmit` talks to the port, every card is the simulated one above.
+        true
+    }
+}
+
+fn extract_card_number(raw_data: &str) -> String {
+    // Extract meaningful card number from raw data
+    // This depends on your card data format
+    if let Some(card_part) = raw_data.split('_').nth(1) {
+        card_part.replace("RFID_C", "").replace("_B", "")
+    } else {
+        "UNKNOWN".to_string()
+    }
+}
+
+
+/// Commands a reader actor handles one at a time, between polls.
+enum ReaderCommand {
+    SetPolling(bool),
+    Payment {
+        request: PaymentRequest,
+        reply: oneshot::Sender<Result<PaymentResultDetails, String>>,
+    },
+}
+
+/// What reader actors report back to the service. Unbounded so an actor never
+/// waits on the service, which may itself be waiting for that actor to close.
+#[derive(Debug)]
+enum ReaderEvent {
+    Card(CardEvent),
+    Faulted { reader_id: String, error: String },
+}
+
+/// Runs `f` on the blocking pool with the reader moved in and hands the reader
+/// back, so serial I/O never stalls a runtime worker. `None` if `f` panicked.
+async fn with_reader<T: Send + 'static>(
+    mut reader: RFIDReader,
+    f: impl FnOnce(&mut RFIDReader) -> T + Send + 'static,
+) -> Option<(RFIDReader, T)> {
+    tokio::task::spawn_blocking(move || {
+        let result = f(&mut reader);
+        (reader, result)
+    })
+    .await
+    .ok()
+}
+
+/// The task that owns one reader's port. Polling and payments go through its
+/// command queue, so they can never interleave on the wire. It ends when its
+/// handle is dropped, closing the port, or on a serial I/O error.
+async fn run_reader(
+    mut reader: RFIDReader,
+    mut commands: mpsc::Receiver<ReaderCommand>,
+    events: mpsc::UnboundedSender<ReaderEvent>,
+    debounce: Duration,
+    stagger: u32,
+) {
+    let reader_id = reader.id.clone();
+    log::info!("RFID reader task started for reader {}", reader_id);
+    let mut polling = false;
+    let mut presence = CardPresence::default();
+    let mut sim_counter = stagger; // For simulation
+    let mut ticker = tokio::time::interval(POLL_INTERVAL);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        tokio::select! {
+            command = commands.recv() => match command {
+                None => break,
+                Some(ReaderCommand::SetPolling(on)) => polling = on,
+                Some(ReaderCommand::Payment { request, reply }) => {
+                    let Some((returned, result)) = with_reader(reader, move |r| r.process_payment(&request)).await else {
+                        let _ = events.send(ReaderEvent::Faulted { reader_id: reader_id.clone(), error: "Payment task panicked".to_string() });
+                        return;
+                    };
+                    reader = returned;
+                    let _ = reply.send(result);
+                }
+            },
+            _ = ticker.tick(), if polling => {
+                // Simulate a card resting on the reader for ~1 second every ~5 seconds (remove in production)
+                sim_counter += 1;
+                let simulated_card = (sim_counter % 50 >= 40).then(|| format!("SIM_6032{:012}", sim_counter / 50));
+
+                let Some((returned, polled)) = with_reader(reader, |r| r.poll_for_card()).await else {
+                    let _ = events.send(ReaderEvent::Faulted { reader_id: reader_id.clone(), error: "Polling task panicked".to_string() });
+                    return;
+                };
+                reader = returned;
+                // An I/O error ends the actor; the supervisor re-opens the reader
+                // once the device is back.
+                let polled = match polled {
+                    Ok(polled) => polled,
+                    Err(error) => {
+                        log::error!("RFID: Reader {} failed: {}", reader_id, error);
+                        let _ = events.send(ReaderEvent::Faulted { reader_id: reader_id.clone(), error });
+                        return;
+                    }
+                };
+                for event in presence.update(&reader_id, polled.or(simulated_card), debounce, std::time::Instant::now()) {
+                    let _ = events.send(ReaderEvent::Card(event));
+                }
+            }
         }
     }
+
+    log::info!("RFID reader task finished for reader {}", reader_id);
 }
 
-// Improved manager with better thread safety
+/// The service's handle on one running reader actor.
+struct ReaderHandle {
+    port_name: String,
+    commands: mpsc::Sender<ReaderCommand>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl ReaderHandle {
+    /// Stops the actor and waits until it has released the port.
+    async fn close(self) {
+        drop(self.commands);
+        if tokio::time::timeout(CLOSE_TIMEOUT, self.task).await.is_err() {
+            log::warn!("RFID: Reader on {} did not close within {:?}", self.port_name, CLOSE_TIMEOUT);
+        }
+    }
+}
+
+/// Per-reader outcome of opening the configured readers, by reader id.
+type ReaderOpenResults = Vec<(String, Result<(), String>)>;
+
+/// Requests the RFID service handles in order.
+enum ServiceCommand {
+    /// Closes every reader and opens the ones in the config.
+    Open { config: AppConfig, reply: oneshot::Sender<ReaderOpenResults> },
+    SetPolling { on: bool, reply: oneshot::Sender<()> },
+    Payment {
+        reader_id: Option<String>,
+        request: PaymentRequest,
+        reply: oneshot::Sender<Result<PaymentResultDetails, String>>,
+    },
+    Status { reply: oneshot::Sender<RFIDStatus> },
+    CheckHealth { config: AppConfig, ports: Option<Vec<serialport::SerialPortInfo>> },
+}
+
+/// Snapshot of the readers for status queries.
+#[derive(Clone, Debug)]
+pub struct RFIDStatus {
+    /// Open readers by id, with the port each one is on.
+    pub readers: BTreeMap<String, String>,
+    pub reader_status: BTreeMap<String, DeviceStatus>,
+    pub polling: bool,
+}
+
+/// How often an actor polls its reader for a card.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long a closing reader gets to release its port.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Per-request timeouts for callers of the service.
+const OPEN_TIMEOUT: Duration = Duration::from_secs(15);
+/// For payments this only bounds handing the payment to its reader; after that the
+/// result is awaited however long it takes, warning every time this much passes.
+const PAYMENT_TIMEOUT: Duration = Duration::from_secs(10);
+const STATUS_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How many recent taps are remembered for routing a payment back to its reader.
+const RECENT_TAPS_LIMIT: usize = 32;
+
+/// How often the supervisor checks open readers and retries disconnected ones.
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Picks the device path for a reader: the USB adapter matching its VID/PID/serial
+/// number if one is set and present, otherwise the configured port. A configured
+/// port missing from a successful enumeration fails here, since opening it cannot
+/// be trusted to notice.
+fn resolve_reader_port(reader: &ReaderConfig, ports: Option<&[serialport::SerialPortInfo]>) -> Result<String, String> {
+    if reader.has_usb_match() {
+        if let Some(port) = ports.and_then(|ports| ports.iter().find(|p| reader.matches_usb(p))) {
+            return Ok(port.port_name.clone());
+        }
+        if reader.port.trim().is_empty() {
+            return Err("No USB serial adapter matches the reader".to_string());
+        }
+    }
+    if ports.is_some_and(|ports| !config_handler::is_port_listed(ports, &reader.port)) {
+        return Err(format!("Serial port {} is not present", reader.port));
+    }
+    Ok(reader.port.clone())
+}
+
+fn available_ports() -> Option<Vec<serialport::SerialPortInfo>> {
+    serialport::available_ports()
+        .map_err(|e| log::debug!("RFID: Could not enumerate serial ports: {}", e))
+        .ok()
+}
+
+/// Opens one reader on the blocking pool, resolving its device path first.
+async fn open_reader(reader_config: ReaderConfig, ports: Option<Vec<serialport::SerialPortInfo>>) -> Result<RFIDReader, String> {
+    tokio::task::spawn_blocking(move || {
+        let port = resolve_reader_port(&reader_config, ports.as_deref())?;
+        let mut reader = RFIDReader::new(&reader_config.id, &port, reader_config.baud_rate);
+        reader.init_port()?;
+        Ok(reader)
+    })
+    .await
+    .map_err(|e| format!("Reader open task failed: {}", e))?
+}
+
+/// State owned by the RFID service task: the reader actors, tap history for
+/// payment routing and the supervision status. Only that task touches it.
 pub struct RFIDManager {
-    reader: Arc<Mutex<Option<RFIDReader>>>,
-    is_polling: Arc<AtomicBool>,
-    card_event_sender: Arc<Mutex<Option<mpsc::Sender<String>>>>,
-    polling_handle: Arc<Mutex<Option<tokio::task::JoinHandle<()>>>>,
+    app_handle: tauri::AppHandle,
+    readers: BTreeMap<String, ReaderHandle>,
+    polling: bool,
+    card_debounce: Duration,
+    events: mpsc::UnboundedSender<ReaderEvent>,
+    recent_taps: VecDeque<CardTap>,
+    /// Last reported state of every configured reader, by id. Only readers in here
+    /// are supervised, so nothing is opened before the lane initializes its readers.
+    reader_status: BTreeMap<String, DeviceStatus>,
 }
 
 impl RFIDManager {
-    pub fn new() -> Self {
-        RFIDManager {
-            reader: Arc::new(Mutex::new(None)),
-            is_polling: Arc::new(AtomicBool::new(false)),
-            card_event_sender: Arc::new(Mutex::new(None)),
-            polling_handle: Arc::new(Mutex::new(None)),
+    fn spawn_actor(&mut self, reader: RFIDReader, stagger: u32) {
+        let reader_id = reader.id.clone();
+        let port_name = reader.port_name.clone();
+        let (commands, receiver) = mpsc::channel(8);
+        if self.polling {
+            let _ = commands.try_send(ReaderCommand::SetPolling(true));
         }
+        let task = tokio::spawn(run_reader(reader, receiver, self.events.clone(), self.card_debounce, stagger));
+        self.readers.insert(reader_id, ReaderHandle { port_name, commands, task });
     }
 
-    pub fn is_polling(&self) -> bool {
-        self.is_polling.load(Ordering::Acquire)
+    async fn close_all(&mut self) {
+        for (_, handle) in std::mem::take(&mut self.readers) {
+            handle.close().await;
+        }
     }
 
-    pub fn stop_polling(&self) -> Result<(), String> {
-        if !self.is_polling() {
-            return Ok(());
+    /// Closes every reader and opens the ones in `config`; readers that fail to
+    /// open are left out and retried by the supervisor. Ports are released first
+    /// since the new settings may name the same devices.
+    async fn open_readers(&mut self, config: AppConfig) -> ReaderOpenResults {
+        self.close_all().await;
+        self.card_debounce = Duration::from_millis(config.card_debounce_ms);
+        self.reader_status.retain(|id, _| config.emoney_readers.iter().any(|r| &r.id == id));
+        let ports = tokio::task::spawn_blocking(available_ports).await.ok().flatten();
+
+        let mut results = Vec::new();
+        for (index, reader_config) in config.emoney_readers.into_iter().enumerate() {
+            let reader_id = reader_config.id.clone();
+            let result = match open_reader(reader_config, ports.clone()).await {
+                Ok(reader) => {
+                    let port = reader.port_name.clone();
+                    self.spawn_actor(reader, index as u32 * 25);
+                    self.set_status(&reader_id, DeviceStatus::Connected, Some(port), "Reader initialized");
+                    Ok(())
+                }
+                Err(e) => {
+                    self.set_status(&reader_id, DeviceStatus::Disconnected, None, &e);
+                    Err(e)
+                }
+            };
+            results.push((reader_id, result));
         }
+        results
+    }
 
-        // Signal stop
-        self.is_polling.store(false, Ordering::Release);
-        
-        // Close the sender to signal the event listener to stop
-        if let Ok(mut sender_guard) = self.card_event_sender.lock() {
-            *sender_guard = None;
+    fn set_polling(&mut self, on: bool) {
+        self.polling = on;
+        for (reader_id, handle) in &self.readers {
+            if handle.commands.try_send(ReaderCommand::SetPolling(on)).is_err() {
+                log::warn!("RFID: Reader {} did not take the polling change", reader_id);
+            }
+        }
+        log::info!("RFID polling {}", if on { "started" } else { "stopped" });
+    }
+
+    /// Records a reader's state and emits `device_status_changed` when it differs
+    /// from the last one reported.
+    fn set_status(&mut self, reader_id: &str, status: DeviceStatus, port: Option<String>, message: &str) {
+        if self.reader_status.insert(reader_id.to_string(), status) == Some(status) {
+            return;
+        }
+        log::info!("RFID: Reader {} is now {:?} ({})", reader_id, status, message);
+        let event = DeviceStatusEvent {
+            device: "emoney_reader",
+            reader_id: reader_id.to_string(),
+            status,
+            port,
+            message: message.to_string(),
+        };
+        if let Err(e) = self.app_handle.emit("device_status_changed", &event) {
+            log::error!("Failed to emit device_status_changed event: {}", e);
+        }
+    }
+
+    /// One supervisor pass: drops readers whose device is gone and tries to
+    /// re-open the supervised readers that are not open.
+    async fn check_health(&mut self, config: AppConfig, ports: Option<Vec<serialport::SerialPortInfo>>) {
+        for (index, reader_config) in config.emoney_readers.into_iter().enumerate() {
+            if !self.reader_status.contains_key(&reader_config.id) {
+                continue;
+            }
+            let reader_id = reader_config.id.clone();
+            if let Some(handle) = self.readers.get(&reader_id) {
+                let port = handle.port_name.clone();
+                let gone = ports.as_ref().is_some_and(|ports| !config_handler::is_port_listed(ports, &port));
+                if gone {
+                    if let Some(handle) = self.readers.remove(&reader_id) {
+                        handle.close().await;
+                    }
+                    self.set_status(&reader_id, DeviceStatus::Disconnected, Some(port.clone()), &format!("Serial port {} disappeared", port));
+                }
+                continue;
+            }
+            match open_reader(reader_config, ports.clone()).await {
+                Ok(reader) => {
+                    let port = reader.port_name.clone();
+                    self.spawn_actor(reader, index as u32 * 25);
+                    self.set_status(&reader_id, DeviceStatus::Connected, Some(port), "Reader reconnected");
+                }
+                Err(e) => log::debug!("RFID: Reader {} still unavailable: {}", reader_id, e),
+            }
+        }
+    }
+
+    /// Picks the reader a payment should run on: the one asked for, else the one
+    /// that most recently saw this card, else the only reader on the lane.
+    fn reader_for_payment(&self, reader_id: Option<&str>, card_data: &str) -> Result<&ReaderHandle, String> {
+        if self.readers.is_empty() {
+            return Err("RFID Reader not initialized. Call initialize_rfid_reader_command first.".to_string());
         }
 
-        // Wait for polling task to finish
-        if let Ok(mut handle_guard) = self.polling_handle.lock() {
-            if let Some(handle) = handle_guard.take() {
-                handle.abort();
+        let tapped_on = self.recent_taps.iter()
+            .rev()
+            .find(|tap| tap.card_data == card_data)
+            .map(|tap| tap.reader_id.clone());
+        let id = match (reader_id, tapped_on) {
+            (Some(id), _) => id.to_string(),
+            (None, Some(id)) => id,
+            (None, None) if self.readers.len() == 1 => self.readers.keys().next().cloned().unwrap_or_default(),
+            (None, None) => return Err("Several readers are configured; specify which reader the card is on".to_string()),
+        };
+        self.readers.get(&id)
+            .ok_or_else(|| format!("Reader '{}' is not initialized", id))
+    }
+
+    /// Hands the payment to its reader's queue; the reader answers the caller directly
+    /// so the service is free for other requests while the card is charged.
+    fn route_payment(
+        &self,
+        reader_id: Option<String>,
+        request: PaymentRequest,
+        reply: oneshot::Sender<Result<PaymentResultDetails, String>>,
+    ) {
+        let handle = match self.reader_for_payment(reader_id.as_deref(), &request.card_data) {
+            Ok(handle) => handle,
+            Err(e) => {
+                let _ = reply.send(Err(e));
+                return;
+            }
+        };
+        if let Err(e) = handle.commands.try_send(ReaderCommand::Payment { request, reply }) {
+            let message = match &e {
+                mpsc::error::TrySendError::Full(_) => "Reader is busy; try again",
+                mpsc::error::TrySendError::Closed(_) => "Reader is disconnected",
+            };
+            if let ReaderCommand::Payment { reply, .. } = e.into_inner() {
+                let _ = reply.send(Err(message.to_string()));
             }
         }
+    }
+
+    fn handle_event(&mut self, event: ReaderEvent) {
+        match event {
+            ReaderEvent::Faulted { reader_id, error } => {
+                let port = self.readers.remove(&reader_id).map(|handle| handle.port_name);
+                self.set_status(&reader_id, DeviceStatus::Disconnected, port, &error);
+            }
+            ReaderEvent::Card(CardEvent::Removed(tap)) => {
+                log::info!("Card removed from reader {}: {}", tap.reader_id, tap.card_data);
+                if let Err(e) = self.app_handle.emit("card_removed", &tap.payload()) {
+                    log::error!("Failed to emit card_removed event: {}", e);
+                }
+            }
+            ReaderEvent::Card(CardEvent::Present(tap)) => {
+                log::info!("Card tapped on reader {}: {}", tap.reader_id, tap.card_data);
+                if self.recent_taps.len() == RECENT_TAPS_LIMIT {
+                    self.recent_taps.pop_front();
+                }
+                self.recent_taps.push_back(tap.clone());
 
-        log::info!("RFID polling stopped");
+                if let Err(e) = self.app_handle.emit("card_present", &tap.payload()) {
+                    log::error!("Failed to emit card_present event: {}", e);
+                }
+
+                // Kept for screens still listening to the raw tap event.
+                let event_payload = EventPayload {
+                    message: tap.card_data.clone(),
+                    data: Some(tap.card_data),
+                    reader_id: tap.reader_id,
+                };
+                if let Err(e) = self.app_handle.emit("rfid_card_tapped", &event_payload) {
+                    log::error!("Failed to emit rfid_card_tapped event: {}", e);
+                }
+            }
+        }
+    }
+
+    fn status(&self) -> RFIDStatus {
+        RFIDStatus {
+            readers: self.readers.iter().map(|(id, handle)| (id.clone(), handle.port_name.clone())).collect(),
+            reader_status: self.reader_status.clone(),
+            polling: self.polling,
+        }
+    }
+
+    async fn handle_command(&mut self, command: ServiceCommand) {
+        match command {
+            ServiceCommand::Open { config, reply } => {
+                let results = self.open_readers(config).await;
+                let _ = reply.send(results);
+            }
+            ServiceCommand::SetPolling { on, reply } => {
+                self.set_polling(on);
+                let _ = reply.send(());
+            }
+            ServiceCommand::Payment { reader_id, request, reply } => {
+                self.route_payment(reader_id, request, reply);
+            }
+            ServiceCommand::Status { reply } => {
+                let _ = reply.send(self.status());
+            }
+            ServiceCommand::CheckHealth { config, ports } => self.check_health(config, ports).await,
+        }
+    }
+
+    async fn run(mut self, mut commands: mpsc::Receiver<ServiceCommand>, mut events: mpsc::UnboundedReceiver<ReaderEvent>) {
+        log::info!("RFID service started");
+        loop {
+            tokio::select! {
+                command = commands.recv() => match command {
+                    Some(command) => self.handle_command(command).await,
+                    None => break,
+                },
+                Some(event) = events.recv() => self.handle_event(event),
+            }
+        }
+        self.close_all().await;
+        log::info!("RFID service finished");
+    }
+}
+
+/// Handle on the RFID service task, managed as Tauri state. Every request goes
+/// through the service's queue and waits at most its own timeout for the answer.
+#[derive(Clone)]
+pub struct RFIDService {
+    commands: mpsc::Sender<ServiceCommand>,
+}
+
+impl RFIDService {
+    pub fn spawn(app_handle: tauri::AppHandle) -> Self {
+        let (commands, command_receiver) = mpsc::channel(32);
+        let (events, event_receiver) = mpsc::unbounded_channel();
+        let manager = RFIDManager {
+            app_handle,
+            readers: BTreeMap::new(),
+            polling: false,
+            card_debounce: Duration::ZERO,
+            events,
+            recent_taps: VecDeque::new(),
+            reader_status: BTreeMap::new(),
+        };
+        tauri::async_runtime::spawn(manager.run(command_receiver, event_receiver));
+        RFIDService { commands }
+    }
+
+    async fn request<T>(&self, timeout: Duration, make: impl FnOnce(oneshot::Sender<T>) -> ServiceCommand) -> Result<T, String> {
+        let (reply, response) = oneshot::channel();
+        let exchange = async {
+            self.commands.send(make(reply)).await
+                .map_err(|_| "RFID service is not running".to_string())?;
+            response.await
+                .map_err(|_| "RFID service dropped the request".to_string())
+        };
+        tokio::time::timeout(timeout, exchange).await
+            .map_err(|_| format!("RFID service did not answer within {:?}", timeout))?
+    }
+
+    /// Closes every reader and opens the ones in `config`. Returns the result per reader id.
+    pub async fn open_readers(&self, config: &AppConfig) -> Result<ReaderOpenResults, String> {
+        let config = config.clone();
+        self.request(OPEN_TIMEOUT, |reply| ServiceCommand::Open { config, reply }).await
+    }
+
+    pub async fn set_polling(&self, on: bool) -> Result<(), String> {
+        self.request(STATUS_TIMEOUT, |reply| ServiceCommand::SetPolling { on, reply }).await
+    }
+
+    pub async fn status(&self) -> Result<RFIDStatus, String> {
+        self.request(STATUS_TIMEOUT, |reply| ServiceCommand::Status { reply }).await
+    }
+
+    /// Once the reader has the payment, the card may be charged at any moment. Giving
+    /// up on the result then would leave a charged card unjournaled and invite the
+    /// operator to charge it again, so it is awaited until the reader answers.
+    pub async fn process_payment(&self, reader_id: Option<String>, request: PaymentRequest) -> Result<PaymentResultDetails, String> {
+        let (reply, mut response) = oneshot::channel();
+        tokio::time::timeout(PAYMENT_TIMEOUT, self.commands.send(ServiceCommand::Payment { reader_id, request, reply })).await
+            .map_err(|_| format!("RFID service did not take the payment within {:?}", PAYMENT_TIMEOUT))?
+            .map_err(|_| "RFID service is not running".to_string())?;
+        loop {
+            match tokio::time::timeout(PAYMENT_TIMEOUT, &mut response).await {
+                Ok(result) => return result.map_err(|_| "RFID service dropped the payment".to_string())?,
+                Err(_) => log::warn!("RFID: Payment still in progress after {:?}; waiting for the reader", PAYMENT_TIMEOUT),
+            }
+        }
+    }
+
+    /// Re-opens every reader with `config`, keeping polling as it was. If any
+    /// reader fails to open, the error lists them.
+    pub async fn restart_readers(&self, config: &AppConfig) -> Result<(), String> {
+        let failures: Vec<String> = self.open_readers(config).await?
+            .into_iter()
+            .filter_map(|(id, result)| result.err().map(|e| format!("{}: {}", id, e)))
+            .collect();
+        if !failures.is_empty() {
+            return Err(format!("Failed to open reader(s) {}", failures.join("; ")));
+        }
+        log::info!("RFID readers re-initialized: {:?}", config.emoney_readers.iter().map(|r| &r.id).collect::<Vec<_>>());
         Ok(())
     }
+
+    async fn check_health(&self, config: AppConfig, ports: Option<Vec<serialport::SerialPortInfo>>) -> Result<(), String> {
+        self.commands.send(ServiceCommand::CheckHealth { config, ports }).await
+            .map_err(|_| "RFID service is not running".to_string())
+    }
 }
 
-pub type RFIDManagerState = Arc<Mutex<RFIDManager>>;
+pub type RFIDManagerState = RFIDService;
+
+/// How long an open check waits for a port before giving up on it.
+const OPEN_CHECK_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// A serial port as listed for the settings screen. This only lists ports; it does
+/// not identify e-money readers. That needs the reader handshake, which
+/// `RFIDReader::init_port` does not implement yet.
+#[derive(Clone, serde::Serialize, Debug)]
+pub struct SerialPortCandidate {
+    pub port_name: String,
+    /// "usb", "pci", "bluetooth" or "unknown".
+    pub port_type: &'static str,
+    pub usb_vid: Option<u16>,
+    pub usb_pid: Option<u16>,
+    pub manufacturer: Option<String>,
+    pub product: Option<String>,
+    pub serial_number: Option<String>,
+    /// Reader already using this port; such ports are not opened.
+    pub in_use_by: Option<String>,
+    /// Set when asked for: whether the port could be opened.
+    pub open_check: Option<PortOpenCheck>,
+    /// Reader settings for using this port, pinned to the adapter's USB identity when
+    /// it has one. A starting point for the form, not a sign that a reader is there.
+    pub suggested_reader: Option<ReaderConfig>,
+}
+
+#[derive(Clone, serde::Serialize, Debug)]
+pub struct PortOpenCheck {
+    pub opens: bool,
+    pub message: String,
+}
+
+impl SerialPortCandidate {
+    fn from_info(info: serialport::SerialPortInfo) -> Self {
+        let mut candidate = SerialPortCandidate {
+            port_name: info.port_name,
+            port_type: "unknown",
+            usb_vid: None,
+            usb_pid: None,
+            manufacturer: None,
+            product: None,
+            serial_number: None,
+            in_use_by: None,
+            open_check: None,
+            suggested_reader: None,
+        };
+        match info.port_type {
+            serialport::SerialPortType::UsbPort(usb) => {
+                candidate.port_type = "usb";
+                candidate.usb_vid = Some(usb.vid);
+                candidate.usb_pid = Some(usb.pid);
+                candidate.manufacturer = usb.manufacturer;
+                candidate.product = usb.product;
+                candidate.serial_number = usb.serial_number;
+            }
+            serialport::SerialPortType::PciPort => candidate.port_type = "pci",
+            serialport::SerialPortType::BluetoothPort => candidate.port_type = "bluetooth",
+            serialport::SerialPortType::Unknown => {}
+        }
+        candidate
+    }
+
+    fn reader_settings(&self, baud_rate: u32) -> ReaderConfig {
+        ReaderConfig {
+            id: String::new(),
+            port: self.port_name.clone(),
+            baud_rate,
+            usb_vid: self.usb_vid,
+            usb_pid: self.usb_pid,
+            usb_serial: self.serial_number.clone(),
+        }
+    }
+}
+
+/// Only opens the port; nothing is sent to it.
+fn check_port_opens(port_name: &str, baud_rate: u32) -> Result<(), String> {
+    serialport::new(port_name, baud_rate)
+        .timeout(OPEN_CHECK_TIMEOUT)
+        .open()
+        .map(drop)
+        .map_err(|e| format!("Could not open {}: {}", port_name, e))
+}
 
 #[tauri::command]
 pub async fn initialize_rfid_reader_command(
@@ -168,135 +966,52 @@ pub async fn initialize_rfid_reader_command(
     rfid_manager_state: State<'_, RFIDManagerState>,
 ) -> Result<String, String> {
     let config = config_state.0.lock()
-        .map_err(|_| "Failed to acquire config lock")?;
-    
-    let manager = rfid_manager_state.lock()
-        .map_err(|_| "Failed to acquire manager lock")?;
-
-    log::info!(
-        "Initializing RFID reader - Port: {}, Baud: {}", 
-        config.emoney_reader_port, config.emoney_baud_rate
-    );
-
-    let mut reader = RFIDReader::new(&config.emoney_reader_port, config.emoney_baud_rate);
-    reader.init_port()?;
-
-    // Store the initialized reader
-    if let Ok(mut reader_guard) = manager.reader.lock() {
-        *reader_guard = Some(reader);
-    } else {
-        return Err("Failed to store reader instance".to_string());
+        .map_err(|_| "Failed to acquire config lock")?
+        .clone();
+
+    for reader in &config.emoney_readers {
+        log::info!("Initializing RFID reader {} - Port: {}, USB: {:?}/{:?}/{:?}, Baud: {}",
+            reader.id, reader.port, reader.usb_vid, reader.usb_pid, reader.usb_serial, reader.baud_rate);
+    }
+
+    // A lane with one working reader can still take payments; report the rest.
+    let results = rfid_manager_state.open_readers(&config).await?;
+    let ready: Vec<&String> = results.iter()
+        .filter(|(_, result)| result.is_ok())
+        .map(|(id, _)| id)
+        .collect();
+    let failures: Vec<String> = results.iter()
+        .filter_map(|(id, result)| result.as_ref().err().map(|e| format!("{}: {}", id, e)))
+        .collect();
+    if failures.len() == results.len() {
+        return Err(format!("No RFID reader could be initialized ({})", failures.join("; ")));
+    }
+    if !failures.is_empty() {
+        log::warn!("Some RFID readers failed to initialize: {}", failures.join("; "));
+        return Ok(format!("RFID readers {:?} ready; failed: {}", ready, failures.join("; ")));
     }
 
-    log::info!("RFID Reader initialized successfully");
+    log::info!("RFID Readers initialized successfully: {:?}", ready);
     Ok("RFID Reader initialized and ready for use".to_string())
 }
 
 #[tauri::command]
 pub async fn start_rfid_detection_command(
-    app_handle: tauri::AppHandle,
-    config_state: State<'_, AppConfigState>,
     rfid_manager_state: State<'_, RFIDManagerState>,
 ) -> Result<(), String> {
-    let manager = rfid_manager_state.lock()
-        .map_err(|_| "Failed to acquire manager lock")?;
+    let status = rfid_manager_state.status().await?;
 
-    if manager.is_polling() {
+    if status.polling {
         log::info!("RFID detection already running");
         return Ok(());
     }
 
-    // Ensure reader is initialized
-    {
-        let reader_guard = manager.reader.lock()
-            .map_err(|_| "Failed to acquire reader lock")?;
-        if reader_guard.is_none() {
-            return Err("RFID Reader not initialized. Call initialize_rfid_reader_command first.".to_string());
-        }
-    }
-
-    let config = config_state.0.lock()
-        .map_err(|_| "Failed to acquire config lock")?
-        .clone();
-
-    // Set up communication channel
-    let (tx, mut rx) = mpsc::channel::<String>(32);
-    
-    // Store sender in manager
-    if let Ok(mut sender_guard) = manager.card_event_sender.lock() {
-        *sender_guard = Some(tx.clone());
-    }
-
-    // Set polling flag
-    manager.is_polling.store(true, Ordering::Release);
-
-    // Clone necessary data for tasks
-    let reader_arc = Arc::clone(&manager.reader);
-    let is_polling_arc = Arc::clone(&manager.is_polling);
-    let app_handle_clone = app_handle.clone();
-
-    // Spawn polling task
-    let polling_handle = tokio::spawn(async move {
-        log::info!("RFID polling task started");
-        let poll_interval = Duration::from_millis(100); // Adjust as needed
-        let mut sim_counter = 0u32; // For simulation
-
-        while is_polling_arc.load(Ordering::Acquire) {
-            // Simulate card detection (remove in production)
-            sim_counter += 1;
-            if sim_counter % 50 == 0 { // Every ~5 seconds
-                let simulated_card = format!("SIM_CARD_{:04X}", sim_counter);
-                log::debug!("Simulated card detected: {}", simulated_card);
-                
-                if tx.send(simulated_card).await.is_err() {
-                    log::warn!("Failed to send card data - receiver may have been dropped");
-                    break;
-                }
-            }
-
-            // TODO: Replace simulation with actual polling
-            // if let Ok(mut reader_guard) = reader_arc.lock() {
-            //     if let Some(ref mut reader) = *reader_guard {
-            //         if let Some(card_data) = reader.poll_for_card() {
-            //             log::debug!("Card detected: {}", card_data);
-            //             if tx.send(card_data).await.is_err() {
-            //                 log::warn!("Failed to send card data");
-            //                 break;
-            //             }
-            //         }
-            //     }
-            // }
-
-            tokio::time::sleep(poll_interval).await;
-        }
-
-        log::info!("RFID polling task finished");
-    });
-
-    // Store polling handle
-    if let Ok(mut handle_guard) = manager.polling_handle.lock() {
-        *handle_guard = Some(polling_handle);
+    // Ensure at least one reader is initialized
+    if status.readers.is_empty() {
+        return Err("RFID Reader not initialized. Call initialize_rfid_reader_command first.".to_string());
     }
 
-    // Spawn event emission task
-    tokio::spawn(async move {
-        log::info!("RFID event listener started");
-        
-        while let Some(card_data) = rx.recv().await {
-            log::info!("Card tapped: {}", card_data);
-            
-            let event_payload = EventPayload {
-                message: card_data.clone(),
-                data: Some(card_data),
-            };
-
-            if let Err(e) = app_handle_clone.emit("rfid_card_tapped", &event_payload) {
-                log::error!("Failed to emit rfid_card_tapped event: {}", e);
-            }
-        }
-        
-        log::info!("RFID event listener finished");
-    });
+    rfid_manager_state.set_polling(true).await?;
 
     log::info!("RFID detection started successfully");
     Ok(())
@@ -306,51 +1021,273 @@ pub async fn start_rfid_detection_command(
 pub async fn stop_rfid_detection_command(
     rfid_manager_state: State<'_, RFIDManagerState>,
 ) -> Result<(), String> {
-    let manager = rfid_manager_state.lock()
-        .map_err(|_| "Failed to acquire manager lock")?;
-    
-    manager.stop_polling()?;
-    Ok(())
+    rfid_manager_state.set_polling(false).await
 }
 
 #[tauri::command]
 pub async fn rfid_payment_command(
+    app_handle: tauri::AppHandle,
     rfid_manager_state: State<'_, RFIDManagerState>,
-    card_data: String, 
-    amount: f64
+    config_state: State<'_, AppConfigState>,
+    db_state: State<'_, DatabaseState>,
+    card_data: String,
+    transaction_id: String,
+    reader_id: Option<String>,
 ) -> Result<PaymentResultDetails, String> {
-    let manager = rfid_manager_state.lock()
-        .map_err(|_| "Failed to acquire manager lock")?;
-
-    let mut reader_guard = manager.reader.lock()
-        .map_err(|_| "Failed to acquire reader lock")?;
+    let config = config_state.0.lock()
+        .map_err(|_| "Failed to acquire config lock")?
+        .clone();
+    let (gate_name, accepted_issuers) = (config.gate_name.clone(), config.enabled_issuers.clone());
+    // The amount shown for the transaction, checked against what CGS returned for it;
+    // the UI never says what to charge.
+    let tariff_state = app_handle.state::<TariffState>();
+    let tariff = tariff_handler::charge_for_quote(&config, &tariff_state, &transaction_id)?;
+    tariff.check()?;
+    let amount = tariff.total;
 
-    match reader_guard.as_mut() {
-        Some(reader) => {
-            reader.process_payment(&card_data, amount)
+    // A stolen card is refused before the reader is asked to touch it.
+    let card_no = CardIdentity::from_raw(&card_data).card_no;
+    let result = match blacklist_handler::ensure_not_blacklisted(&db_state, BlacklistKind::Card, &card_no) {
+        Ok(()) => {
+            let request = PaymentRequest { card_data, amount, accepted_issuers };
+            rfid_manager_state.process_payment(reader_id.clone(), request).await
+                .map(|payment| PaymentResultDetails { tariff_items: tariff.items, ..payment })
         }
-        None => {
-            Err("RFID Reader not initialized. Call initialize_rfid_reader_command first.".to_string())
+        Err(e) => Err(e),
+    };
+
+    if let Ok(payment) = &result {
+        tariff_state.clear(&transaction_id);
+        // The card has been charged; a journal failure must not turn that into an error.
+        if let Err(e) = settlement_handler::journal_payment(&db_state, &gate_name, payment) {
+            log::error!("{}", e);
         }
     }
+
+    let details = match &result {
+        Ok(payment) => serde_json::to_value(payment).unwrap_or_default(),
+        Err(e) => serde_json::json!({ "success": false, "amount": amount, "reader_id": reader_id, "error": e }),
+    };
+    app_handle.state::<AuditLogState>().record(AuditKind::Payment, "system", &gate_name, details);
+    result
 }
 
 #[tauri::command]
 pub async fn get_rfid_status_command(
     rfid_manager_state: State<'_, RFIDManagerState>,
 ) -> Result<serde_json::Value, String> {
-    let manager = rfid_manager_state.lock()
-        .map_err(|_| "Failed to acquire manager lock")?;
+    let status = rfid_manager_state.status().await?;
 
-    let reader_initialized = {
-        let reader_guard = manager.reader.lock()
-            .map_err(|_| "Failed to acquire reader lock")?;
-        reader_guard.is_some()
-    };
+    let readers: Vec<&String> = status.readers.keys().collect();
+    let reader_initialized = !readers.is_empty();
 
     Ok(serde_json::json!({
         "initialized": reader_initialized,
-        "polling": manager.is_polling(),
+        "readers": readers,
+        "reader_status": status.reader_status,
+        "polling": status.polling,
         "status": if reader_initialized { "ready" } else { "not_initialized" }
     }))
-}
\ No newline at end of file
+}
+
+/// Watches the supervised readers: a serial I/O error or a vanished device emits
+/// `device_status_changed` and the reader is re-opened once it comes back.
+pub fn spawn_reader_supervisor(app_handle: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(HEALTH_CHECK_INTERVAL).await;
+            let config = app_handle.state::<AppConfigState>().0.lock().ok().map(|config| config.clone());
+            let Some(config) = config else {
+                continue;
+            };
+            let ports = tokio::task::spawn_blocking(available_ports).await.ok().flatten();
+            let service = app_handle.state::<RFIDManagerState>().inner().clone();
+            if let Err(e) = service.check_health(config, ports).await {
+                log::error!("RFID: {}", e);
+            }
+        }
+    });
+}
+
+/// Lists the serial ports on this machine for picking the e-money reader port.
+/// With `check_open`, every port not already held by a reader is opened at
+/// `baud_rate` (default: the first configured reader's) and closed again. Whether
+/// an e-money reader is behind a port is not checked.
+#[tauri::command]
+pub async fn list_serial_ports_command(
+    config_state: State<'_, AppConfigState>,
+    rfid_manager_state: State<'_, RFIDManagerState>,
+    check_open: bool,
+    baud_rate: Option<u32>,
+) -> Result<Vec<SerialPortCandidate>, String> {
+    let config = config_state.0.lock()
+        .map_err(|_| "Failed to acquire config lock")?
+        .clone();
+    let in_use = rfid_manager_state.status().await?.readers;
+    let baud_rate = baud_rate
+        .or_else(|| config.emoney_readers.first().map(|reader| reader.baud_rate))
+        .unwrap_or(38400);
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let ports = serialport::available_ports()
+            .map_err(|e| format!("Could not enumerate serial ports: {}", e))?;
+        let mut candidates = Vec::with_capacity(ports.len());
+        for info in ports {
+            let in_use_by = in_use.iter()
+                .find(|(_, port)| config_handler::is_port_listed(std::slice::from_ref(&info), port))
+                .map(|(reader_id, _)| reader_id.clone());
+            let mut candidate = SerialPortCandidate::from_info(info);
+            candidate.in_use_by = in_use_by;
+            if candidate.in_use_by.is_none() {
+                candidate.suggested_reader = Some(candidate.reader_settings(baud_rate));
+                if check_open {
+                    let result = check_port_opens(&candidate.port_name, baud_rate);
+                    log::info!("RFID: Open check on {} @ {} baud: {:?}", candidate.port_name, baud_rate, result);
+                    candidate.open_check = Some(PortOpenCheck {
+                        opens: result.is_ok(),
+                        message: result.err().unwrap_or_else(|| "Port opens; reader identification is not implemented".to_string()),
+                    });
+                }
+            }
+            candidates.push(candidate);
+        }
+        Ok(candidates)
+    })
+    .await
+    .map_err(|e| format!("Port scan task failed: {}", e))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    const DEBOUNCE: Duration = Duration::from_secs(3);
+
+    fn kinds(events: &[CardEvent]) -> Vec<(&'static str, String)> {
+        events.iter()
+            .map(|event| match event {
+                CardEvent::Present(tap) => ("present", tap.card_data.clone()),
+                CardEvent::Removed(tap) => ("removed", tap.card_data.clone()),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn card_left_on_reader_is_reported_once() {
+        let mut presence = CardPresence::default();
+        let start = Instant::now();
+        let first = presence.update("main", Some("CARD1".to_string()), DEBOUNCE, start);
+        assert_eq!(kinds(&first), vec![("present", "CARD1".to_string())]);
+        for step in 1..10 {
+            let events = presence.update("main", Some("CARD1".to_string()), DEBOUNCE, start + Duration::from_millis(100 * step));
+            assert!(events.is_empty());
+        }
+    }
+
+    #[test]
+    fn missed_polls_within_removal_grace_keep_the_card() {
+        let mut presence = CardPresence::default();
+        let start = Instant::now();
+        presence.update("main", Some("CARD1".to_string()), DEBOUNCE, start);
+        assert!(presence.update("main", None, DEBOUNCE, start + CARD_REMOVAL_GRACE / 2).is_empty());
+        assert!(presence.update("main", Some("CARD1".to_string()), DEBOUNCE, start + CARD_REMOVAL_GRACE).is_empty());
+    }
+
+    #[test]
+    fn card_is_removed_after_removal_grace() {
+        let mut presence = CardPresence::default();
+        let start = Instant::now();
+        presence.update("main", Some("CARD1".to_string()), DEBOUNCE, start);
+        let events = presence.update("main", None, DEBOUNCE, start + CARD_REMOVAL_GRACE);
+        assert_eq!(kinds(&events), vec![("removed", "CARD1".to_string())]);
+        assert!(presence.update("main", None, DEBOUNCE, start + CARD_REMOVAL_GRACE * 2).is_empty());
+    }
+
+    #[test]
+    fn same_card_within_debounce_is_a_re_read() {
+        let mut presence = CardPresence::default();
+        let start = Instant::now();
+        presence.update("main", Some("CARD1".to_string()), DEBOUNCE, start);
+        presence.update("main", None, DEBOUNCE, start + CARD_REMOVAL_GRACE);
+        let again = start + DEBOUNCE - Duration::from_millis(1);
+        assert!(presence.update("main", Some("CARD1".to_string()), DEBOUNCE, again).is_empty());
+        // Not announced, so its removal is not reported either.
+        assert!(presence.update("main", None, DEBOUNCE, again + CARD_REMOVAL_GRACE).is_empty());
+    }
+
+    #[test]
+    fn same_card_after_debounce_is_a_new_tap() {
+        let mut presence = CardPresence::default();
+        let start = Instant::now();
+        presence.update("main", Some("CARD1".to_string()), DEBOUNCE, start);
+        presence.update("main", None, DEBOUNCE, start + CARD_REMOVAL_GRACE);
+        let events = presence.update("main", Some("CARD1".to_string()), DEBOUNCE, start + DEBOUNCE);
+        assert_eq!(kinds(&events), vec![("present", "CARD1".to_string())]);
+    }
+
+    #[test]
+    fn card_swap_reports_removal_then_new_card() {
+        let mut presence = CardPresence::default();
+        let start = Instant::now();
+        presence.update("main", Some("CARD1".to_string()), DEBOUNCE, start);
+        let events = presence.update("main", Some("CARD2".to_string()), DEBOUNCE, start + Duration::from_millis(100));
+        assert_eq!(kinds(&events), vec![("removed", "CARD1".to_string()), ("present", "CARD2".to_string())]);
+    }
+
+    fn listed(names: &[&str]) -> Vec<serialport::SerialPortInfo> {
+        names.iter()
+            .map(|name| serialport::SerialPortInfo { port_name: name.to_string(), port_type: serialport::SerialPortType::Unknown })
+            .collect()
+    }
+
+    fn fixed(port: &str) -> ReaderConfig {
+        ReaderConfig { id: "main".to_string(), port: port.to_string(), baud_rate: 38400, usb_vid: None, usb_pid: None, usb_serial: None }
+    }
+
+    #[test]
+    fn fixed_port_missing_from_enumeration_does_not_open() {
+        let ports = listed(&["COM3"]);
+        assert_eq!(resolve_reader_port(&fixed("COM3"), Some(&ports)).unwrap(), "COM3");
+        assert!(resolve_reader_port(&fixed("COM4"), Some(&ports)).is_err());
+        // Without an enumeration there is nothing to check against.
+        assert_eq!(resolve_reader_port(&fixed("COM4"), None).unwrap(), "COM4");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinked_port_matches_the_device_it_points_to() {
+        let dir = std::env::temp_dir().join(format!("rfid-ports-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let device = dir.join("ttyUSB0");
+        let by_id = dir.join("usb-Reader_1234-if00-port0");
+        std::fs::write(&device, b"").unwrap();
+        std::os::unix::fs::symlink(&device, &by_id).unwrap();
+
+        let ports = listed(&[device.to_str().unwrap()]);
+        let result = resolve_reader_port(&fixed(by_id.to_str().unwrap()), Some(&ports));
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(result.unwrap(), by_id.to_str().unwrap());
+    }
+
+    #[test]
+    fn suggested_reader_pins_the_usb_identity() {
+        let usb = serialport::UsbPortInfo {
+            vid: 0x0403,
+            pid: 0x6001,
+            serial_number: Some("A10K1234".to_string()),
+            manufacturer: None,
+            product: None,
+        };
+        let candidate = SerialPortCandidate::from_info(serialport::SerialPortInfo {
+            port_name: "/dev/ttyUSB0".to_string(),
+            port_type: serialport::SerialPortType::UsbPort(usb),
+        });
+        let reader = candidate.reader_settings(38400);
+        assert_eq!((reader.port.as_str(), reader.usb_vid, reader.usb_pid), ("/dev/ttyUSB0", Some(0x0403), Some(0x6001)));
+        assert_eq!(reader.usb_serial.as_deref(), Some("A10K1234"));
+
+        let plain = SerialPortCandidate::from_info(listed(&["COM3"]).remove(0)).reader_settings(9600);
+        assert!(!plain.has_usb_match());
+    }
+}
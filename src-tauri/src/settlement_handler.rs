@@ -0,0 +1,440 @@
+// src-tauri/src/settlement_handler.rs
+// Settlement of e-money deductions with the issuing banks. Every successful
+// deduction is journaled with its raw transaction data; settlement exports the
+// unsettled ones of a date range into one file per issuer and marks them settled.
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use tauri::State;
+
+use crate::audit_handler::{AuditKind, AuditLogState};
+use crate::auth_handler::{self, AuthState, Role};
+use crate::config_handler::AppConfigState;
+use crate::db_handler::DatabaseState;
+use crate::issuer_handler::{CardType, SUPPORTED_CARD_TYPES};
+use crate::money::Money;
+use crate::rfid_handler::PaymentResultDetails;
+
+/// Record layout an issuer's bank takes settlement files in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SettlementFormat {
+    /// Header, detail and trailer records of fixed-width fields.
+    FixedWidth,
+    /// Comma-separated with a header row.
+    Csv,
+}
+
+fn settlement_format(card_type: CardType) -> SettlementFormat {
+    match card_type {
+        CardType::MandiriEmoney | CardType::BniTapcash => SettlementFormat::FixedWidth,
+        CardType::BcaFlazz | CardType::BriBrizzi | CardType::Unknown => SettlementFormat::Csv,
+    }
+}
+
+/// Adds a successful deduction to the journal settlement is built from; a disputed
+/// deduction is settled like any other and also recorded for the issuer to resolve.
+pub fn journal_payment(db_state: &DatabaseState, gate_name: &str, payment: &PaymentResultDetails) -> Result<(), String> {
+    let conn = db_state.0.lock().map_err(|_| "Failed to acquire database lock")?;
+    conn.execute(
+        "INSERT INTO payment_journal (transaction_id, gate_name, reader_id, card_type, card_no, amount, balance_after,
+             mid, tid, transaction_counter, transaction_data, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+        params![
+            payment.transaction_id,
+            gate_name,
+            payment.reader_id,
+            card_type_key(payment.card_type),
+            payment.card_no,
+            payment.amount_paid.minor_units(),
+            payment.balance_after.minor_units(),
+            payment.mid,
+            payment.tid,
+            payment.transaction_counter,
+            payment.transaction_data,
+            chrono::Local::now().to_rfc3339(),
+        ],
+    ).map_err(|e| format!("Failed to journal payment {}: {}", payment.transaction_id, e))?;
+    if let Some(reason) = &payment.dispute {
+        log::warn!("SETTLEMENT: Payment {} journaled as disputed: {}", payment.transaction_id, reason);
+        conn.execute(
+            "INSERT OR REPLACE INTO disputed_deductions (transaction_id, reason, created_at) VALUES (?1, ?2, ?3)",
+            params![payment.transaction_id, reason, chrono::Local::now().to_rfc3339()],
+        ).map_err(|e| format!("Failed to journal dispute for payment {}: {}", payment.transaction_id, e))?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+struct JournalRecord {
+    id: i64,
+    card_no: String,
+    amount: Money,
+    balance_after: Money,
+    mid: String,
+    tid: String,
+    counter: u32,
+    transaction_data: String,
+    created_at: String,
+    settled: bool,
+}
+
+/// A jump or repeat in one SAM's (MID/TID) transaction counters, i.e. deductions
+/// missing from the journal or recorded twice.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct CounterGap {
+    pub card_type: CardType,
+    pub mid: String,
+    pub tid: String,
+    pub previous: u32,
+    pub next: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SettlementFileSummary {
+    pub card_type: CardType,
+    pub path: String,
+    pub records: usize,
+    pub total_amount: Money,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SettlementReport {
+    pub files: Vec<SettlementFileSummary>,
+    pub counter_gaps: Vec<CounterGap>,
+    pub marked_settled: usize,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SettlementRequest {
+    /// Inclusive local dates, `YYYY-MM-DD`.
+    pub from_date: String,
+    pub to_date: String,
+    pub destination_dir: String,
+    /// Mark the exported records settled so they are not exported again.
+    pub mark_settled: bool,
+}
+
+fn parse_date(value: &str) -> Result<chrono::NaiveDate, String> {
+    chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| format!("Invalid date '{}', expected YYYY-MM-DD", value))
+}
+
+fn card_type_key(card_type: CardType) -> String {
+    serde_json::to_value(card_type)
+        .ok()
+        .and_then(|value| value.as_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+fn load_records(db_state: &DatabaseState, card_type: CardType, from: &str, to: &str) -> Result<Vec<JournalRecord>, String> {
+    let conn = db_state.0.lock().map_err(|_| "Failed to acquire database lock")?;
+    let mut stmt = conn.prepare(
+        "SELECT id, card_no, CAST(amount AS INTEGER), CAST(balance_after AS INTEGER), mid, tid, transaction_counter, transaction_data, created_at, settled_at IS NOT NULL
+         FROM payment_journal
+         WHERE card_type = ?1 AND substr(created_at, 1, 10) BETWEEN ?2 AND ?3
+         ORDER BY mid, tid, transaction_counter, id",
+    ).map_err(|e| e.to_string())?;
+    let rows = stmt.query_map(params![card_type_key(card_type), from, to], |row| {
+        Ok(JournalRecord {
+            id: row.get(0)?,
+            card_no: row.get(1)?,
+            amount: Money::idr(row.get(2)?),
+            balance_after: Money::idr(row.get(3)?),
+            mid: row.get(4)?,
+            tid: row.get(5)?,
+            counter: row.get(6)?,
+            transaction_data: row.get(7)?,
+            created_at: row.get(8)?,
+            settled: row.get(9)?,
+        })
+    }).map_err(|e| e.to_string())?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+}
+
+/// The journaled counter is the SAM's, which counts every deduction made with it, so
+/// it must run without holes or repeats per MID/TID. Records are sorted by SAM and counter.
+fn find_counter_gaps(card_type: CardType, records: &[JournalRecord]) -> Vec<CounterGap> {
+    records.windows(2)
+        .filter(|pair| {
+            (&pair[0].mid, &pair[0].tid) == (&pair[1].mid, &pair[1].tid)
+                && pair[0].counter.checked_add(1) != Some(pair[1].counter)
+        })
+        .map(|pair| CounterGap {
+            card_type,
+            mid: pair[0].mid.clone(),
+            tid: pair[0].tid.clone(),
+            previous: pair[0].counter,
+            next: pair[1].counter,
+        })
+        .collect()
+}
+
+fn compact_timestamp(rfc3339: &str) -> String {
+    chrono::DateTime::parse_from_rfc3339(rfc3339)
+        .map(|t| t.format("%Y%m%d%H%M%S").to_string())
+        .unwrap_or_else(|_| "00000000000000".to_string())
+}
+
+/// Left-aligns `value` in a fixed-width field. Values that do not fit are refused
+/// rather than cut, since a shortened card number or terminal id settles wrongly.
+fn text_field(name: &str, value: &str, width: usize) -> Result<String, String> {
+    if value.chars().count() > width {
+        return Err(format!("{} '{}' does not fit the {}-character settlement field", name, value, width));
+    }
+    Ok(format!("{:<width$}", value, width = width))
+}
+
+fn number_field(name: &str, value: i64, width: usize) -> Result<String, String> {
+    let text = format!("{:0width$}", value, width = width);
+    if value < 0 || text.len() > width {
+        return Err(format!("{} {} does not fit the {}-digit settlement field", name, value, width));
+    }
+    Ok(text)
+}
+
+fn render_fixed_width(card_type: CardType, gate_name: &str, from: &str, to: &str, records: &[&JournalRecord]) -> Result<String, String> {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "H{}{}{}{}{}",
+        text_field("Card type", card_type.cacm_code(), 8)?,
+        text_field("Gate name", gate_name, 20)?,
+        from.replace('-', ""),
+        to.replace('-', ""),
+        chrono::Local::now().format("%Y%m%d%H%M%S"),
+    );
+    for record in records {
+        let _ = writeln!(
+            out,
+            "D{}{}{}{}{}{}{}{}",
+            text_field("MID", &record.mid, 15)?,
+            text_field("TID", &record.tid, 8)?,
+            number_field("Counter", i64::from(record.counter), 8)?,
+            text_field("Card number", &record.card_no, 19)?,
+            number_field("Amount", record.amount.minor_units(), 12)?,
+            number_field("Balance", record.balance_after.minor_units(), 12)?,
+            compact_timestamp(&record.created_at),
+            record.transaction_data,
+        );
+    }
+    let total = Money::total(records.iter().map(|r| r.amount)).ok_or("Settlement total is out of range")?;
+    let _ = writeln!(
+        out,
+        "T{}{}",
+        number_field("Record count", records.len() as i64, 8)?,
+        number_field("Total", total.minor_units(), 15)?,
+    );
+    Ok(out)
+}
+
+fn render_csv(records: &[&JournalRecord]) -> String {
+    let mut out = String::from("mid,tid,counter,card_no,amount,balance_after,transaction_time,transaction_data\n");
+    for record in records {
+        let _ = writeln!(
+            out,
+            "{},{},{},{},{},{},{},{}",
+            record.mid,
+            record.tid,
+            record.counter,
+            record.card_no,
+            record.amount.minor_units(),
+            record.balance_after.minor_units(),
+            compact_timestamp(&record.created_at),
+            record.transaction_data,
+        );
+    }
+    out
+}
+
+fn mark_settled(db_state: &DatabaseState, ids: &[i64], file: &Path) -> Result<usize, String> {
+    let mut conn = db_state.0.lock().map_err(|_| "Failed to acquire database lock")?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let settled_at = chrono::Local::now().to_rfc3339();
+    let file = file.to_string_lossy();
+    let mut updated = 0;
+    for id in ids {
+        updated += tx.execute(
+            "UPDATE payment_journal SET settled_at = ?1, settlement_file = ?2 WHERE id = ?3 AND settled_at IS NULL",
+            params![settled_at, file, id],
+        ).map_err(|e| e.to_string())?;
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(updated)
+}
+
+/// Writes one settlement file per issuer with unsettled deductions between the
+/// two dates and reports counter gaps seen in that range, settled or not.
+#[tauri::command]
+pub fn generate_settlement_command(
+    config_state: State<'_, AppConfigState>,
+    auth_state: State<'_, AuthState>,
+    db_state: State<'_, DatabaseState>,
+    audit_state: State<'_, AuditLogState>,
+    session_token: String,
+    request: SettlementRequest,
+) -> Result<SettlementReport, String> {
+    let session = auth_handler::require_role(&auth_state, &config_state, &session_token, Role::Supervisor)?;
+    let from = parse_date(&request.from_date)?;
+    let to = parse_date(&request.to_date)?;
+    if from > to {
+        return Err("The start date is after the end date".to_string());
+    }
+    let (from, to) = (from.format("%Y-%m-%d").to_string(), to.format("%Y-%m-%d").to_string());
+    let gate_name = config_state.0.lock().map_err(|_| "Failed to acquire config lock")?.gate_name.clone();
+    let destination = PathBuf::from(&request.destination_dir);
+    std::fs::create_dir_all(&destination)
+        .map_err(|e| format!("Failed to create settlement directory {:?}: {}", destination, e))?;
+
+    let mut report = SettlementReport { files: Vec::new(), counter_gaps: Vec::new(), marked_settled: 0 };
+    for &card_type in SUPPORTED_CARD_TYPES {
+        let records = load_records(&db_state, card_type, &from, &to)?;
+        report.counter_gaps.extend(find_counter_gaps(card_type, &records));
+        let unsettled: Vec<&JournalRecord> = records.iter().filter(|r| !r.settled).collect();
+        if unsettled.is_empty() {
+            continue;
+        }
+
+        let (contents, extension) = match settlement_format(card_type) {
+            SettlementFormat::FixedWidth => (render_fixed_width(card_type, &gate_name, &from, &to, &unsettled)?, "txt"),
+            SettlementFormat::Csv => (render_csv(&unsettled), "csv"),
+        };
+        let file_name = format!(
+            "{}_{}_{}_{}_{}.{}",
+            card_type.cacm_code(),
+            gate_name,
+            from.replace('-', ""),
+            to.replace('-', ""),
+            chrono::Local::now().format("%Y%m%d%H%M%S"),
+            extension,
+        );
+        let path = destination.join(file_name);
+        std::fs::write(&path, contents)
+            .map_err(|e| format!("Failed to write settlement file {:?}: {}", path, e))?;
+        log::info!("SETTLEMENT: Wrote {} {:?} records to {:?}", unsettled.len(), card_type, path);
+
+        if request.mark_settled {
+            let ids: Vec<i64> = unsettled.iter().map(|r| r.id).collect();
+            report.marked_settled += mark_settled(&db_state, &ids, &path)?;
+        }
+        report.files.push(SettlementFileSummary {
+            card_type,
+            path: path.to_string_lossy().to_string(),
+            records: unsettled.len(),
+            total_amount: Money::total(unsettled.iter().map(|r| r.amount))
+                .ok_or("Settlement total is out of range")?,
+        });
+    }
+
+    for gap in &report.counter_gaps {
+        log::warn!("SETTLEMENT: {:?} SAM {}/{} counter jumps from {} to {}", gap.card_type, gap.mid, gap.tid, gap.previous, gap.next);
+    }
+    audit_state.record(AuditKind::Settlement, &session.username, &gate_name, serde_json::json!({
+        "from": from,
+        "to": to,
+        "files": report.files,
+        "counter_gaps": report.counter_gaps,
+        "marked_settled": report.marked_settled,
+    }));
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db_handler;
+
+    fn record(id: i64, tid: &str, counter: u32) -> JournalRecord {
+        JournalRecord {
+            id,
+            card_no: "6032000000000001".to_string(),
+            amount: Money::idr(25_000),
+            balance_after: Money::idr(75_000),
+            mid: "000000000000001".to_string(),
+            tid: tid.to_string(),
+            counter,
+            transaction_data: "ABCD".to_string(),
+            created_at: "2026-10-01T08:30:00+07:00".to_string(),
+            settled: false,
+        }
+    }
+
+    #[test]
+    fn counters_are_checked_per_sam() {
+        let records = [
+            record(1, "T1", 5),
+            record(2, "T1", 6),
+            record(3, "T1", 8),
+            record(4, "T1", 8),
+            record(5, "T2", 1),
+            record(6, "T2", 2),
+        ];
+        let gaps: Vec<(String, u32, u32)> = find_counter_gaps(CardType::MandiriEmoney, &records)
+            .into_iter()
+            .map(|gap| (gap.tid, gap.previous, gap.next))
+            .collect();
+        assert_eq!(gaps, vec![("T1".to_string(), 6, 8), ("T1".to_string(), 8, 8)]);
+
+        let mut other_merchant = record(7, "T1", 9);
+        other_merchant.mid = "000000000000002".to_string();
+        assert!(find_counter_gaps(CardType::MandiriEmoney, &[record(1, "T1", 3), other_merchant]).is_empty());
+    }
+
+    #[test]
+    fn fixed_width_records_keep_their_layout() {
+        let records = [record(1, "SIM00042", 7), record(2, "T2", 12_345_678)];
+        let rendered = render_fixed_width(CardType::MandiriEmoney, "GATE-01", "2026-10-01", "2026-10-02", &records.iter().collect::<Vec<_>>()).unwrap();
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(&lines[0][..29], "HMANDIRI GATE-01             ");
+        assert_eq!(&lines[0][29..45], "2026100120261002");
+        assert_eq!(
+            lines[1],
+            "D000000000000001SIM00042000000076032000000000001   000000025000000000075000\
+             20261001083000ABCD"
+        );
+        assert_eq!(&lines[2][16..32], "T2      12345678");
+        assert_eq!(lines[3], "T00000002000000000050000");
+    }
+
+    #[test]
+    fn values_too_long_for_their_field_are_refused() {
+        let render = |record: JournalRecord| {
+            render_fixed_width(CardType::MandiriEmoney, "GATE-01", "2026-10-01", "2026-10-01", &[&record])
+        };
+        let mut long_card = record(1, "T1", 1);
+        long_card.card_no = "60320000000000000001".to_string();
+        assert!(render(long_card).is_err());
+        let mut long_tid = record(1, "T1", 1);
+        long_tid.tid = "SIM000042".to_string();
+        assert!(render(long_tid).is_err());
+        assert!(render(record(1, "T1", 100_000_000)).is_err());
+        let mut negative = record(1, "T1", 1);
+        negative.amount = Money::idr(-1);
+        assert!(render(negative).is_err());
+        let long_gate = render_fixed_width(CardType::MandiriEmoney, "A-GATE-NAME-OVER-20-CHARS", "2026-10-01", "2026-10-01", &[]);
+        assert!(long_gate.is_err());
+    }
+
+    #[test]
+    fn mark_settled_counts_only_rows_it_updated() {
+        let db = db_handler::open_in_memory();
+        {
+            let conn = db.0.lock().unwrap();
+            for transaction_id in ["TXN1", "TXN2"] {
+                conn.execute(
+                    "INSERT INTO payment_journal (transaction_id, gate_name, reader_id, card_type, card_no, amount, balance_after,
+                         mid, tid, transaction_counter, transaction_data, created_at)
+                     VALUES (?1, 'G1', 'main', 'mandiri_emoney', '6032', 25000, 75000, 'M', 'T', 1, '', '2026-10-01T08:30:00+07:00')",
+                    params![transaction_id],
+                ).unwrap();
+            }
+        }
+        let file = Path::new("settlement.txt");
+        assert_eq!(mark_settled(&db, &[1], file).unwrap(), 1);
+        assert_eq!(mark_settled(&db, &[1, 2, 99], file).unwrap(), 1);
+        let records = load_records(&db, CardType::MandiriEmoney, "2026-10-01", "2026-10-01").unwrap();
+        assert!(records.iter().all(|r| r.settled));
+    }
+}
@@ -1,9 +1,22 @@
 // src-tauri/src/soap_services.rs
 use reqwest;
 use serde::{Deserialize, Serialize};
-use crate::config_handler::AppConfigState;
-use tauri::State;
+use crate::adam_handler::PortalAuthorizationState;
+use crate::anpr_handler::AnprState;
+use crate::blacklist_handler::{self, BlacklistKind};
+use crate::config_handler::{AppConfig, AppConfigState, SoapAuthMode};
+use crate::container_ocr_handler::{self, ContainerCheck, OcrState};
+use crate::credential_handler::{CredentialStoreState, GateCredential};
+use crate::db_handler::DatabaseState;
+use crate::offline_handler::{self, CacheKind};
+use crate::tariff_handler::{self, TariffState};
+use crate::uhf_handler::UhfState;
+use crate::visit_handler;
+use tauri::{Manager, State};
 use base64::{Engine as _, engine::general_purpose};
+use sha1::{Digest, Sha1};
+use std::fmt;
+use std::time::Duration;
 
 // ... (CGSMessageResult, CMSData, CGSTReceiveResult remain the same) ...
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -44,6 +57,9 @@ pub struct CGSTReceiveResult {
     pub transaction_id_str: Option<String>,
     #[serde(rename = "resultCMS")]
     pub result_cms: Option<Vec<CMSData>>,
+    /// Containers the OCR recognized on the truck, checked against the gate passes and CMS data.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub container_check: Option<ContainerCheck>,
 }
 
 
@@ -56,8 +72,38 @@ pub struct GateInCommandData {
     pub gate_name: String,
 }
 
-async fn post_soap_request(url: &str, soap_action: &str, body: String) -> Result<String, String> {
-    let client = reqwest::Client::new();
+const CGS_NAMESPACE: &str = "http://halotec-indonesia.com/";
+pub const CHECK_TID_STATUS: &str = "CheckTIDStatus";
+pub const TRUCK_IN_OUT: &str = "TruckInOut";
+pub const MESSAGE_6TAR: &str = "Message6TAR";
+/// `result` of a gate-in CGS has not confirmed yet.
+const DEGRADED_RESULT: &str = "DEGRADED";
+/// Long enough for a slow CGS, short enough that a dead link does not hold a truck for minutes.
+const SOAP_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Why a SOAP call failed. Only an unreachable CGS lets a lane fall back to degraded mode.
+#[derive(Debug)]
+pub enum SoapError {
+    /// No answer: connection failure, timeout or a gateway error in front of the service.
+    Unreachable(String),
+    /// The service answered, but not successfully.
+    Failed(String),
+}
+
+impl fmt::Display for SoapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SoapError::Unreachable(e) => write!(f, "Service unreachable: {}", e),
+            SoapError::Failed(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+async fn post_soap_request(url: &str, soap_action: &str, body: String) -> Result<String, SoapError> {
+    let client = reqwest::Client::builder()
+        .timeout(SOAP_REQUEST_TIMEOUT)
+        .build()
+        .map_err(|e| SoapError::Failed(e.to_string()))?;
     log::trace!("SOAP Request to: {}, Action: {}", url, soap_action);
     log::trace!("SOAP Body: {}", body);
     let response = client.post(url)
@@ -65,37 +111,139 @@ async fn post_soap_request(url: &str, soap_action: &str, body: String) -> Result
         .header("SOAPAction", soap_action)
         .body(body)
         .send().await
-        .map_err(|e| e.to_string())?;
+        .map_err(|e| SoapError::Unreachable(e.to_string()))?;
     let status = response.status();
-    let response_text = response.text().await.map_err(|e| e.to_string())?;
+    let response_text = response.text().await.map_err(|e| SoapError::Unreachable(e.to_string()))?;
     log::trace!("SOAP Response Status: {}", status);
     log::trace!("SOAP Response Body (first 500 chars): {}", response_text.chars().take(500).collect::<String>());
     if status.is_success() {
         Ok(response_text)
+    } else if matches!(status.as_u16(), 502..=504) {
+        Err(SoapError::Unreachable(format!("status {}", status)))
     } else {
-        Err(format!("SOAP request failed with status {}: {}", status, response_text))
+        Err(SoapError::Failed(format!("SOAP request failed with status {}: {}", status, response_text)))
     }
 }
 
-fn get_auth_header_xml(gate_name: &str) -> String {
-    let username = general_purpose::STANDARD.encode(gate_name);
-    let password = general_purpose::STANDARD.encode(format!("{}PWD", gate_name));
+/// Sends one operation of the SOAP service at `url`, e.g. `<CheckTIDStatus xmlns=...>...</CheckTIDStatus>`,
+/// in an envelope carrying the lane's current authentication header.
+pub async fn call_soap_service(
+    config: &AppConfig,
+    credential_state: &CredentialStoreState,
+    url: &str,
+    namespace: &str,
+    operation: &str,
+    operation_xml: &str,
+) -> Result<String, SoapError> {
+    let auth_header_xml = get_auth_header_xml(config, credential_state).map_err(SoapError::Failed)?;
+    let soap_body = format!(
+        r#"<soap:Envelope xmlns:soap="http://schemas.xmlsoap.org/soap/envelope/"><soap:Header>{}</soap:Header><soap:Body>{}</soap:Body></soap:Envelope>"#,
+        auth_header_xml, operation_xml
+    );
+    post_soap_request(url, &format!("{}{}", namespace, operation), soap_body).await
+}
+
+/// Sends one CGS operation.
+pub async fn call_cgs(config: &AppConfig, credential_state: &CredentialStoreState, operation: &str, operation_xml: &str) -> Result<String, SoapError> {
+    call_soap_service(config, credential_state, &config.cgs_gateway_url, CGS_NAMESPACE, operation, operation_xml).await
+}
+
+/// Whether CGS accepted `operation`, judged from its response.
+pub fn cgs_accepted(operation: &str, response_xml: &str) -> bool {
+    // Simplified parsing, ideally use an XML parser
+    match operation {
+        CHECK_TID_STATUS => response_xml.contains("<Status>true</Status>") || response_xml.contains("<Status>True</Status>"),
+        TRUCK_IN_OUT => response_xml.contains("<status>true</status>")
+            && (response_xml.contains("<result>OK</result>") || response_xml.contains("<result>Ok</result>")),
+        MESSAGE_6TAR => response_xml.contains("OK")
+            || response_xml.contains("<Status>S</Status>")
+            || response_xml.contains("<Message6TARResult>OK</Message6TARResult>"),
+        _ => false,
+    }
+}
+
+const WSSE_NS: &str = "http://docs.oasis-open.org/wss/2004/01/oasis-200401-wss-wssecurity-secext-1.0.xsd";
+const WSU_NS: &str = "http://docs.oasis-open.org/wss/2004/01/oasis-200401-wss-wssecurity-utility-1.0.xsd";
+const WSSE_PASSWORD_TEXT: &str = "http://docs.oasis-open.org/wss/2004/01/oasis-200401-wss-username-token-profile-1.0#PasswordText";
+const WSSE_PASSWORD_DIGEST: &str = "http://docs.oasis-open.org/wss/2004/01/oasis-200401-wss-username-token-profile-1.0#PasswordDigest";
+const WSSE_BASE64_BINARY: &str = "http://docs.oasis-open.org/wss/2004/01/oasis-200401-wss-soap-message-security-1.0#Base64Binary";
+
+pub(crate) fn xml_escape(value: &str) -> String {
+    value.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn auth_header_xml(username: &str, password: &str) -> String {
+    // The ASMX AuthHeader expects both values base64-encoded.
+    let username = general_purpose::STANDARD.encode(username);
+    let password = general_purpose::STANDARD.encode(password);
     format!(r#"<AuthHeader xmlns="http://halotec-indonesia.com/"><UserName>{}</UserName><Password>{}</Password></AuthHeader>"#, username, password)
 }
 
+fn ws_security_header_xml(credential: &GateCredential, digest: bool) -> String {
+    let created = chrono::Utc::now().format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string();
+    let (password_type, password, nonce_xml) = if digest {
+        // PasswordDigest = Base64(SHA-1(nonce + created + password)), per the UsernameToken profile.
+        let nonce: [u8; 16] = *uuid::Uuid::new_v4().as_bytes();
+        let mut hasher = Sha1::new();
+        hasher.update(nonce);
+        hasher.update(created.as_bytes());
+        hasher.update(credential.password.as_bytes());
+        let password_digest = general_purpose::STANDARD.encode(hasher.finalize());
+        let nonce_xml = format!(r#"<wsse:Nonce EncodingType="{}">{}</wsse:Nonce>"#, WSSE_BASE64_BINARY, general_purpose::STANDARD.encode(nonce));
+        (WSSE_PASSWORD_DIGEST, password_digest, nonce_xml)
+    } else {
+        (WSSE_PASSWORD_TEXT, xml_escape(&credential.password), String::new())
+    };
+    format!(
+        r#"<wsse:Security soap:mustUnderstand="1" xmlns:wsse="{wsse_ns}" xmlns:wsu="{wsu_ns}"><wsse:UsernameToken wsu:Id="UsernameToken-{token_id}"><wsse:Username>{username}</wsse:Username><wsse:Password Type="{password_type}">{password}</wsse:Password>{nonce_xml}<wsu:Created>{created}</wsu:Created></wsse:UsernameToken></wsse:Security>"#,
+        wsse_ns = WSSE_NS,
+        wsu_ns = WSU_NS,
+        token_id = uuid::Uuid::new_v4().simple(),
+        username = xml_escape(&credential.username),
+        password_type = password_type,
+        password = password,
+        nonce_xml = nonce_xml,
+        created = created,
+    )
+}
+
+/// Builds the SOAP header content for the configured `soap_auth_mode`.
+fn get_auth_header_xml(config: &AppConfig, credential_state: &CredentialStoreState) -> Result<String, String> {
+    if config.soap_auth_mode == SoapAuthMode::Legacy {
+        return Ok(auth_header_xml(&config.gate_name, &format!("{}PWD", config.gate_name)));
+    }
+    let credential = credential_state.0.lock()
+        .map_err(|_| "Failed to acquire credential store lock")?
+        .soap_credential(&config.gate_name)?
+        .ok_or_else(|| format!("No SOAP credentials stored for gate {} (auth mode {:?})", config.gate_name, config.soap_auth_mode))?;
+    Ok(match config.soap_auth_mode {
+        SoapAuthMode::Legacy | SoapAuthMode::AuthHeader => auth_header_xml(&credential.username, &credential.password),
+        SoapAuthMode::WsSecurityText => ws_security_header_xml(&credential, false),
+        SoapAuthMode::WsSecurityDigest => ws_security_header_xml(&credential, true),
+    })
+}
+
 #[tauri::command]
-pub async fn validate_rfid_card_command(config_state: State<'_, AppConfigState>, card_data: String) -> Result<CGSMessageResult, String> {
+pub async fn validate_rfid_card_command(config_state: State<'_, AppConfigState>, credential_state: State<'_, CredentialStoreState>, db_state: State<'_, DatabaseState>, uhf_state: State<'_, UhfState>, card_data: String) -> Result<CGSMessageResult, String> {
     let config = config_state.0.lock().unwrap().clone();
     let parts: Vec<&str> = card_data.split('_').collect();
     let proximity_id = parts.get(0).unwrap_or(&"").to_string();
     let tid_from_card = parts.get(1).unwrap_or(&card_data.as_str()).to_string();
     log::info!("SOAP: Validating RFID: Prox={}, TID={}, Gate={}", proximity_id, tid_from_card, config.gate_name);
-    let auth_header_xml = get_auth_header_xml(&config.gate_name);
-    let soap_body = format!(r#"<soap:Envelope xmlns:soap="http://schemas.xmlsoap.org/soap/envelope/"><soap:Header>{auth_header_xml}</soap:Header><soap:Body><CheckTIDStatus xmlns="http://halotec-indonesia.com/"><tid>{tid_from_card}</tid><gateId>{gate_id}</gateId><proximityId>{proximity_id}</proximityId></CheckTIDStatus></soap:Body></soap:Envelope>"#, auth_header_xml=auth_header_xml, tid_from_card=tid_from_card, gate_id=config.gate_name, proximity_id=proximity_id);
-    match post_soap_request(&config.cgs_gateway_url, "http://halotec-indonesia.com/CheckTIDStatus", soap_body).await {
+    blacklist_handler::ensure_not_blacklisted(&db_state, BlacklistKind::Tag, &tid_from_card)?;
+    // The windshield tag is what TruckInOut will use, so a blacklisted one is refused before payment.
+    if let Some(truck_tag) = uhf_state.current_tag() {
+        blacklist_handler::ensure_not_blacklisted(&db_state, BlacklistKind::Tag, &truck_tag)?;
+    }
+    let operation_xml = format!(r#"<CheckTIDStatus xmlns="{ns}"><tid>{tid_from_card}</tid><gateId>{gate_id}</gateId><proximityId>{proximity_id}</proximityId></CheckTIDStatus>"#, ns=CGS_NAMESPACE, tid_from_card=tid_from_card, gate_id=config.gate_name, proximity_id=proximity_id);
+    match call_cgs(&config, &credential_state, CHECK_TID_STATUS, &operation_xml).await {
         Ok(response_xml) => {
-            // Simplified parsing, ideally use an XML parser
-            if response_xml.contains("<Status>true</Status>") || response_xml.contains("<Status>True</Status>") {
+            if cgs_accepted(CHECK_TID_STATUS, &response_xml) {
+                offline_handler::remember_validated(&db_state, CacheKind::Tag, &tid_from_card);
                 let msg = response_xml.split("<Message>").nth(1).and_then(|s| s.split("</Message>").next()).unwrap_or("Validated").to_string();
                 Ok(CGSMessageResult { status: true, message: Some(msg), inner_message: None })
             } else {
@@ -104,6 +252,16 @@ pub async fn validate_rfid_card_command(config_state: State<'_, AppConfigState>,
                 Err(err_msg) // Return the error message from the SOAP service
             }
         }
+        Err(SoapError::Unreachable(e)) => {
+            log::error!("SOAP: CGS unreachable for CheckTIDStatus: {}", e);
+            let known = offline_handler::is_known(&db_state, &config, CacheKind::Tag, &tid_from_card);
+            offline_handler::decide(&db_state, &config, CHECK_TID_STATUS, &tid_from_card, known, &operation_xml)?;
+            Ok(CGSMessageResult {
+                status: true,
+                message: Some("Validated offline, pending CGS confirmation".to_string()),
+                inner_message: Some(DEGRADED_RESULT.to_string()),
+            })
+        }
         Err(e) => {
             log::error!("SOAP request error for CheckTIDStatus: {}", e);
             Err(format!("SOAP request error: {}", e))
@@ -111,50 +269,105 @@ pub async fn validate_rfid_card_command(config_state: State<'_, AppConfigState>,
     }
 }
 
+/// Entry lanes open a visit for the tag and exit lanes close it, so exit lanes
+/// can charge overstay. Failures are only logged: CGS has already accepted the truck.
+fn journal_visit(db_state: &DatabaseState, config: &AppConfig, tag_number: &str, transaction_id: &str, cms_items: &[CMSData]) {
+    if tag_number.is_empty() {
+        return;
+    }
+    let result = if config.gate_type == 0 {
+        let truck_in_time = visit_handler::truck_in_from_cms(cms_items).unwrap_or_else(chrono::Local::now);
+        visit_handler::journal_truck_in(db_state, tag_number, transaction_id, &config.gate_name, truck_in_time)
+    } else {
+        visit_handler::journal_truck_out(db_state, tag_number, chrono::Local::now()).map(|_| ())
+    };
+    if let Err(e) = result {
+        log::error!("{}", e);
+    }
+}
+
 #[tauri::command]
-pub async fn send_gate_in_command(config_state: State<'_, AppConfigState>, data: GateInCommandData) -> Result<CGSTReceiveResult, String> {
+pub async fn send_gate_in_command(config_state: State<'_, AppConfigState>, credential_state: State<'_, CredentialStoreState>, app_handle: tauri::AppHandle, uhf_state: State<'_, UhfState>, db_state: State<'_, DatabaseState>, data: GateInCommandData) -> Result<CGSTReceiveResult, String> {
     let config = config_state.0.lock().unwrap().clone();
     log::info!("SOAP: GateIn TX: {}, GPs: {:?}, Gate: {}", data.transaction_id_str, data.gate_passes, data.gate_name);
-    let auth_header_xml = get_auth_header_xml(&config.gate_name);
     let tar_xml_elements: String = data.gate_passes.iter().map(|tar| format!("<string>{}</string>", tar)).collect();
-    let rfid_tag_num = data.rfid_info.as_ref().map_or_else(String::new, |ri| ri.main.clone()); // Example: use main as TagNum
-    let soap_body = format!(
-        r#"<soap:Envelope xmlns:soap="http://schemas.xmlsoap.org/soap/envelope/">
-            <soap:Header>{auth_header_xml}</soap:Header>
-            <soap:Body>
-                <TruckInOut xmlns="http://halotec-indonesia.com/">
+    // A windshield tag read by the UHF reader identifies the truck; otherwise fall back to the card.
+    let rfid_tag_num = match uhf_state.current_tag() {
+        Some(truck_tag) => truck_tag,
+        None => data.rfid_info.as_ref().map_or_else(String::new, |ri| ri.main.clone()), // Example: use main as TagNum
+    };
+    blacklist_handler::ensure_not_blacklisted(&db_state, BlacklistKind::Tag, &rfid_tag_num)?;
+    // The plate the camera read is what is on the truck; the tag's registration is the fallback.
+    let police_num = app_handle.state::<AnprState>().current_plate().or_else(|| data.rfid_info.as_ref().map(|r| r.sub.clone()));
+    let mut container_check = container_ocr_handler::verify_before_gate_in(&app_handle, &config, &data.gate_passes)?;
+    let operation_xml = format!(
+        r#"<TruckInOut xmlns="{ns}">
                     <TRANSACTIONID>{transaction_id}</TRANSACTIONID>
                     <TAGNUM>{rfid_tag_num}</TAGNUM>
                     <TARList>{tar_list}</TARList>
                     <INOUT>IN</INOUT>
                     <GATEID>{gate_id}</GATEID>
-                </TruckInOut>
-            </soap:Body>
-        </soap:Envelope>"#,
-        auth_header_xml = auth_header_xml,
+                </TruckInOut>"#,
+        ns = CGS_NAMESPACE,
         transaction_id = data.transaction_id_str,
         rfid_tag_num = rfid_tag_num,
         tar_list = tar_xml_elements,
         gate_id = data.gate_name
     );
-    match post_soap_request(&config.cgs_gateway_url, "http://halotec-indonesia.com/TruckInOut", soap_body).await {
+    match call_cgs(&config, &credential_state, TRUCK_IN_OUT, &operation_xml).await {
         Ok(response_xml) => {
             // More robust parsing needed here
-            if response_xml.contains("<status>true</status>") && (response_xml.contains("<result>OK</result>") || response_xml.contains("<result>Ok</result>")) {
+            if cgs_accepted(TRUCK_IN_OUT, &response_xml) {
+                offline_handler::remember_validated(&db_state, CacheKind::Tag, &rfid_tag_num);
+                // A gate pass is good for one visit; once used it must not vouch for another truck.
+                for gate_pass in &data.gate_passes {
+                    offline_handler::forget_validated(&db_state, CacheKind::GatePass, gate_pass);
+                }
                 let cms_items = Some(vec![CMSData { 
                     daily_seq: Some("CMS_SIM_001".to_string()), 
                     cntr_number: data.gate_passes.get(0).cloned(), 
-                    truck_police_num: data.rfid_info.map(|r| r.sub), 
+                    truck_police_num: police_num, 
                     truck_in_time: Some(chrono::Local::now().to_rfc3339()), 
                     ..Default::default() 
                 }]);
-                Ok(CGSTReceiveResult { status: true, result: Some("OK".to_string()), transaction_id_str: Some(data.transaction_id_str), result_cms: cms_items })
+                tariff_handler::record_pending_charge(&app_handle.state::<TariffState>(), &db_state, &config, &data.transaction_id_str, &rfid_tag_num, &data.gate_passes, cms_items.as_deref().unwrap_or_default());
+                journal_visit(&db_state, &config, &rfid_tag_num, &data.transaction_id_str, cms_items.as_deref().unwrap_or_default());
+                container_ocr_handler::verify_cms_items(&app_handle, &mut container_check, cms_items.as_deref().unwrap_or_default());
+                app_handle.state::<OcrState>().clear();
+                app_handle.state::<AnprState>().clear();
+                uhf_state.release_tag(&rfid_tag_num);
+                Ok(CGSTReceiveResult { status: true, result: Some("OK".to_string()), transaction_id_str: Some(data.transaction_id_str), result_cms: cms_items, container_check: Some(container_check) })
             } else {
                 let err_msg = response_xml.split("<result>").nth(1).and_then(|s| s.split("</result>").next()).unwrap_or("GateIn Failed").to_string();
                 log::warn!("GateIn SOAP response indicates failure: {}", err_msg);
                 Err(err_msg)
             }
         }
+        Err(SoapError::Unreachable(e)) => {
+            log::error!("SOAP: CGS unreachable for TruckInOut: {}", e);
+            let known = offline_handler::is_known(&db_state, &config, CacheKind::Tag, &rfid_tag_num)
+                && data.gate_passes.iter().all(|gate_pass| offline_handler::is_known(&db_state, &config, CacheKind::GatePass, gate_pass));
+            offline_handler::decide(&db_state, &config, TRUCK_IN_OUT, &data.transaction_id_str, known, &operation_xml)?;
+            for gate_pass in &data.gate_passes {
+                offline_handler::forget_validated(&db_state, CacheKind::GatePass, gate_pass);
+            }
+            // CGS has not assigned CMS data yet; the slip carries what the lane knows.
+            let now = chrono::Local::now().to_rfc3339();
+            let cms_items: Vec<CMSData> = data.gate_passes.iter().map(|gate_pass| CMSData {
+                result_status: Some(DEGRADED_RESULT.to_string()),
+                result_message: Some("Pending CGS confirmation".to_string()),
+                cntr_number: Some(gate_pass.clone()),
+                truck_police_num: police_num.clone(),
+                truck_in_time: Some(now.clone()),
+                ..Default::default()
+            }).collect();
+            tariff_handler::record_pending_charge(&app_handle.state::<TariffState>(), &db_state, &config, &data.transaction_id_str, &rfid_tag_num, &data.gate_passes, &cms_items);
+            journal_visit(&db_state, &config, &rfid_tag_num, &data.transaction_id_str, &cms_items);
+            app_handle.state::<OcrState>().clear();
+            app_handle.state::<AnprState>().clear();
+            uhf_state.release_tag(&rfid_tag_num);
+            Ok(CGSTReceiveResult { status: true, result: Some(DEGRADED_RESULT.to_string()), transaction_id_str: Some(data.transaction_id_str), result_cms: Some(cms_items), container_check: Some(container_check) })
+        }
         Err(e) => {
             log::error!("SOAP request error for TruckInOut: {}", e);
             Err(format!("SOAP request error: {}", e))
@@ -162,32 +375,51 @@ pub async fn send_gate_in_command(config_state: State<'_, AppConfigState>, data:
     }
 }
 
-#[tauri::command]
-pub async fn send_truck_in_command(config_state: State<'_, AppConfigState>, transaction_id_str: String) -> Result<String, String> {
-    let config = config_state.0.lock().unwrap().clone();
-    log::info!("SOAP: TruckIn confirm TX ID: {}", transaction_id_str);
-    let auth_header_xml = get_auth_header_xml(&config.gate_name);
-    let soap_body = format!(
-        r#"<soap:Envelope xmlns:soap="http://schemas.xmlsoap.org/soap/envelope/">
-            <soap:Header>{auth_header_xml}</soap:Header>
-            <soap:Body>
-                <Message6TAR xmlns="http://halotec-indonesia.com/">
+fn message_6tar_xml(transaction_id: &str) -> String {
+    format!(
+        r#"<Message6TAR xmlns="{ns}">
                     <transactionId>{transaction_id}</transactionId>
                     <tar>{tar}</tar>
                     <updateams>true</updateams>
                     <datetime>{datetime}</datetime>
-                </Message6TAR>
-            </soap:Body>
-        </soap:Envelope>"#,
-        auth_header_xml = auth_header_xml,
-        transaction_id = transaction_id_str,
+                </Message6TAR>"#,
+        ns = CGS_NAMESPACE,
+        transaction_id = transaction_id,
         tar = "FINAL_DUMMY_TAR", // Or actual TAR if available
         datetime = chrono::Utc::now().format("%Y%m%d%H%M%S")
-    );
-    match post_soap_request(&config.cgs_gateway_url, "http://halotec-indonesia.com/Message6TAR", soap_body).await {
+    )
+}
+
+/// Sends the truck-in confirmation for a transaction a supervisor force-completes.
+/// If CGS cannot be reached it is queued for replay regardless of the degraded-mode
+/// policy; a refusal by CGS is returned as the error.
+pub async fn force_complete_truck_in(config: &AppConfig, credential_state: &CredentialStoreState, db_state: &DatabaseState, transaction_id: &str) -> Result<String, String> {
+    let operation_xml = message_6tar_xml(transaction_id);
+    match call_cgs(config, credential_state, MESSAGE_6TAR, &operation_xml).await {
+        Ok(response_xml) if cgs_accepted(MESSAGE_6TAR, &response_xml) => Ok("Truck-in confirmed by CGS".to_string()),
+        Ok(response_xml) => {
+            let err_msg = response_xml.split("<Message6TARResult>").nth(1).and_then(|s| s.split("</Message6TARResult>").next()).unwrap_or("Message6TAR refused").to_string();
+            Err(format!("CGS refused the truck-in confirmation: {}", err_msg))
+        }
+        Err(SoapError::Unreachable(e)) => {
+            log::error!("SOAP: CGS unreachable for forced Message6TAR: {}", e);
+            offline_handler::queue_override(db_state, config, MESSAGE_6TAR, transaction_id, &operation_xml);
+            Ok("Truck-in confirmation queued until CGS is reachable".to_string())
+        }
+        Err(e) => Err(format!("SOAP request error: {}", e)),
+    }
+}
+
+#[tauri::command]
+pub async fn send_truck_in_command(config_state: State<'_, AppConfigState>, credential_state: State<'_, CredentialStoreState>, portal_auth_state: State<'_, PortalAuthorizationState>, db_state: State<'_, DatabaseState>, transaction_id_str: String) -> Result<String, String> {
+    let config = config_state.0.lock().unwrap().clone();
+    log::info!("SOAP: TruckIn confirm TX ID: {}", transaction_id_str);
+    let operation_xml = message_6tar_xml(&transaction_id_str);
+    match call_cgs(&config, &credential_state, MESSAGE_6TAR, &operation_xml).await {
         Ok(response_xml) => {
              // More robust parsing needed here
-            if response_xml.contains("OK") || response_xml.contains("<Status>S</Status>") || response_xml.contains("<Message6TARResult>OK</Message6TARResult>") {
+            if cgs_accepted(MESSAGE_6TAR, &response_xml) {
+                portal_auth_state.grant(&transaction_id_str);
                 Ok("TruckIn successful.".to_string())
             } else {
                 let err_msg = response_xml.split("<Message6TARResult>").nth(1).and_then(|s|s.split("</Message6TARResult>").next()).unwrap_or(&response_xml).to_string();
@@ -195,9 +427,16 @@ pub async fn send_truck_in_command(config_state: State<'_, AppConfigState>, tran
                 Err(err_msg)
             }
         }
+        Err(SoapError::Unreachable(e)) => {
+            log::error!("SOAP: CGS unreachable for Message6TAR: {}", e);
+            // The truck already passed gate-in, online or under the same policy.
+            offline_handler::decide(&db_state, &config, MESSAGE_6TAR, &transaction_id_str, true, &operation_xml)?;
+            portal_auth_state.grant(&transaction_id_str);
+            Ok("TruckIn recorded offline, pending CGS confirmation.".to_string())
+        }
         Err(e) => {
             log::error!("SOAP request error for Message6TAR: {}", e);
             Err(format!("SOAP request error: {}", e))
         },
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,612 @@
+// src-tauri/src/tariff_handler.rs
+// Gate charges. A site's tariff is a list of rules in the `[tariff]` section of
+// the config; every rule that matches the transaction adds a line to the bill.
+use chrono::{DateTime, Local, NaiveTime};
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use tauri::State;
+
+use crate::config_handler::{AppConfig, AppConfigState};
+use crate::db_handler::DatabaseState;
+use crate::money::Money;
+use crate::soap_services_handler::CMSData;
+use crate::visit_handler;
+
+/// What a rule's amount is charged per.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ChargeBasis {
+    /// Once per transaction.
+    #[default]
+    Truck,
+    /// Once per matching container.
+    Container,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CargoStatus {
+    Full,
+    Empty,
+}
+
+impl CargoStatus {
+    /// Reads CGS `cntrStatus` values such as `F`, `FULL`, `E`, `EMPTY` or `MTY`.
+    fn from_cms(value: &str) -> Option<Self> {
+        let value = value.trim().to_ascii_uppercase();
+        if value.starts_with('F') {
+            Some(CargoStatus::Full)
+        } else if value.starts_with('E') || value.starts_with("MT") {
+            Some(CargoStatus::Empty)
+        } else {
+            None
+        }
+    }
+}
+
+/// Container lengths in feet, keyed by the first character of an ISO 6346 size-type code.
+const ISO_LENGTHS: &[(char, &str)] = &[
+    ('1', "10"),
+    ('2', "20"),
+    ('3', "30"),
+    ('4', "40"),
+    ('L', "45"),
+    ('M', "48"),
+    ('P', "53"),
+];
+
+pub fn container_length(iso_code: &str) -> Option<&'static str> {
+    let size = iso_code.trim().chars().next()?.to_ascii_uppercase();
+    ISO_LENGTHS.iter().find(|(code, _)| *code == size).map(|(_, length)| *length)
+}
+
+pub fn is_known_length(length: &str) -> bool {
+    ISO_LENGTHS.iter().any(|(_, known)| *known == length)
+}
+
+/// One tariff line. Conditions left out match everything; the container
+/// conditions select which containers a `container` rule is charged for.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TariffRule {
+    /// Short code printed on the slip and sent to CaCMTool.
+    pub code: String,
+    pub description: String,
+    pub amount: Money,
+    #[serde(default)]
+    pub per: ChargeBasis,
+    /// Container lengths in feet ("20", "40", "45"), from the `cntrIsocode` size character.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sizes: Vec<String>,
+    /// `cntrIsocode` values or prefixes, e.g. "22G1" or "45R".
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub iso_codes: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cntr_status: Option<CargoStatus>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_containers: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_containers: Option<usize>,
+    /// Local time window `HH:MM-HH:MM`, end exclusive; may wrap past midnight.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hours: Option<String>,
+    /// Time in terminal, in minutes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_dwell_mins: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_dwell_mins: Option<i64>,
+}
+
+impl TariffRule {
+    fn has_container_conditions(&self) -> bool {
+        !self.sizes.is_empty() || !self.iso_codes.is_empty() || self.cntr_status.is_some()
+    }
+
+    fn matches_container(&self, container: &CMSData) -> bool {
+        let iso_code = container.cntr_isocode.as_deref().unwrap_or("").trim().to_ascii_uppercase();
+        let size_ok = self.sizes.is_empty()
+            || container_length(&iso_code).is_some_and(|length| self.sizes.iter().any(|s| s.trim() == length));
+        let iso_ok = self.iso_codes.is_empty()
+            || self.iso_codes.iter().any(|prefix| !iso_code.is_empty() && iso_code.starts_with(&prefix.trim().to_ascii_uppercase()));
+        let status_ok = self.cntr_status.map_or(true, |wanted| {
+            container.cntr_status.as_deref().and_then(CargoStatus::from_cms) == Some(wanted)
+        });
+        size_ok && iso_ok && status_ok
+    }
+
+    fn matches_transaction(&self, containers: usize, now: NaiveTime, dwell_mins: Option<i64>) -> bool {
+        if self.min_containers.is_some_and(|min| containers < min) || self.max_containers.is_some_and(|max| containers > max) {
+            return false;
+        }
+        if let Some(hours) = &self.hours {
+            match parse_hours(hours) {
+                Ok((start, end)) if !in_window(now, start, end) => return false,
+                Ok(_) => {}
+                Err(_) => return false,
+            }
+        }
+        if self.min_dwell_mins.is_some() || self.max_dwell_mins.is_some() {
+            let Some(dwell) = dwell_mins else {
+                return false;
+            };
+            if self.min_dwell_mins.is_some_and(|min| dwell < min) || self.max_dwell_mins.is_some_and(|max| dwell > max) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Surcharge for the part of a stay beyond `up_to_mins` of the previous band.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OverstayBand {
+    /// Longest overstay, in minutes past the grace period, this band covers; the
+    /// last band may leave it out to cover everything longer.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub up_to_mins: Option<i64>,
+    pub amount: Money,
+}
+
+/// Charged on exit lanes when a truck stayed longer than `grace_mins` after truck-in.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct OverstayConfig {
+    pub grace_mins: i64,
+    /// In ascending order of `up_to_mins`; an overstay pays the first band that covers it.
+    pub bands: Vec<OverstayBand>,
+}
+
+impl OverstayConfig {
+    /// The surcharge for `dwell_mins` in the terminal, if it is an overstay.
+    fn surcharge(&self, dwell_mins: i64) -> Option<Money> {
+        let overstay = dwell_mins - self.grace_mins;
+        if overstay <= 0 {
+            return None;
+        }
+        self.bands.iter()
+            .find(|band| band.up_to_mins.map_or(true, |limit| overstay <= limit))
+            .or(self.bands.last())
+            .map(|band| band.amount)
+    }
+}
+
+/// Rules are applied in order. With no rules every truck pays `emoney_deduct_price`.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct TariffConfig {
+    pub rules: Vec<TariffRule>,
+    pub overstay: OverstayConfig,
+}
+
+/// Parses `HH:MM-HH:MM`.
+pub fn parse_hours(value: &str) -> Result<(NaiveTime, NaiveTime), String> {
+    let (start, end) = value.split_once('-')
+        .ok_or_else(|| format!("'{}' is not a time window, expected HH:MM-HH:MM", value))?;
+    let parse = |t: &str| NaiveTime::parse_from_str(t.trim(), "%H:%M")
+        .map_err(|_| format!("'{}' is not a time of day, expected HH:MM", t.trim()));
+    let (start, end) = (parse(start)?, parse(end)?);
+    if start == end {
+        return Err(format!("Time window '{}' is empty; leave it out to match all day", value));
+    }
+    Ok((start, end))
+}
+
+fn in_window(now: NaiveTime, start: NaiveTime, end: NaiveTime) -> bool {
+    if start < end {
+        start <= now && now < end
+    } else {
+        now >= start || now < end
+    }
+}
+
+/// What a transaction is charged from: the containers CGS returned, the gate
+/// passes scanned and, on exit lanes, how long the truck has been inside.
+#[derive(Debug, Clone, Default)]
+pub struct TariffRequest {
+    pub cms_items: Vec<CMSData>,
+    pub gate_passes: Vec<String>,
+    pub dwell_mins: Option<i64>,
+}
+
+impl TariffRequest {
+    /// One container per CMS item; before CGS has answered, one per gate pass.
+    fn container_count(&self) -> usize {
+        if self.cms_items.is_empty() {
+            self.gate_passes.len()
+        } else {
+            self.cms_items.len()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TariffItem {
+    pub code: String,
+    pub description: String,
+    pub quantity: u32,
+    pub unit_amount: Money,
+    pub amount: Money,
+}
+
+/// An itemized charge. `total` is what the card is debited.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct TariffBreakdown {
+    pub items: Vec<TariffItem>,
+    pub total: Money,
+    /// Time in terminal the charge was worked out with, on exit lanes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dwell_mins: Option<i64>,
+}
+
+impl TariffBreakdown {
+    /// Rejects a breakdown whose lines do not add up or that has nothing to charge.
+    pub fn check(&self) -> Result<(), String> {
+        for item in &self.items {
+            if item.unit_amount.checked_mul(item.quantity) != Some(item.amount) {
+                return Err(format!("Tariff line {} does not add up", item.code));
+            }
+        }
+        if Money::total(self.items.iter().map(|item| item.amount)) != Some(self.total) {
+            return Err("Tariff total does not match its lines".to_string());
+        }
+        if !self.total.is_positive() {
+            return Err("Nothing to charge: the tariff total is zero".to_string());
+        }
+        Ok(())
+    }
+}
+
+fn line(code: &str, description: &str, quantity: usize, unit_amount: Money) -> Result<TariffItem, String> {
+    let quantity = u32::try_from(quantity).map_err(|_| format!("Too many items for tariff line {}", code))?;
+    let amount = unit_amount.checked_mul(quantity)
+        .ok_or_else(|| format!("Tariff line {} is out of range", code))?;
+    Ok(TariffItem { code: code.to_string(), description: description.to_string(), quantity, unit_amount, amount })
+}
+
+/// Prices `request` at local time `now`.
+pub fn calculate(config: &AppConfig, request: &TariffRequest, now: NaiveTime) -> Result<TariffBreakdown, String> {
+    let mut items = Vec::new();
+    if config.tariff.rules.is_empty() {
+        items.push(line("GATE", "Gate fee", 1, config.emoney_deduct_price)?);
+    }
+    let containers = request.container_count();
+    for rule in &config.tariff.rules {
+        if !rule.matches_transaction(containers, now, request.dwell_mins) {
+            continue;
+        }
+        let matching = request.cms_items.iter().filter(|c| rule.matches_container(c)).count();
+        let quantity = match rule.per {
+            ChargeBasis::Truck if rule.has_container_conditions() => usize::from(matching > 0),
+            ChargeBasis::Truck => 1,
+            // Without CMS data only unconditional per-container rules can be priced.
+            ChargeBasis::Container if request.cms_items.is_empty() && !rule.has_container_conditions() => containers,
+            ChargeBasis::Container => matching,
+        };
+        if quantity > 0 {
+            items.push(line(&rule.code, &rule.description, quantity, rule.amount)?);
+        }
+    }
+    if let Some(dwell) = request.dwell_mins {
+        if let Some(surcharge) = config.tariff.overstay.surcharge(dwell) {
+            let overstay = dwell - config.tariff.overstay.grace_mins;
+            let description = format!("Overstay {}h {:02}m", overstay / 60, overstay % 60);
+            items.push(line("OVERSTAY", &description, 1, surcharge)?);
+        }
+    }
+    let total = Money::total(items.iter().map(|item| item.amount)).ok_or("Tariff total is out of range")?;
+    Ok(TariffBreakdown { items, total, dwell_mins: request.dwell_mins })
+}
+
+/// A transaction CGS has taken in or out and the lane has yet to charge for.
+#[derive(Debug, Clone)]
+pub struct PendingCharge {
+    pub transaction_id: String,
+    pub cms_items: Vec<CMSData>,
+    pub gate_passes: Vec<String>,
+    /// When the truck came in, on exit lanes; dwell is worked out from it each
+    /// time the charge is priced.
+    pub truck_in: Option<DateTime<Local>>,
+    /// The breakdown last shown for it. The card is debited exactly this.
+    pub quote: Option<TariffBreakdown>,
+}
+
+/// The transaction the lane charges next. The UI only ever names it by id, so
+/// what the card is debited is always worked out here.
+#[derive(Default)]
+pub struct TariffState(Mutex<Option<PendingCharge>>);
+
+impl TariffState {
+    pub fn set(&self, charge: PendingCharge) {
+        if let Ok(mut pending) = self.0.lock() {
+            *pending = Some(charge);
+        }
+    }
+
+    pub fn get(&self, transaction_id: &str) -> Result<PendingCharge, String> {
+        self.0.lock().map_err(|_| "Failed to acquire tariff lock")?
+            .as_ref()
+            .filter(|charge| charge.transaction_id == transaction_id)
+            .cloned()
+            .ok_or_else(|| format!("Transaction {} has nothing to charge", transaction_id))
+    }
+
+    fn set_quote(&self, transaction_id: &str, quote: TariffBreakdown) -> Result<(), String> {
+        let mut pending = self.0.lock().map_err(|_| "Failed to acquire tariff lock")?;
+        match pending.as_mut().filter(|charge| charge.transaction_id == transaction_id) {
+            Some(charge) => {
+                charge.quote = Some(quote);
+                Ok(())
+            }
+            None => Err(format!("Transaction {} has nothing to charge", transaction_id)),
+        }
+    }
+
+    /// Forgets the transaction once it is paid, so it cannot be charged twice.
+    pub fn clear(&self, transaction_id: &str) {
+        if let Ok(mut pending) = self.0.lock() {
+            if pending.as_ref().is_some_and(|charge| charge.transaction_id == transaction_id) {
+                *pending = None;
+            }
+        }
+    }
+}
+
+/// Records what CGS returned for the transaction. On exit lanes this runs before
+/// the visit is closed, so the truck-in time is found from the tag TruckInOut used.
+pub fn record_pending_charge(
+    tariff_state: &TariffState,
+    db_state: &DatabaseState,
+    config: &AppConfig,
+    transaction_id: &str,
+    tag_number: &str,
+    gate_passes: &[String],
+    cms_items: &[CMSData],
+) {
+    let truck_in = if config.gate_type == 1 {
+        let truck_in = match visit_handler::truck_in_from_cms(cms_items) {
+            Some(time) => Some(time),
+            None if tag_number.is_empty() => None,
+            None => visit_handler::open_visit_truck_in(db_state, tag_number).unwrap_or_else(|e| {
+                log::error!("{}", e);
+                None
+            }),
+        };
+        if truck_in.is_none() {
+            log::warn!("TARIFF: No truck-in time for tag {}; overstay is not charged", tag_number);
+        }
+        truck_in
+    } else {
+        None
+    };
+    tariff_state.set(PendingCharge {
+        transaction_id: transaction_id.to_string(),
+        cms_items: cms_items.to_vec(),
+        gate_passes: gate_passes.to_vec(),
+        truck_in,
+        quote: None,
+    });
+}
+
+/// Prices the lane's pending transaction with its tariff.
+pub fn price_pending(config: &AppConfig, tariff_state: &TariffState, transaction_id: &str) -> Result<TariffBreakdown, String> {
+    let charge = tariff_state.get(transaction_id)?;
+    let now = Local::now();
+    let request = TariffRequest {
+        cms_items: charge.cms_items,
+        gate_passes: charge.gate_passes,
+        dwell_mins: charge.truck_in.map(|time| (now - time).num_minutes().max(0)),
+    };
+    let breakdown = calculate(config, &request, now.time())?;
+    log::info!(
+        "TARIFF: {} line(s), total {} for {} container(s) in {}",
+        breakdown.items.len(),
+        breakdown.total,
+        request.container_count(),
+        transaction_id
+    );
+    Ok(breakdown)
+}
+
+/// Prices the pending transaction and keeps the result as the amount shown for it.
+pub fn quote_pending(config: &AppConfig, tariff_state: &TariffState, transaction_id: &str) -> Result<TariffBreakdown, String> {
+    let breakdown = price_pending(config, tariff_state, transaction_id)?;
+    tariff_state.set_quote(transaction_id, breakdown.clone())?;
+    Ok(breakdown)
+}
+
+/// What to debit for the transaction: the quote shown for it, as long as pricing it
+/// now still comes to the same total. Otherwise the new price becomes the quote and
+/// the payment is refused, so the card never pays more or less than was shown.
+pub fn charge_for_quote(config: &AppConfig, tariff_state: &TariffState, transaction_id: &str) -> Result<TariffBreakdown, String> {
+    let quote = tariff_state.get(transaction_id)?.quote
+        .ok_or_else(|| format!("The charge for {} has not been shown yet", transaction_id))?;
+    let current = price_pending(config, tariff_state, transaction_id)?;
+    if current.total != quote.total {
+        let message = format!(
+            "The charge for {} changed from {} to {} since it was shown; show the new amount and tap again",
+            transaction_id, quote.total, current.total
+        );
+        tariff_state.set_quote(transaction_id, current)?;
+        return Err(message);
+    }
+    Ok(quote)
+}
+
+/// Prices a transaction with the lane's tariff for display before the card is charged.
+/// The amount returned is the one the payment will debit.
+#[tauri::command]
+pub fn calculate_tariff_command(
+    config_state: State<'_, AppConfigState>,
+    tariff_state: State<'_, TariffState>,
+    transaction_id: String,
+) -> Result<TariffBreakdown, String> {
+    let config = config_state.0.lock().map_err(|_| "Failed to acquire config lock")?.clone();
+    quote_pending(&config, &tariff_state, &transaction_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(code: &str, amount: i64) -> TariffRule {
+        serde_json::from_value(serde_json::json!({ "code": code, "description": code, "amount": amount })).unwrap()
+    }
+
+    fn container(iso_code: &str, status: &str) -> CMSData {
+        CMSData { cntr_isocode: Some(iso_code.to_string()), cntr_status: Some(status.to_string()), ..Default::default() }
+    }
+
+    fn overstay() -> OverstayConfig {
+        OverstayConfig {
+            grace_mins: 60,
+            bands: vec![
+                OverstayBand { up_to_mins: Some(60), amount: Money::idr(10_000) },
+                OverstayBand { up_to_mins: Some(180), amount: Money::idr(25_000) },
+                OverstayBand { up_to_mins: None, amount: Money::idr(50_000) },
+            ],
+        }
+    }
+
+    fn noon() -> NaiveTime {
+        NaiveTime::from_hms_opt(12, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn without_rules_every_truck_pays_the_flat_price() {
+        let config = AppConfig { emoney_deduct_price: Money::idr(17_000), ..Default::default() };
+        let breakdown = calculate(&config, &TariffRequest::default(), noon()).unwrap();
+        assert_eq!(breakdown.items.len(), 1);
+        assert_eq!(breakdown.items[0].code, "GATE");
+        assert_eq!(breakdown.total, Money::idr(17_000));
+    }
+
+    #[test]
+    fn container_rules_charge_matching_containers() {
+        let mut forty_full = rule("FULL40", 20_000);
+        forty_full.per = ChargeBasis::Container;
+        forty_full.sizes = vec!["40".to_string()];
+        forty_full.cntr_status = Some(CargoStatus::Full);
+        let mut config = AppConfig::default();
+        config.tariff.rules = vec![rule("GATE", 10_000), forty_full];
+        let request = TariffRequest {
+            cms_items: vec![container("45G1", "F"), container("42G1", "FULL"), container("45G1", "MTY"), container("22G1", "F")],
+            ..Default::default()
+        };
+
+        let breakdown = calculate(&config, &request, noon()).unwrap();
+        assert_eq!(breakdown.items[1].quantity, 2);
+        assert_eq!(breakdown.items[1].amount, Money::idr(40_000));
+        assert_eq!(breakdown.total, Money::idr(50_000));
+        breakdown.check().unwrap();
+    }
+
+    #[test]
+    fn hours_window_wraps_past_midnight() {
+        let mut night = rule("NIGHT", 5_000);
+        night.hours = Some("22:00-06:00".to_string());
+        let mut config = AppConfig::default();
+        config.tariff.rules = vec![rule("GATE", 10_000), night];
+        let at = |h, m| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+
+        assert_eq!(calculate(&config, &TariffRequest::default(), at(23, 0)).unwrap().total, Money::idr(15_000));
+        assert_eq!(calculate(&config, &TariffRequest::default(), at(5, 59)).unwrap().total, Money::idr(15_000));
+        assert_eq!(calculate(&config, &TariffRequest::default(), at(6, 0)).unwrap().total, Money::idr(10_000));
+        assert_eq!(calculate(&config, &TariffRequest::default(), noon()).unwrap().total, Money::idr(10_000));
+    }
+
+    #[test]
+    fn no_surcharge_within_grace() {
+        let overstay = overstay();
+        assert_eq!(overstay.surcharge(0), None);
+        assert_eq!(overstay.surcharge(60), None);
+    }
+
+    #[test]
+    fn surcharge_band_limits_are_inclusive() {
+        let overstay = overstay();
+        assert_eq!(overstay.surcharge(61), Some(Money::idr(10_000)));
+        assert_eq!(overstay.surcharge(120), Some(Money::idr(10_000)));
+        assert_eq!(overstay.surcharge(121), Some(Money::idr(25_000)));
+        assert_eq!(overstay.surcharge(240), Some(Money::idr(25_000)));
+        assert_eq!(overstay.surcharge(241), Some(Money::idr(50_000)));
+        assert_eq!(overstay.surcharge(10_000), Some(Money::idr(50_000)));
+    }
+
+    #[test]
+    fn longest_band_covers_overstays_past_its_limit() {
+        let overstay = OverstayConfig {
+            grace_mins: 0,
+            bands: vec![OverstayBand { up_to_mins: Some(60), amount: Money::idr(10_000) }],
+        };
+        assert_eq!(overstay.surcharge(90), Some(Money::idr(10_000)));
+    }
+
+    #[test]
+    fn overstay_is_added_as_its_own_line() {
+        let mut config = AppConfig { emoney_deduct_price: Money::idr(17_000), ..Default::default() };
+        config.tariff.overstay = overstay();
+        let request = TariffRequest { dwell_mins: Some(195), ..Default::default() };
+
+        let breakdown = calculate(&config, &request, noon()).unwrap();
+        assert_eq!(breakdown.items[1].code, "OVERSTAY");
+        assert_eq!(breakdown.items[1].description, "Overstay 2h 15m");
+        assert_eq!(breakdown.total, Money::idr(42_000));
+        assert_eq!(breakdown.dwell_mins, Some(195));
+    }
+
+    #[test]
+    fn pending_charge_is_priced_with_dwell_up_to_now() {
+        let mut config = AppConfig { emoney_deduct_price: Money::idr(17_000), ..Default::default() };
+        config.tariff.overstay = overstay();
+        let tariff_state = TariffState::default();
+        tariff_state.set(PendingCharge {
+            transaction_id: "T1".to_string(),
+            cms_items: Vec::new(),
+            gate_passes: vec!["GP1".to_string()],
+            truck_in: Some(Local::now() - chrono::Duration::minutes(195)),
+            quote: None,
+        });
+
+        assert!(price_pending(&config, &tariff_state, "T2").is_err());
+        let breakdown = price_pending(&config, &tariff_state, "T1").unwrap();
+        assert_eq!(breakdown.dwell_mins, Some(195));
+        assert_eq!(breakdown.total, Money::idr(42_000));
+
+        tariff_state.clear("T1");
+        assert!(price_pending(&config, &tariff_state, "T1").is_err());
+    }
+
+    #[test]
+    fn payment_debits_the_quote_while_the_total_holds() {
+        let mut config = AppConfig { emoney_deduct_price: Money::idr(17_000), ..Default::default() };
+        config.tariff.overstay = overstay();
+        let tariff_state = TariffState::default();
+        tariff_state.set(PendingCharge {
+            transaction_id: "T1".to_string(),
+            cms_items: Vec::new(),
+            gate_passes: vec!["GP1".to_string()],
+            truck_in: Some(Local::now() - chrono::Duration::minutes(130)),
+            quote: None,
+        });
+        assert!(charge_for_quote(&config, &tariff_state, "T1").is_err(), "nothing shown yet");
+
+        let quote = quote_pending(&config, &tariff_state, "T1").unwrap();
+        assert_eq!(quote.total, Money::idr(42_000));
+        assert_eq!(charge_for_quote(&config, &tariff_state, "T1").unwrap(), quote);
+
+        // The truck crosses into the next overstay band between quote and tap.
+        tariff_state.0.lock().unwrap().as_mut().unwrap().truck_in = Some(Local::now() - chrono::Duration::minutes(250));
+        let refused = charge_for_quote(&config, &tariff_state, "T1").unwrap_err();
+        assert!(refused.contains("changed"), "{}", refused);
+        let requoted = tariff_state.get("T1").unwrap().quote.unwrap();
+        assert_eq!(requoted.total, Money::idr(67_000));
+        assert_eq!(charge_for_quote(&config, &tariff_state, "T1").unwrap(), requoted);
+    }
+
+    #[test]
+    fn check_rejects_lines_that_do_not_add_up() {
+        let config = AppConfig { emoney_deduct_price: Money::idr(17_000), ..Default::default() };
+        let mut breakdown = calculate(&config, &TariffRequest::default(), noon()).unwrap();
+        breakdown.total = Money::idr(1);
+        assert!(breakdown.check().is_err());
+    }
+}
@@ -0,0 +1,513 @@
+// src-tauri/src/uhf_handler.rs
+// Long-range UHF reader for windshield truck tags, driven over LLRP 1.0.1 (TCP 5084).
+// `examples/llrp_standin.rs` is a minimal reader stand-in for trying this without hardware.
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{Emitter, Manager};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::Notify;
+
+use crate::config_handler::{AppConfig, AppConfigState};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+const DISABLED_RECHECK_INTERVAL: Duration = Duration::from_secs(10);
+/// A truck tag is used as TAGNUM only if it was read this recently.
+const TAG_VALIDITY: Duration = Duration::from_secs(120);
+const ROSPEC_ID: u32 = 1;
+
+// LLRP message types
+const MSG_CLOSE_CONNECTION: u16 = 14;
+const MSG_ADD_ROSPEC: u16 = 20;
+const MSG_DELETE_ROSPEC: u16 = 21;
+const MSG_ENABLE_ROSPEC: u16 = 24;
+const MSG_ADD_ROSPEC_RESPONSE: u16 = 30;
+const MSG_DELETE_ROSPEC_RESPONSE: u16 = 31;
+const MSG_ENABLE_ROSPEC_RESPONSE: u16 = 34;
+const MSG_RO_ACCESS_REPORT: u16 = 61;
+const MSG_KEEPALIVE: u16 = 62;
+const MSG_READER_EVENT_NOTIFICATION: u16 = 63;
+const MSG_KEEPALIVE_ACK: u16 = 72;
+const MSG_ERROR_MESSAGE: u16 = 100;
+
+// LLRP parameter types
+const PARAM_ANTENNA_ID: u16 = 1;
+const PARAM_PEAK_RSSI: u16 = 6;
+const PARAM_EPC_96: u16 = 13;
+const PARAM_RO_SPEC: u16 = 177;
+const PARAM_RO_BOUNDARY_SPEC: u16 = 178;
+const PARAM_RO_SPEC_START_TRIGGER: u16 = 179;
+const PARAM_RO_SPEC_STOP_TRIGGER: u16 = 182;
+const PARAM_AI_SPEC: u16 = 183;
+const PARAM_AI_SPEC_STOP_TRIGGER: u16 = 184;
+const PARAM_INVENTORY_PARAMETER_SPEC: u16 = 186;
+const PARAM_RO_REPORT_SPEC: u16 = 237;
+const PARAM_TAG_REPORT_CONTENT_SELECTOR: u16 = 238;
+const PARAM_TAG_REPORT_DATA: u16 = 240;
+const PARAM_EPC_DATA: u16 = 241;
+const PARAM_LLRP_STATUS: u16 = 287;
+
+/// A truck tag read that passed the antenna, RSSI and duplicate filters.
+#[derive(Debug, Serialize, Clone)]
+pub struct TruckTagRead {
+    pub tag_id: String,
+    pub antenna_id: Option<u16>,
+    pub rssi: Option<i8>,
+    pub timestamp: String,
+}
+
+#[derive(Default)]
+pub struct UhfState {
+    latest: Mutex<Option<(TruckTagRead, Instant)>>,
+    last_seen: Mutex<HashMap<String, Instant>>,
+    reconnect: Notify,
+}
+
+impl UhfState {
+    /// The truck tag to send as TAGNUM, if one was read recently enough.
+    pub fn current_tag(&self) -> Option<String> {
+        self.latest.lock().ok()?
+            .as_ref()
+            .filter(|(_, seen)| seen.elapsed() <= TAG_VALIDITY)
+            .map(|(read, _)| read.tag_id.clone())
+    }
+
+    /// Forgets `tag_id` once the truck it belongs to has gone through, so a following
+    /// truck without a readable tag is not sent to CGS under it. A newer read is kept.
+    pub fn release_tag(&self, tag_id: &str) {
+        if let Ok(mut latest) = self.latest.lock() {
+            if latest.as_ref().is_some_and(|(read, _)| read.tag_id == tag_id) {
+                *latest = None;
+            }
+        }
+    }
+
+    /// Drops the connection so the reader is set up again with the current settings.
+    pub fn request_reconnect(&self) {
+        self.reconnect.notify_one();
+    }
+
+    /// Applies the antenna, RSSI and duplicate filters. Every read refreshes the
+    /// tag's last-seen time, so a truck parked under the antenna is reported once,
+    /// and keeps it current for TAGNUM for as long as it stays there.
+    fn accept(&self, tag: &RawTagRead, config: &AppConfig) -> bool {
+        if let Some(antenna) = tag.antenna_id {
+            if !config.uhf_antennas.is_empty() && !config.uhf_antennas.contains(&antenna) {
+                return false;
+            }
+        }
+        if tag.rssi.is_some_and(|rssi| i32::from(rssi) < config.uhf_min_rssi) {
+            return false;
+        }
+        let Ok(mut last_seen) = self.last_seen.lock() else {
+            return false;
+        };
+        let window = Duration::from_secs(config.uhf_duplicate_window_secs);
+        last_seen.retain(|_, seen| seen.elapsed() <= window);
+        let now = Instant::now();
+        if last_seen.insert(tag.epc.clone(), now).is_none() {
+            return true;
+        }
+        if let Ok(mut latest) = self.latest.lock() {
+            if let Some((read, seen)) = latest.as_mut() {
+                if read.tag_id == tag.epc {
+                    *seen = now;
+                }
+            }
+        }
+        false
+    }
+}
+
+#[derive(Debug, Default)]
+struct RawTagRead {
+    epc: String,
+    antenna_id: Option<u16>,
+    rssi: Option<i8>,
+}
+
+struct LlrpMessage {
+    message_type: u16,
+    id: u32,
+    body: Vec<u8>,
+}
+
+fn tlv(param_type: u16, content: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(content.len() + 4);
+    out.extend_from_slice(&(param_type & 0x03ff).to_be_bytes());
+    out.extend_from_slice(&((content.len() + 4) as u16).to_be_bytes());
+    out.extend_from_slice(content);
+    out
+}
+
+/// ROSpec that starts immediately, runs until disabled and reports every tag as it is seen.
+fn build_rospec(antennas: &[u16]) -> Vec<u8> {
+    let start_trigger = tlv(PARAM_RO_SPEC_START_TRIGGER, &[1]); // Immediate
+    let stop_trigger = tlv(PARAM_RO_SPEC_STOP_TRIGGER, &[0, 0, 0, 0, 0]); // Null
+    let boundary = tlv(PARAM_RO_BOUNDARY_SPEC, &[start_trigger, stop_trigger].concat());
+
+    // Antenna 0 means all antennas; filtering to the configured ones happens on our side too.
+    let antenna_ids: Vec<u16> = if antennas.is_empty() { vec![0] } else { antennas.to_vec() };
+    let mut ai_spec = (antenna_ids.len() as u16).to_be_bytes().to_vec();
+    for id in &antenna_ids {
+        ai_spec.extend_from_slice(&id.to_be_bytes());
+    }
+    ai_spec.extend(tlv(PARAM_AI_SPEC_STOP_TRIGGER, &[0, 0, 0, 0, 0])); // Null
+    ai_spec.extend(tlv(PARAM_INVENTORY_PARAMETER_SPEC, &[0, 1, 1])); // Spec 1, EPC Class1 Gen2
+    let ai_spec = tlv(PARAM_AI_SPEC, &ai_spec);
+
+    // Report after every tag (N = 1) with antenna id and peak RSSI.
+    let selector = tlv(PARAM_TAG_REPORT_CONTENT_SELECTOR, &0x1400u16.to_be_bytes());
+    let report_spec = tlv(PARAM_RO_REPORT_SPEC, &[&[2, 0, 1][..], &selector].concat());
+
+    let mut rospec = ROSPEC_ID.to_be_bytes().to_vec();
+    rospec.extend_from_slice(&[0, 0]); // Priority 0, state Disabled
+    rospec.extend(boundary);
+    rospec.extend(ai_spec);
+    rospec.extend(report_spec);
+    tlv(PARAM_RO_SPEC, &rospec)
+}
+
+/// Length of the value of a TV-encoded parameter, which carries no length field.
+fn tv_value_len(param_type: u16) -> Option<usize> {
+    Some(match param_type {
+        1 | 7 | 8 | 10 | 11 | 12 | 14 | 15 => 2,
+        2..=5 => 8,
+        6 => 1,
+        9 | 16 => 4,
+        13 => 12,
+        _ => return None,
+    })
+}
+
+/// Splits a parameter list into `(type, value)` pairs.
+fn parse_params(mut data: &[u8]) -> Result<Vec<(u16, &[u8])>, String> {
+    let mut params = Vec::new();
+    while !data.is_empty() {
+        if data[0] & 0x80 != 0 {
+            let param_type = u16::from(data[0] & 0x7f);
+            let len = tv_value_len(param_type).ok_or_else(|| format!("Unknown LLRP TV parameter {}", param_type))?;
+            let value = data.get(1..1 + len).ok_or("Truncated LLRP TV parameter")?;
+            params.push((param_type, value));
+            data = &data[1 + len..];
+        } else {
+            if data.len() < 4 {
+                return Err("Truncated LLRP parameter header".to_string());
+            }
+            let param_type = u16::from_be_bytes([data[0], data[1]]) & 0x03ff;
+            let len = usize::from(u16::from_be_bytes([data[2], data[3]]));
+            if len < 4 || len > data.len() {
+                return Err(format!("Bad length {} for LLRP parameter {}", len, param_type));
+            }
+            params.push((param_type, &data[4..len]));
+            data = &data[len..];
+        }
+    }
+    Ok(params)
+}
+
+fn parse_tag_report(data: &[u8]) -> Result<RawTagRead, String> {
+    let mut tag = RawTagRead::default();
+    for (param_type, value) in parse_params(data)? {
+        match param_type {
+            PARAM_EPC_96 => tag.epc = hex_upper(value),
+            PARAM_EPC_DATA if value.len() >= 2 => {
+                let bits = usize::from(u16::from_be_bytes([value[0], value[1]]));
+                let bytes = value.get(2..2 + bits.div_ceil(8)).ok_or("Truncated EPCData")?;
+                tag.epc = hex_upper(bytes);
+            }
+            PARAM_ANTENNA_ID if value.len() == 2 => tag.antenna_id = Some(u16::from_be_bytes([value[0], value[1]])),
+            PARAM_PEAK_RSSI if value.len() == 1 => tag.rssi = Some(value[0] as i8),
+            _ => {}
+        }
+    }
+    if tag.epc.is_empty() {
+        return Err("Tag report without an EPC".to_string());
+    }
+    Ok(tag)
+}
+
+fn hex_upper(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02X}", b)).collect()
+}
+
+/// Checks the LLRPStatus parameter every response carries.
+fn check_status(message: &LlrpMessage) -> Result<(), String> {
+    let params = parse_params(&message.body)?;
+    let (_, status) = params.iter()
+        .find(|(param_type, _)| *param_type == PARAM_LLRP_STATUS)
+        .ok_or_else(|| format!("LLRP message {} has no status", message.message_type))?;
+    if status.len() < 2 {
+        return Err("Truncated LLRPStatus".to_string());
+    }
+    let code = u16::from_be_bytes([status[0], status[1]]);
+    if code == 0 {
+        return Ok(());
+    }
+    let description = status.get(2..4)
+        .map(|len| usize::from(u16::from_be_bytes([len[0], len[1]])))
+        .and_then(|len| status.get(4..4 + len))
+        .map(|text| String::from_utf8_lossy(text).into_owned())
+        .unwrap_or_default();
+    Err(format!("Reader rejected LLRP message {} (status {}): {}", message.message_type, code, description))
+}
+
+struct LlrpConnection {
+    stream: TcpStream,
+    next_id: u32,
+}
+
+impl LlrpConnection {
+    async fn connect(ip: &str, port: u16) -> Result<Self, String> {
+        let address = format!("{}:{}", ip, port);
+        let stream = tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect(&address)).await
+            .map_err(|_| format!("UHF reader at {} did not answer within {:?}", address, CONNECT_TIMEOUT))?
+            .map_err(|e| format!("UHF reader at {} unreachable: {}", address, e))?;
+        Ok(LlrpConnection { stream, next_id: 1 })
+    }
+
+    async fn send(&mut self, message_type: u16, id: u32, body: &[u8]) -> Result<(), String> {
+        let mut frame = Vec::with_capacity(body.len() + 10);
+        frame.extend_from_slice(&((1u16 << 10) | (message_type & 0x03ff)).to_be_bytes()); // version 1
+        frame.extend_from_slice(&((body.len() + 10) as u32).to_be_bytes());
+        frame.extend_from_slice(&id.to_be_bytes());
+        frame.extend_from_slice(body);
+        self.stream.write_all(&frame).await
+            .map_err(|e| format!("Failed to write to UHF reader: {}", e))
+    }
+
+    async fn receive(&mut self) -> Result<LlrpMessage, String> {
+        let mut header = [0u8; 10];
+        self.stream.read_exact(&mut header).await
+            .map_err(|e| format!("Connection to UHF reader lost: {}", e))?;
+        let message_type = u16::from_be_bytes([header[0], header[1]]) & 0x03ff;
+        let length = u32::from_be_bytes([header[2], header[3], header[4], header[5]]) as usize;
+        let id = u32::from_be_bytes([header[6], header[7], header[8], header[9]]);
+        if !(10..=1 << 20).contains(&length) {
+            return Err(format!("Bad LLRP message length {}", length));
+        }
+        let mut body = vec![0u8; length - 10];
+        self.stream.read_exact(&mut body).await
+            .map_err(|e| format!("Connection to UHF reader lost: {}", e))?;
+        Ok(LlrpMessage { message_type, id, body })
+    }
+
+    /// Sends a request and waits for its response, answering keepalives meanwhile.
+    async fn request(&mut self, message_type: u16, body: &[u8], response_type: u16) -> Result<(), String> {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.send(message_type, id, body).await?;
+        let deadline = Instant::now() + RESPONSE_TIMEOUT;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let message = tokio::time::timeout(remaining, self.receive()).await
+                .map_err(|_| format!("UHF reader did not answer LLRP message {}", message_type))??;
+            match message.message_type {
+                MSG_KEEPALIVE => self.send(MSG_KEEPALIVE_ACK, message.id, &[]).await?,
+                MSG_ERROR_MESSAGE => return check_status(&message),
+                t if t == response_type && message.id == id => return check_status(&message),
+                _ => {} // Reports or notifications arriving before the response
+            }
+        }
+    }
+
+    async fn start_inventory(&mut self, config: &AppConfig) -> Result<(), String> {
+        // Remove whatever a previous session (or another client) left behind.
+        self.request(MSG_DELETE_ROSPEC, &0u32.to_be_bytes(), MSG_DELETE_ROSPEC_RESPONSE).await?;
+        self.request(MSG_ADD_ROSPEC, &build_rospec(&config.uhf_antennas), MSG_ADD_ROSPEC_RESPONSE).await?;
+        self.request(MSG_ENABLE_ROSPEC, &ROSPEC_ID.to_be_bytes(), MSG_ENABLE_ROSPEC_RESPONSE).await
+    }
+}
+
+fn handle_report(app_handle: &tauri::AppHandle, uhf_state: &UhfState, config: &AppConfig, body: &[u8]) -> Result<(), String> {
+    for (param_type, value) in parse_params(body)? {
+        if param_type != PARAM_TAG_REPORT_DATA {
+            continue;
+        }
+        let tag = parse_tag_report(value)?;
+        if !uhf_state.accept(&tag, config) {
+            continue;
+        }
+        let read = TruckTagRead {
+            tag_id: tag.epc,
+            antenna_id: tag.antenna_id,
+            rssi: tag.rssi,
+            timestamp: chrono::Local::now().to_rfc3339(),
+        };
+        log::info!("UHF: Truck tag {} (antenna {:?}, RSSI {:?})", read.tag_id, read.antenna_id, read.rssi);
+        if let Ok(mut latest) = uhf_state.latest.lock() {
+            *latest = Some((read.clone(), Instant::now()));
+        }
+        if let Err(e) = app_handle.emit("uhf_tag_read", &read) {
+            log::error!("Failed to emit uhf_tag_read event: {}", e);
+        }
+    }
+    Ok(())
+}
+
+/// Runs one reader session until the connection fails or a reconnect is requested.
+async fn run_session(app_handle: &tauri::AppHandle, config: &AppConfig) -> Result<(), String> {
+    let uhf_state = app_handle.state::<UhfState>();
+    let mut connection = LlrpConnection::connect(&config.uhf_reader_ip, config.uhf_reader_port).await?;
+    connection.start_inventory(config).await?;
+    log::info!("UHF: Inventory running on {}:{}", config.uhf_reader_ip, config.uhf_reader_port);
+
+    loop {
+        let message = tokio::select! {
+            message = connection.receive() => message?,
+            _ = uhf_state.reconnect.notified() => {
+                log::info!("UHF: Reconnecting with new settings");
+                let _ = connection.send(MSG_CLOSE_CONNECTION, 0, &[]).await;
+                return Ok(());
+            }
+        };
+        match message.message_type {
+            MSG_RO_ACCESS_REPORT => {
+                if let Err(e) = handle_report(app_handle, &uhf_state, config, &message.body) {
+                    log::warn!("UHF: Ignoring malformed tag report: {}", e);
+                }
+            }
+            MSG_KEEPALIVE => connection.send(MSG_KEEPALIVE_ACK, message.id, &[]).await?,
+            MSG_READER_EVENT_NOTIFICATION => log::debug!("UHF: Reader event notification"),
+            MSG_ERROR_MESSAGE => check_status(&message)?,
+            other => log::debug!("UHF: Ignoring LLRP message {}", other),
+        }
+    }
+}
+
+pub fn spawn_uhf_reader(app_handle: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let config = app_handle.state::<AppConfigState>().0.lock().ok().map(|c| c.clone());
+            let Some(config) = config else {
+                log::error!("UHF: Failed to acquire config lock");
+                tokio::time::sleep(RECONNECT_DELAY).await;
+                continue;
+            };
+            if !config.uhf_enabled {
+                tokio::time::sleep(DISABLED_RECHECK_INTERVAL).await;
+                continue;
+            }
+            match run_session(&app_handle, &config).await {
+                Ok(()) => continue,
+                Err(e) => log::error!("UHF: {}", e),
+            }
+            tokio::time::sleep(RECONNECT_DELAY).await;
+        }
+    });
+}
+
+#[tauri::command]
+pub fn get_current_truck_tag_command(uhf_state: tauri::State<'_, UhfState>) -> Option<String> {
+    uhf_state.current_tag()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tag(epc: &str, antenna_id: Option<u16>, rssi: Option<i8>) -> RawTagRead {
+        RawTagRead { epc: epc.to_string(), antenna_id, rssi }
+    }
+
+    fn filters(antennas: &[u16], min_rssi: i32) -> AppConfig {
+        AppConfig {
+            uhf_antennas: antennas.to_vec(),
+            uhf_min_rssi: min_rssi,
+            uhf_duplicate_window_secs: 30,
+            ..AppConfig::default()
+        }
+    }
+
+    fn tag_report(epc: &[u8; 12], antenna_id: u16, rssi: i8) -> Vec<u8> {
+        let mut report = vec![0x80 | PARAM_EPC_96 as u8];
+        report.extend_from_slice(epc);
+        report.push(0x80 | PARAM_ANTENNA_ID as u8);
+        report.extend_from_slice(&antenna_id.to_be_bytes());
+        report.extend_from_slice(&[0x80 | PARAM_PEAK_RSSI as u8, rssi as u8]);
+        report
+    }
+
+    #[test]
+    fn params_split_tv_and_tlv_encodings() {
+        let data = [&[0x80 | PARAM_ANTENNA_ID as u8, 0, 3][..], &tlv(PARAM_LLRP_STATUS, &[0, 0, 0, 0])].concat();
+        assert_eq!(parse_params(&data).unwrap(), vec![(PARAM_ANTENNA_ID, &[0, 3][..]), (PARAM_LLRP_STATUS, &[0, 0, 0, 0][..])]);
+        assert!(parse_params(&[0x80 | PARAM_ANTENNA_ID as u8, 0]).is_err(), "truncated TV value");
+        assert!(parse_params(&[0x80 | 0x7f, 0]).is_err(), "unknown TV type");
+        assert!(parse_params(&[0, 1, 0]).is_err(), "truncated TLV header");
+        assert!(parse_params(&[0, 1, 0, 9, 0]).is_err(), "TLV longer than the data");
+        assert!(parse_params(&[0, 1, 0, 3]).is_err(), "TLV shorter than its header");
+    }
+
+    #[test]
+    fn tag_reports_yield_epc_antenna_and_rssi() {
+        let epc = [0xE2, 0x00, 0x34, 0x12, 0x01, 0x23, 0x45, 0x67, 0x89, 0xAB, 0xCD, 0xEF];
+        let tag = parse_tag_report(&tag_report(&epc, 2, -55)).unwrap();
+        assert_eq!((tag.epc.as_str(), tag.antenna_id, tag.rssi), ("E20034120123456789ABCDEF", Some(2), Some(-55)));
+
+        // EPCData carries a bit length; a 64-bit EPC is 8 bytes.
+        let epc_data = tlv(PARAM_EPC_DATA, &[&64u16.to_be_bytes()[..], &epc[..8]].concat());
+        assert_eq!(parse_tag_report(&epc_data).unwrap().epc, "E200341201234567");
+        let truncated = tlv(PARAM_EPC_DATA, &[&96u16.to_be_bytes()[..], &epc[..8]].concat());
+        assert!(parse_tag_report(&truncated).is_err());
+        assert!(parse_tag_report(&[0x80 | PARAM_ANTENNA_ID as u8, 0, 1]).is_err(), "no EPC");
+    }
+
+    #[test]
+    fn rospec_lists_antennas_and_reports_every_tag() {
+        let rospec = build_rospec(&[1, 3]);
+        let params = parse_params(&rospec).unwrap();
+        assert_eq!(params.len(), 1);
+        let (param_type, body) = params[0];
+        assert_eq!(param_type, PARAM_RO_SPEC);
+        assert_eq!(&body[..6], &[0, 0, 0, ROSPEC_ID as u8, 0, 0]);
+
+        let children = parse_params(&body[6..]).unwrap();
+        let types: Vec<u16> = children.iter().map(|(t, _)| *t).collect();
+        assert_eq!(types, vec![PARAM_RO_BOUNDARY_SPEC, PARAM_AI_SPEC, PARAM_RO_REPORT_SPEC]);
+        assert_eq!(&children[1].1[..6], &[0, 2, 0, 1, 0, 3]);
+        assert_eq!(&children[2].1[..3], &[2, 0, 1]);
+
+        let any_antenna = build_rospec(&[]);
+        let any_body = parse_params(&any_antenna).unwrap()[0].1;
+        let ai_spec = parse_params(&any_body[6..]).unwrap()[1].1;
+        assert_eq!(&ai_spec[..4], &[0, 1, 0, 0]);
+    }
+
+    #[test]
+    fn accept_filters_antenna_rssi_and_duplicates() {
+        let state = UhfState::default();
+        let config = filters(&[1, 2], -70);
+        assert!(!state.accept(&tag("A1", Some(3), Some(-40)), &config), "antenna not configured");
+        assert!(!state.accept(&tag("A1", Some(1), Some(-71)), &config), "too weak");
+        assert!(state.accept(&tag("A1", Some(1), Some(-60)), &config));
+        assert!(!state.accept(&tag("A1", Some(2), Some(-50)), &config), "duplicate within the window");
+        assert!(state.accept(&tag("B2", None, None), &config));
+        assert!(state.accept(&tag("C3", Some(9), None), &filters(&[], -70)), "no antenna filter");
+    }
+
+    #[test]
+    fn duplicate_reads_keep_a_parked_truck_current() {
+        let state = UhfState::default();
+        let config = filters(&[], -70);
+        assert!(state.accept(&tag("A1", None, None), &config));
+        assert!(state.accept(&tag("B2", None, None), &config));
+        let stale = Instant::now().checked_sub(TAG_VALIDITY + Duration::from_secs(1)).unwrap();
+        let read = TruckTagRead { tag_id: "A1".to_string(), antenna_id: None, rssi: None, timestamp: String::new() };
+        *state.latest.lock().unwrap() = Some((read, stale));
+        assert_eq!(state.current_tag(), None);
+
+        assert!(!state.accept(&tag("B2", None, None), &config));
+        assert_eq!(state.current_tag(), None, "another tag does not refresh A1");
+        assert!(!state.accept(&tag("A1", None, None), &config));
+        assert_eq!(state.current_tag().as_deref(), Some("A1"));
+
+        state.release_tag("A1");
+        assert!(!state.accept(&tag("A1", None, None), &config));
+        assert_eq!(state.current_tag(), None, "a released tag stays released");
+    }
+}
@@ -0,0 +1,72 @@
+// src-tauri/src/visit_handler.rs
+// Truck visits: when a tag entered the terminal and whether it has left, so an
+// exit lane can tell how long a truck stayed.
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use rusqlite::{params, OptionalExtension};
+
+use crate::db_handler::DatabaseState;
+use crate::soap_services_handler::CMSData;
+
+/// Layouts CGS has been seen to send `truckInTime` in, read as local time.
+const CMS_TIME_FORMATS: &[&str] = &[
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%d/%m/%Y %H:%M:%S",
+    "%d-%m-%Y %H:%M:%S",
+    "%Y%m%d%H%M%S",
+];
+
+/// Parses a truck-in time from CGS or the local journal.
+pub fn parse_truck_in_time(value: &str) -> Option<DateTime<Local>> {
+    let value = value.trim();
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Some(time.with_timezone(&Local));
+    }
+    CMS_TIME_FORMATS.iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .and_then(|naive| Local.from_local_datetime(&naive).earliest())
+}
+
+/// The earliest truck-in time CGS reported for the transaction's containers.
+pub fn truck_in_from_cms(cms_items: &[CMSData]) -> Option<DateTime<Local>> {
+    cms_items.iter()
+        .filter_map(|item| item.truck_in_time.as_deref().and_then(parse_truck_in_time))
+        .min()
+}
+
+/// Records a truck entering through this lane.
+pub fn journal_truck_in(
+    db_state: &DatabaseState,
+    tag_number: &str,
+    transaction_id: &str,
+    gate_name: &str,
+    truck_in_time: DateTime<Local>,
+) -> Result<(), String> {
+    let conn = db_state.0.lock().map_err(|_| "Failed to acquire database lock")?;
+    conn.execute(
+        "INSERT INTO truck_visits (tag_number, transaction_id, gate_name, truck_in_time) VALUES (?1, ?2, ?3, ?4)",
+        params![tag_number, transaction_id, gate_name, truck_in_time.to_rfc3339()],
+    ).map_err(|e| format!("Failed to journal truck-in of {}: {}", tag_number, e))?;
+    Ok(())
+}
+
+/// Truck-in time of the tag's latest visit that has not left yet.
+pub fn open_visit_truck_in(db_state: &DatabaseState, tag_number: &str) -> Result<Option<DateTime<Local>>, String> {
+    let conn = db_state.0.lock().map_err(|_| "Failed to acquire database lock")?;
+    let truck_in: Option<String> = conn.query_row(
+        "SELECT truck_in_time FROM truck_visits WHERE tag_number = ?1 AND truck_out_time IS NULL ORDER BY id DESC LIMIT 1",
+        params![tag_number],
+        |row| row.get(0),
+    ).optional().map_err(|e| e.to_string())?;
+    Ok(truck_in.as_deref().and_then(parse_truck_in_time))
+}
+
+/// Closes every open visit of the tag.
+pub fn journal_truck_out(db_state: &DatabaseState, tag_number: &str, truck_out_time: DateTime<Local>) -> Result<usize, String> {
+    let conn = db_state.0.lock().map_err(|_| "Failed to acquire database lock")?;
+    conn.execute(
+        "UPDATE truck_visits SET truck_out_time = ?1 WHERE tag_number = ?2 AND truck_out_time IS NULL",
+        params![truck_out_time.to_rfc3339(), tag_number],
+    ).map_err(|e| format!("Failed to journal truck-out of {}: {}", tag_number, e))
+}
@@ -8,6 +8,8 @@ import { Button } from "@/components/ui/button";
 import { Card, CardHeader, CardTitle, CardContent, CardFooter } from "@/components/ui/card"; // Added CardContent
 import { Input } from "@/components/ui/input";
 import { Progress } from "@/components/ui/progress";
+import { OperatorLogin, type OperatorSession } from "@/components/OperatorLogin";
+import { ManualOverride } from "@/components/ManualOverride";
 
 // --- Icon Components ---
 const RFIDIcon = () => <img src="@/assets/rfid-icon-white.svg" alt="RFID" className="w-12 h-12 md:w-16 md:h-16 mb-4" />;
@@ -18,10 +20,11 @@ const CheckmarkIcon = () => <img src="@/assets/checkmark-white.svg" alt="Success
 const APP_STATE = {
   DETECTING_RFID: 'DETECTING_RFID',
   VALIDATING_RFID: 'VALIDATING_RFID',
+  AWAITING_QR: 'AWAITING_QR',
+  AWAITING_NEXT_QR: 'AWAITING_NEXT_QR',
+  PROCESSING_GATE_IN: 'PROCESSING_GATE_IN',
   AWAITING_PAYMENT: 'AWAITING_PAYMENT',
   PROCESSING_PAYMENT: 'PROCESSING_PAYMENT',
-  PAYMENT_SUCCESS_AWAIT_QR: 'PAYMENT_SUCCESS_AWAIT_QR',
-  AWAITING_NEXT_QR: 'AWAITING_NEXT_QR',
   PROCESSING_FINAL: 'PROCESSING_FINAL',
   ERROR: 'ERROR',
 } as const;
@@ -32,6 +35,7 @@ interface RFIDData {
   raw: string; 
   main: string; 
   sub: string; 
+  readerId?: string;
 }
 
 interface GatePass { 
@@ -43,7 +47,32 @@ interface GatePass {
 
 interface AppSettings {
   gate_name?: string;
-  emoney_deduct_price?: number;
+}
+
+interface PlateCheck {
+  outcome: 'match' | 'mismatch' | 'no_read' | 'unregistered' | 'disabled';
+  read?: { plate: string; camera_id: string; confidence?: number };
+  registered?: string;
+}
+
+interface ContainerCheck {
+  outcome: 'match' | 'mismatch' | 'no_read' | 'disabled';
+  recognized: { number: string; iso_code?: string; check_digit_valid: boolean }[];
+  issues: { container: string; kind: string; detail: string }[];
+}
+
+interface TariffItem {
+  code: string;
+  description: string;
+  quantity: number;
+  unit_amount: number;
+  amount: number;
+}
+
+interface TariffBreakdown {
+  items: TariffItem[];
+  total: number;
+  dwell_mins?: number;
 }
 
 interface PaymentResultDetails {
@@ -68,11 +97,13 @@ interface CGSTReceiveResult {
   result?: string; 
   transaction_id_str?: string;
   result_cms?: CMSDataItem[];
+  container_check?: ContainerCheck;
 }
 
 interface EventPayload<T = string> {
   message: T;
   data?: any;
+  reader_id?: string;
 }
 
 function App() {
@@ -81,7 +112,13 @@ function App() {
   const [statusBarText, setStatusBarText] = useState("Initializing...");
   const [isErrorStatus, setIsErrorStatus] = useState(false);
   const [rfidData, setRfidData] = useState<RFIDData | null>(null);
-  const [paymentAmount, setPaymentAmount] = useState(0);
+  const [tariff, setTariff] = useState<TariffBreakdown | null>(null);
+  const [plateAlert, setPlateAlert] = useState<string | null>(null);
+  const [containerAlert, setContainerAlert] = useState<string | null>(null);
+  // Supervisor-gated commands take the token of the logged-in operator.
+  const [operatorSession, setOperatorSession] = useState<OperatorSession | null>(null);
+  const [transactionId, setTransactionId] = useState<string | null>(null);
+  const [gateInResult, setGateInResult] = useState<CGSTReceiveResult | null>(null);
   const [scannedGatePasses, setScannedGatePasses] = useState<GatePass[]>([]);
   const [qrInputValue, setQrInputValue] = useState("");
   const qrInputRef = useRef<HTMLInputElement>(null);
@@ -110,6 +147,11 @@ function App() {
     console.log("Resetting app state");
     clearAllTimers();
     setRfidData(null);
+    setTariff(null);
+    setPlateAlert(null);
+    setContainerAlert(null);
+    setTransactionId(null);
+    setGateInResult(null);
     setScannedGatePasses([]);
     setQrInputValue("");
     setCurrentScreen(APP_STATE.DETECTING_RFID);
@@ -120,12 +162,14 @@ function App() {
   // Initial settings load and RFID listener setup
   useEffect(() => {
     let unlistenRfid: Promise<UnlistenFn> | null = null;
+    let unlistenPlate: Promise<UnlistenFn> | null = null;
+    let unlistenContainer: Promise<UnlistenFn> | null = null;
+    let unlistenSessionLocked: Promise<UnlistenFn> | null = null;
 
     async function setup() {
       try {
         const settings: AppSettings = await invoke('get_app_settings');
         setGateName(settings.gate_name || "Unknown Gate"); // << If this fails, gateName remains "Loading..." or becomes "Gate Error"
-        setPaymentAmount(settings.emoney_deduct_price || 17000);
         console.log("App settings loaded:", settings);
         
         updateStatus("Initializing RFID Reader...");
@@ -138,7 +182,19 @@ function App() {
 
         unlistenRfid = listen<EventPayload>('rfid_card_tapped', (event) => { // Make sure EventPayload matches Rust
           console.log("Frontend received rfid_card_tapped:", event.payload.message);
-          handleRfidTap(event.payload.message); // Pass the actual message string
+          handleRfidTap(event.payload.message, event.payload.reader_id); // Pass the actual message string
+        });
+
+        unlistenPlate = listen<PlateCheck>('anpr_plate_mismatch', (event) => {
+          setPlateAlert(`Plate mismatch: camera read ${event.payload.read?.plate ?? "?"}, tag is registered to ${event.payload.registered ?? "?"}`);
+        });
+
+        unlistenSessionLocked = listen<{ username: string; reason: string }>('operator_session_locked', (event) => {
+          setOperatorSession(current => current?.username === event.payload.username ? null : current);
+        });
+
+        unlistenContainer = listen<ContainerCheck>('container_mismatch', (event) => {
+          setContainerAlert(`Container mismatch: ${event.payload.issues.map(issue => issue.detail).join('; ')}`);
         });
 
       } catch (e: any) {
@@ -155,11 +211,20 @@ function App() {
       if (unlistenRfid) {
         unlistenRfid.then(f => f()).catch(console.error); // Ensure unlistenRfid is not null before calling .then
       }
+      if (unlistenPlate) {
+        unlistenPlate.then(f => f()).catch(console.error);
+      }
+      if (unlistenContainer) {
+        unlistenContainer.then(f => f()).catch(console.error);
+      }
+      if (unlistenSessionLocked) {
+        unlistenSessionLocked.then(f => f()).catch(console.error);
+      }
       clearAllTimers();
     };
   }, []); // Empty dependency array is correct for running once on mount
 
-  const handleRfidTap = async (cardRawData: string) => {
+  const handleRfidTap = async (cardRawData: string, readerId?: string) => {
     if (currentScreen === APP_STATE.DETECTING_RFID) {
       setCurrentScreen(APP_STATE.VALIDATING_RFID);
       updateStatus(`Card: ${cardRawData}. Validating...`);
@@ -167,10 +232,16 @@ function App() {
         const validationResult: { status: boolean; message?: string } = await invoke('validate_rfid_card_command', { cardData: cardRawData });
         if (validationResult.status) {
           const parts = cardRawData.split('_');
-          const newRfidData = { raw: cardRawData, main: parts[1] || cardRawData, sub: parts[2] || "" };
+          const newRfidData = { raw: cardRawData, main: parts[1] || cardRawData, sub: parts[2] || "", readerId };
           setRfidData(newRfidData);
-          updateStatus(`RFID Validated: ${newRfidData.main}. Proceed to payment.`);
-          setCurrentScreen(APP_STATE.AWAITING_PAYMENT);
+          // A mismatch comes back as an anpr_plate_mismatch event; it alerts, it does not block.
+          invoke('check_truck_plate_command', { registeredPlate: newRfidData.sub })
+            .catch(e => console.error("Plate check error:", e));
+          setCurrentScreen(APP_STATE.AWAITING_QR);
+          updateStatus(`RFID Validated: ${newRfidData.main}. Scan GatePass QR Code.`);
+          setTimeout(() => {
+            if (qrInputRef.current) qrInputRef.current.focus();
+          }, 100);
         } else {
           updateStatus(`RFID Validation Failed: ${validationResult.message || 'Unknown error'}. Tap card again.`, true);
           setCurrentScreen(APP_STATE.DETECTING_RFID);
@@ -183,24 +254,24 @@ function App() {
     }
   };
 
+  // The backend prices the transaction from what CGS returned at GateIn; only its id is sent.
   const handlePaymentConfirm = async () => {
-    if (!rfidData || currentScreen !== APP_STATE.AWAITING_PAYMENT) return;
+    if (!rfidData || !tariff || !transactionId || currentScreen !== APP_STATE.AWAITING_PAYMENT) return;
 
     setCurrentScreen(APP_STATE.PROCESSING_PAYMENT);
     updateStatus("Processing payment...");
     try {
       const paymentResult: PaymentResultDetails = await invoke('rfid_payment_command', {
         cardData: rfidData.raw,
-        amount: paymentAmount
+        transactionId,
+        readerId: rfidData.readerId
       });
       if (paymentResult.success) {
         updateStatus("Payment successful. Printing slip...");
-        await invoke('print_payment_slip_command', { slipDetails: paymentResult });
-        setCurrentScreen(APP_STATE.PAYMENT_SUCCESS_AWAIT_QR);
-        updateStatus("Payment slip printed. Scan GatePass QR Code.");
-        setTimeout(() => {
-          if (qrInputRef.current) qrInputRef.current.focus();
-        }, 100);
+        // The card is charged by now; a printer fault must not hold the truck at the gate.
+        await invoke('print_payment_slip_command', { slipDetails: paymentResult })
+          .catch(e => console.error("Payment slip print error:", e));
+        await completeTransaction();
       } else {
         updateStatus(`Payment Failed: ${paymentResult.message || 'Unknown error'}. Try again or contact support.`, true);
         setCurrentScreen(APP_STATE.AWAITING_PAYMENT);
@@ -208,6 +279,10 @@ function App() {
     } catch (e: any) {
       updateStatus(`Payment Error: ${e.toString()}. Try again or contact support.`, true);
       console.error("Payment error:", e);
+      // The backend refuses a payment whose price changed since it was shown; show the new one.
+      await invoke<TariffBreakdown>('calculate_tariff_command', { transactionId })
+        .then(setTariff)
+        .catch(err => console.error("Tariff refresh error:", err));
       setCurrentScreen(APP_STATE.AWAITING_PAYMENT);
     }
   };
@@ -220,7 +295,7 @@ function App() {
     qrInputTimeoutRef.current = setTimeout(async () => {
       const capturedQr = newValue.trim().replace(/(\r\n|\n|\r)/gm, "");
       
-      if (capturedQr && (currentScreen === APP_STATE.PAYMENT_SUCCESS_AWAIT_QR || currentScreen === APP_STATE.AWAITING_NEXT_QR)) {
+      if (capturedQr && (currentScreen === APP_STATE.AWAITING_QR || currentScreen === APP_STATE.AWAITING_NEXT_QR)) {
         console.log("QR Value to process:", capturedQr);
         if (qrInputRef.current) {
           qrInputRef.current.value = "";
@@ -254,7 +329,9 @@ function App() {
       return;
     }
     
-    setCurrentScreen(APP_STATE.PROCESSING_FINAL);
+    setCurrentScreen(APP_STATE.PROCESSING_GATE_IN);
+    setFinalProgress(20);
+    setFinalProgressMsg("Sending GateIn to SOAP service...");
     try {
       const gateInData = {
         transaction_id_str: String(Date.now()),
@@ -262,37 +339,54 @@ function App() {
         gate_passes: validGatePasses.map(gp => gp.code),
         gate_name: gateName
       };
+      setTransactionId(gateInData.transaction_id_str);
       
-      setFinalProgress(20);
-      setFinalProgressMsg("Sending GateIn to SOAP service...");
-      const gateInResult: CGSTReceiveResult = await invoke('send_gate_in_command', { data: gateInData });
-
-      if (gateInResult.status && gateInResult.result_cms && gateInResult.result_cms.length > 0) {
-        setFinalProgress(50);
-        setFinalProgressMsg("GateIn successful. Printing CMS...");
-        const cmsPrintPayload = {
-          transaction_id: gateInResult.transaction_id_str || "N/A_CMS",
-          cms_items: gateInResult.result_cms,
-          gate_name: gateName,
-          tag_number: rfidData?.main,
-          tractor_number: rfidData?.sub
-        };
-        await invoke('print_cms_command', { cmsData: cmsPrintPayload });
-        
-        setFinalProgress(75);
-        setFinalProgressMsg("CMS Printed. Sending TruckIn confirmation...");
-        await invoke('send_truck_in_command', { transactionIdStr: gateInResult.transaction_id_str || "N/A_TRUCKIN" });
-        
-        setFinalProgress(90);
-        setFinalProgressMsg("Transaction complete! Opening portal.");
-        await invoke('control_adam_portal_command', { action: "open" });
-        setFinalProgress(100);
-        setFinalProgressMsg("Portal opened. Thank you!");
-        
-        setTimeout(resetAppState, 4000);
-      } else {
-        throw new Error(gateInResult.result || "GateIn processing failed at backend.");
+      const result: CGSTReceiveResult = await invoke('send_gate_in_command', { data: gateInData });
+      if (!result.status || !result.result_cms || result.result_cms.length === 0) {
+        throw new Error(result.result || "GateIn processing failed at backend.");
       }
+      setGateInResult(result);
+
+      // Priced only now, once CGS has returned the containers the tariff rules look at.
+      const breakdown: TariffBreakdown = await invoke('calculate_tariff_command', { transactionId: gateInData.transaction_id_str });
+      setTariff(breakdown);
+      setCurrentScreen(APP_STATE.AWAITING_PAYMENT);
+      updateStatus("GateIn accepted. Proceed to payment.");
+    } catch (e: any) {
+      console.error("GateIn error:", e);
+      updateStatus(`GateIn error: ${e.toString()}`, true);
+      setCurrentScreen(APP_STATE.ERROR);
+    }
+  };
+
+  const completeTransaction = async () => {
+    if (!gateInResult) return;
+
+    setCurrentScreen(APP_STATE.PROCESSING_FINAL);
+    try {
+      setFinalProgress(50);
+      setFinalProgressMsg("Payment complete. Printing CMS...");
+      const cmsPrintPayload = {
+        transaction_id: gateInResult.transaction_id_str || "N/A_CMS",
+        cms_items: gateInResult.result_cms,
+        gate_name: gateName,
+        tag_number: rfidData?.main,
+        tractor_number: rfidData?.sub
+      };
+      await invoke('print_cms_command', { cmsData: cmsPrintPayload });
+      
+      setFinalProgress(75);
+      setFinalProgressMsg("CMS Printed. Sending TruckIn confirmation...");
+      const truckInTransactionId = gateInResult.transaction_id_str || "N/A_TRUCKIN";
+      await invoke('send_truck_in_command', { transactionIdStr: truckInTransactionId });
+      
+      setFinalProgress(90);
+      setFinalProgressMsg("Transaction complete! Opening portal.");
+      await invoke('control_adam_portal_command', { action: "open", transactionId: truckInTransactionId });
+      setFinalProgress(100);
+      setFinalProgressMsg("Portal opened. Thank you!");
+      
+      setTimeout(resetAppState, 4000);
     } catch (e: any) {
       console.error("Final processing error:", e);
       updateStatus(`Final processing error: ${e.toString()}`, true);
@@ -302,7 +396,7 @@ function App() {
 
   // Countdown timer effects
   useEffect(() => {
-    if (currentScreen === APP_STATE.PAYMENT_SUCCESS_AWAIT_QR) {
+    if (currentScreen === APP_STATE.AWAITING_QR) {
       let countdown = 10;
       const updateCountdown = () => {
         const el = document.getElementById('gatepass-countdown');
@@ -315,7 +409,7 @@ function App() {
         updateCountdown();
         if (countdown <= 0) {
           if (gatepassCountdownTimerRef.current) clearInterval(gatepassCountdownTimerRef.current);
-          if (currentScreen === APP_STATE.PAYMENT_SUCCESS_AWAIT_QR) {
+          if (currentScreen === APP_STATE.AWAITING_QR) {
             updateStatus("Gatepass scan timeout. Resetting.", true);
             setTimeout(resetAppState, 1500);
           }
@@ -368,6 +462,18 @@ function App() {
     </div>
   );
 
+  const PlateAlertDisplay = () => plateAlert ? (
+    <div className="plate-alert bg-red-600/90 text-white p-3 rounded-lg mb-6 w-full max-w-md mx-auto text-center font-semibold shadow-md">
+      {plateAlert}
+    </div>
+  ) : null;
+
+  const ContainerAlertDisplay = () => containerAlert ? (
+    <div className="container-alert bg-red-600/90 text-white p-3 rounded-lg mb-6 w-full max-w-md mx-auto text-center font-semibold shadow-md">
+      {containerAlert}
+    </div>
+  ) : null;
+
   const renderCurrentView = () => {
     switch (currentScreen) {
       case APP_STATE.DETECTING_RFID:
@@ -397,8 +503,20 @@ function App() {
               </CardHeader>
               <CardContent className="space-y-5 pt-6">
                 {rfidData && <RFIDInfoDisplay rfidData={rfidData} />}
+                <PlateAlertDisplay />
+                {tariff?.dwell_mins !== undefined && (
+                  <p className="text-base text-center">
+                    Time in terminal: {Math.floor(tariff.dwell_mins / 60)}h {tariff.dwell_mins % 60}m
+                  </p>
+                )}
+                {tariff && tariff.items.map(item => (
+                  <p key={item.code} className="text-base flex justify-between">
+                    <span>{item.description}{item.quantity > 1 ? ` x${item.quantity}` : ""}</span>
+                    <span>{item.amount.toLocaleString('id-ID', { style: 'currency', currency: 'IDR' })}</span>
+                  </p>
+                ))}
                 <p className="text-xl text-center">
-                  Amount: <span className="font-bold">{paymentAmount.toLocaleString('id-ID', { style: 'currency', currency: 'IDR' })}</span>
+                  Amount: <span className="font-bold">{(tariff?.total ?? 0).toLocaleString('id-ID', { style: 'currency', currency: 'IDR' })}</span>
                 </p>
                 <p className="text-lg min-h-[2em] text-center">
                   {currentScreen === APP_STATE.PROCESSING_PAYMENT ? "Processing payment... Please wait." : "Click button below to confirm payment."}
@@ -417,10 +535,11 @@ function App() {
           </div>
         );
         
-      case APP_STATE.PAYMENT_SUCCESS_AWAIT_QR:
+      case APP_STATE.AWAITING_QR:
         return (
           <div className="view-container scan-gatepass-view flex flex-col items-center justify-center min-h-[60vh] text-white">
             {rfidData && <RFIDInfoDisplay rfidData={rfidData} />}
+            <PlateAlertDisplay />
             <Card className="w-full max-w-md bg-white/20 border-white/30 text-white shadow-xl">
               <CardHeader className="items-center pt-6">
                 <QRIcon />
@@ -443,6 +562,7 @@ function App() {
         return (
           <div className="view-container scan-next-gatepass-view flex flex-col items-center justify-center min-h-[60vh] text-white">
             {rfidData && <RFIDInfoDisplay rfidData={rfidData} />}
+            <PlateAlertDisplay />
             
             <Card className="w-full max-w-md bg-purple-800/50 border-purple-400/50 text-white mb-4 shadow-lg">
               <CardHeader className="pb-2 pt-4">
@@ -485,10 +605,12 @@ function App() {
           </div>
         );
         
+      case APP_STATE.PROCESSING_GATE_IN:
       case APP_STATE.PROCESSING_FINAL:
         return (
           <div className="view-container processing-view flex flex-col items-center justify-center min-h-[60vh] text-white">
             <h2 className="text-3xl font-bold mb-6">Processing Transaction</h2>
+            <ContainerAlertDisplay />
             <Progress 
               value={finalProgress} 
               className="w-4/5 h-4 my-6 bg-gray-600"
@@ -502,6 +624,7 @@ function App() {
           <div className="view-container error-view flex flex-col items-center justify-center min-h-[60vh] text-white">
             <h2 className="text-3xl font-bold text-red-400 mb-4">Operation Failed</h2>
             <p className="text-lg mb-6 text-center">{statusBarText}</p>
+            <ContainerAlertDisplay />
             <Button 
               onClick={resetAppState} 
               className="bg-yellow-500 hover:bg-yellow-600 text-black font-semibold py-3 px-6 text-lg"
@@ -525,6 +648,12 @@ function App() {
       <header className="flex items-center justify-between p-4 bg-black/20">
         <img src="/assets/app_logo.png" alt="Logo" className="h-10 md:h-12" />
         <div className="text-xl md:text-2xl text-white font-semibold">{gateName}</div>
+        <div className="flex items-center gap-3">
+          {operatorSession?.role === 'supervisor' && (
+            <ManualOverride sessionToken={operatorSession.token} transactionId={transactionId ?? undefined} tagNumber={rfidData?.main} />
+          )}
+          <OperatorLogin session={operatorSession} onSessionChange={setOperatorSession} />
+        </div>
       </header>
       
       <main className="flex-grow overflow-hidden">
@@ -0,0 +1,127 @@
+import { useState } from "react";
+import { invoke } from "@tauri-apps/api/core";
+
+import { Button } from "@/components/ui/button";
+import { Dialog, DialogContent, DialogDescription, DialogFooter, DialogHeader, DialogTitle } from "@/components/ui/dialog";
+import { Input } from "@/components/ui/input";
+import { Label } from "@/components/ui/label";
+import { Select, SelectContent, SelectItem, SelectTrigger, SelectValue } from "@/components/ui/select";
+import { Textarea } from "@/components/ui/textarea";
+
+type OverrideAction = 'open_portal' | 'force_complete';
+
+const REASON_CODES = [
+  { code: 'EMERGENCY', label: "Emergency" },
+  { code: 'SYSTEM_OUTAGE', label: "System outage" },
+  { code: 'CGS_REJECTION_OVERRIDE', label: "CGS rejection override" },
+  { code: 'EQUIPMENT_FAILURE', label: "Equipment failure" },
+  { code: 'PAYMENT_FAILURE', label: "Payment failure" },
+  { code: 'OTHER', label: "Other" },
+];
+
+interface OverrideRecord {
+  success: boolean;
+  outcome: string;
+}
+
+interface ManualOverrideProps {
+  sessionToken: string;
+  transactionId?: string;
+  tagNumber?: string;
+}
+
+// Supervisor override: opens the portal, or force-completes a transaction CGS rejected.
+export function ManualOverride({ sessionToken, transactionId, tagNumber }: ManualOverrideProps) {
+  const [open, setOpen] = useState(false);
+  const [action, setAction] = useState<OverrideAction>('open_portal');
+  const [reasonCode, setReasonCode] = useState('EMERGENCY');
+  const [reasonText, setReasonText] = useState("");
+  const [overrideTransactionId, setOverrideTransactionId] = useState("");
+  const [result, setResult] = useState<string | null>(null);
+  const [busy, setBusy] = useState(false);
+
+  const openDialog = () => {
+    setAction('open_portal');
+    setReasonText("");
+    setOverrideTransactionId(transactionId ?? "");
+    setResult(null);
+    setOpen(true);
+  };
+
+  const submit = async () => {
+    setBusy(true);
+    setResult(null);
+    try {
+      const record: OverrideRecord = await invoke('manual_override_command', {
+        sessionToken,
+        request: {
+          action,
+          reason_code: reasonCode,
+          reason_text: reasonText,
+          transaction_id: overrideTransactionId || null,
+          tag_number: tagNumber ?? null,
+        },
+      });
+      setResult(record.outcome);
+    } catch (e: any) {
+      setResult(`Override failed: ${e.toString()}`);
+    } finally {
+      setBusy(false);
+    }
+  };
+
+  return (
+    <>
+      <Button variant="outline" size="sm" onClick={openDialog} className="bg-transparent text-white">Manual override</Button>
+
+      <Dialog open={open} onOpenChange={setOpen}>
+        <DialogContent>
+          <DialogHeader>
+            <DialogTitle>Manual override</DialogTitle>
+            <DialogDescription>Every override is audited and reported with your name and reason.</DialogDescription>
+          </DialogHeader>
+          <form
+            className="space-y-4"
+            onSubmit={(e) => { e.preventDefault(); submit(); }}
+          >
+            <div className="space-y-2">
+              <Label>Action</Label>
+              <Select value={action} onValueChange={(value) => setAction(value as OverrideAction)}>
+                <SelectTrigger className="w-full"><SelectValue /></SelectTrigger>
+                <SelectContent>
+                  <SelectItem value="open_portal">Open portal</SelectItem>
+                  <SelectItem value="force_complete">Force-complete transaction</SelectItem>
+                </SelectContent>
+              </Select>
+            </div>
+            {action === 'force_complete' && (
+              <div className="space-y-2">
+                <Label htmlFor="override-transaction">Transaction ID</Label>
+                <Input id="override-transaction" value={overrideTransactionId} onChange={(e) => setOverrideTransactionId(e.target.value)} />
+              </div>
+            )}
+            <div className="space-y-2">
+              <Label>Reason</Label>
+              <Select value={reasonCode} onValueChange={setReasonCode}>
+                <SelectTrigger className="w-full"><SelectValue /></SelectTrigger>
+                <SelectContent>
+                  {REASON_CODES.map(reason => <SelectItem key={reason.code} value={reason.code}>{reason.label}</SelectItem>)}
+                </SelectContent>
+              </Select>
+            </div>
+            <div className="space-y-2">
+              <Label htmlFor="override-reason">Description</Label>
+              <Textarea id="override-reason" value={reasonText} onChange={(e) => setReasonText(e.target.value)} />
+            </div>
+            {result && <p className="text-sm">{result}</p>}
+            <DialogFooter>
+              <Button type="submit" disabled={busy || !reasonText.trim() || (action === 'force_complete' && !overrideTransactionId.trim())}>
+                {busy ? "Please wait..." : "Confirm override"}
+              </Button>
+            </DialogFooter>
+          </form>
+        </DialogContent>
+      </Dialog>
+    </>
+  );
+}
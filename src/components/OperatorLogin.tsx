@@ -0,0 +1,111 @@
+import { useState } from "react";
+import { invoke } from "@tauri-apps/api/core";
+
+import { Button } from "@/components/ui/button";
+import { Dialog, DialogContent, DialogDescription, DialogFooter, DialogHeader, DialogTitle } from "@/components/ui/dialog";
+import { Input } from "@/components/ui/input";
+import { Label } from "@/components/ui/label";
+
+export interface OperatorSession {
+  token: string;
+  username: string;
+  role: 'lane_operator' | 'technician' | 'supervisor';
+  idle_timeout_secs: number;
+}
+
+interface OperatorLoginProps {
+  session: OperatorSession | null;
+  onSessionChange: (session: OperatorSession | null) => void;
+}
+
+// Operator login for the supervisor-gated commands. On a fresh install, with no
+// accounts yet, the same form creates the first supervisor.
+export function OperatorLogin({ session, onSessionChange }: OperatorLoginProps) {
+  const [open, setOpen] = useState(false);
+  const [setupRequired, setSetupRequired] = useState(false);
+  const [username, setUsername] = useState("");
+  const [password, setPassword] = useState("");
+  const [error, setError] = useState<string | null>(null);
+  const [busy, setBusy] = useState(false);
+
+  const openLogin = async () => {
+    setError(null);
+    setPassword("");
+    try {
+      setSetupRequired(await invoke<boolean>('auth_setup_required_command'));
+    } catch (e: any) {
+      console.error("Failed to check operator setup:", e);
+    }
+    setOpen(true);
+  };
+
+  const submit = async () => {
+    setBusy(true);
+    setError(null);
+    try {
+      if (setupRequired) {
+        await invoke('create_operator_command', { sessionToken: null, username, password, role: 'supervisor' });
+      }
+      const newSession: OperatorSession = await invoke('operator_login_command', { username, password });
+      onSessionChange(newSession);
+      setOpen(false);
+      setPassword("");
+    } catch (e: any) {
+      setError(e.toString());
+    } finally {
+      setBusy(false);
+    }
+  };
+
+  const logout = async () => {
+    if (session) {
+      await invoke('operator_logout_command', { sessionToken: session.token }).catch(console.error);
+    }
+    onSessionChange(null);
+  };
+
+  return (
+    <>
+      {session ? (
+        <div className="flex items-center gap-3 text-white text-sm">
+          <span>{session.username} ({session.role.replace('_', ' ')})</span>
+          <Button variant="outline" size="sm" onClick={logout} className="bg-transparent text-white">Log out</Button>
+        </div>
+      ) : (
+        <Button variant="outline" size="sm" onClick={openLogin} className="bg-transparent text-white">Operator login</Button>
+      )}
+
+      <Dialog open={open} onOpenChange={setOpen}>
+        <DialogContent>
+          <DialogHeader>
+            <DialogTitle>{setupRequired ? "Create supervisor account" : "Operator login"}</DialogTitle>
+            <DialogDescription>
+              {setupRequired
+                ? "No operator accounts exist yet. The first account is a supervisor."
+                : "Log in to use supervisor functions on this lane."}
+            </DialogDescription>
+          </DialogHeader>
+          <form
+            className="space-y-4"
+            onSubmit={(e) => { e.preventDefault(); submit(); }}
+          >
+            <div className="space-y-2">
+              <Label htmlFor="operator-username">Username</Label>
+              <Input id="operator-username" value={username} onChange={(e) => setUsername(e.target.value)} autoComplete="username" autoFocus />
+            </div>
+            <div className="space-y-2">
+              <Label htmlFor="operator-password">Password</Label>
+              <Input id="operator-password" type="password" value={password} onChange={(e) => setPassword(e.target.value)} autoComplete="current-password" />
+            </div>
+            {error && <p className="text-sm text-red-600">{error}</p>}
+            <DialogFooter>
+              <Button type="submit" disabled={busy || !username || !password}>
+                {busy ? "Please wait..." : setupRequired ? "Create and log in" : "Log in"}
+              </Button>
+            </DialogFooter>
+          </form>
+        </DialogContent>
+      </Dialog>
+    </>
+  );
+}